@@ -14,6 +14,10 @@ pub enum Command {
     },
     /// Set the program to None.
     UnsetProgram { channel: u8 },
+    /// Release and replay every note that is sounding on a channel because of user input. This is used after a program change so that held notes use the new program.
+    RetriggerActiveNotes { channel: u8 },
     /// Set the overall gain.
     SetGain { gain: u8 },
+    /// Stop exporting. No more files will be written.
+    StopExport,
 }
@@ -1,22 +1,44 @@
 use crate::decayer::Decayer;
-use crate::export::{ExportState, ExportType, Exportable, MultiFileSuffix};
-use crate::exporter::Exporter;
+use crate::export::{
+    ExportJob, ExportState, ExportType, Exportable, MultiFileSuffix, Normalization, PostExport,
+};
+use crate::exporter::{Exporter, CHANNEL_VOLUME_CC};
+use crate::frozen_audio::{read_frozen_audio, write_frozen_audio, FrozenAudio};
+use crate::gain_ramp::GainRamp;
+use crate::limiter::Limiter;
+use crate::loudness::{normalize, normalize_peak};
 use crate::play_state::PlayState;
-use crate::types::SharedPlayState;
+use crate::types::{
+    SharedExportCancelled, SharedExportFile, SharedFrozenAudio, SharedMonitorGain,
+    SharedMonoMonitor, SharedPlayState, SharedPostExportResult,
+};
 use crate::SharedExportState;
 use crate::{
-    midi_event_queue::MidiEventQueue, types::SharedSample, Command, Player, Program,
-    SharedMidiEventQueue, SharedSynth, SynthState,
+    midi_event_queue::MidiEventQueue, types::SharedSample, AudioBuffer, Command, ConnSettings,
+    Player, Program, SharedGainRamp, SharedMidiEventQueue, SharedSynth, SynthState,
 };
 use common::open_file::Extension;
-use common::{MidiTrack, Music, PathsState, State, Time, MAX_VOLUME};
+use common::{
+    MidiTrack, Music, Note, PathsState, State, Ties, Time, DEFAULT_CHANNEL_VOLUME, MAX_VOLUME,
+};
 use hashbrown::HashMap;
 use oxisynth::{MidiEvent, SoundFont, SoundFontId, Synth};
 use parking_lot::Mutex;
 use std::fs::File;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread::spawn;
+use std::time::Duration;
+
+/// Previewed notes are released after this long.
+const PREVIEW_DURATION: Duration = Duration::from_millis(250);
+/// The minimum monitor gain in dB.
+const MIN_MONITOR_GAIN_DB: i8 = -30;
+/// The maximum monitor gain in dB.
+const MAX_MONITOR_GAIN_DB: i8 = 12;
+/// When estimating the length of an export, assume that each exportable has a decay tail this long.
+const ESTIMATED_DECAY_SECONDS: f32 = 1.0;
 
 /// A convenient wrapper for a SoundFont.
 struct SoundFontBanks {
@@ -46,8 +68,14 @@ impl SoundFontBanks {
 pub struct Conn {
     /// The current export state, if any.
     pub export_state: SharedExportState,
+    /// If true, the export thread stops as soon as possible. See `Command::StopExport`.
+    export_cancelled: SharedExportCancelled,
+    /// The index of the file that is being exported, which is also the number of files that have been written, and the total number of files. See `get_export_file`.
+    export_file: SharedExportFile,
     /// The playback framerate.
     pub framerate: f32,
+    /// The name of the audio output device, if any.
+    pub output_device: Option<String>,
     /// The audio player. This is here so we don't drop it.
     _player: Option<Player>,
     /// The most recent sample.
@@ -57,6 +85,10 @@ pub struct Conn {
     /// The `Conn` uses this to send MIDI events and export.
     /// The `Player` uses this to write samples to the output buffer.
     synth: SharedSynth,
+    /// The synthesizer's gain ramp.
+    /// The `Conn` sets the target gain.
+    /// The `Player` interpolates the synthesizer's gain toward the target.
+    gain_ramp: SharedGainRamp,
     /// A queue of scheduled MIDI events.
     /// The `Conn` can add to this.
     /// The `Player` can read this and remove events.
@@ -69,65 +101,145 @@ pub struct Conn {
     pub exporter: Exporter,
     /// A flag that `Player` uses to decide how to write samples to the output buffer.
     pub play_state: SharedPlayState,
+    /// The notes that are being previewed, if any: The channel, the keys, and the time at which the preview started.
+    /// The time is None until the next call to `update_preview`.
+    preview: Option<(u8, Vec<u8>, Option<Duration>)>,
+    /// If `exporter.limiter` is true, exported samples never exceed this value in dBFS.
+    limiter_ceiling: f32,
+    /// The index of the track that is temporarily soloed during playback, if any.
+    /// Unlike `MidiTrack.solo`, this isn't part of the music and only lasts while the user holds down a key.
+    audition: Option<usize>,
+    /// A command that runs once per exported file after each export, if any.
+    post_export: Option<PostExport>,
+    /// The result of the most recent post-export command.
+    post_export_result: SharedPostExportResult,
+    /// The monitor gain in dB. See `set_monitor_gain`.
+    monitor_gain_db: i8,
+    /// The linear monitor gain that `Player` applies to the output.
+    monitor_gain: SharedMonitorGain,
+    /// If true, `Player` sums the output to mono. See `set_mono_monitor`.
+    mono_monitor: SharedMonoMonitor,
+    /// If there aren't any notes to export, each exported file is this many seconds of silence. If this is 0, the export doesn't start.
+    empty_export_silence: f32,
+    /// The synthesizer gain when the music gain is `MAX_VOLUME`. The music gain is scaled into this range.
+    /// Many loud voices can clip inside the synthesizer; lower values reduce the chance of that at the cost of some level.
+    synth_headroom: f32,
+    /// The audio that `Player` plays instead of the notes of frozen tracks.
+    frozen_audio: SharedFrozenAudio,
+    /// Frozen audio that has been read from disk. Key = The path to the .wav file.
+    frozen_audio_buffers: HashMap<PathBuf, Arc<AudioBuffer>>,
+    /// The notes that are sounding because of user input. Key = The channel. Value = The key and velocity of each note.
+    sounding_notes: HashMap<u8, Vec<(u8, u8)>>,
 }
 
 impl Default for Conn {
     fn default() -> Self {
+        Self::new(ConnSettings::default())
+    }
+}
+
+impl Conn {
+    /// - `settings` The audio output, playback, and export settings. See `ConnSettings`.
+    pub fn new(settings: ConnSettings) -> Self {
+        let ConnSettings {
+            output_device,
+            gain_ramp_duration,
+            limiter_ceiling,
+            post_export,
+            empty_export_silence,
+            synth_headroom,
+        } = settings;
+        let synth_headroom = synth_headroom.clamp(0.0, 1.0);
         // Set the synthesizer.
         let mut synth = Synth::default();
-        synth.set_gain(1.0);
+        synth.set_gain(synth_headroom);
         let synth = Arc::new(Mutex::new(synth));
 
         // Create other shared data.
         let midi_event_queue = Arc::new(Mutex::new(MidiEventQueue::default()));
         let sample = Arc::new(Mutex::new((0.0, 0.0)));
         let play_state = Arc::new(Mutex::new(PlayState::NotPlaying));
+        let gain_ramp = Arc::new(Mutex::new(GainRamp::new(synth_headroom)));
+        let monitor_gain = Arc::new(Mutex::new(1.0));
+        let mono_monitor = Arc::new(Mutex::new(false));
+        let frozen_audio = Arc::new(Mutex::new(FrozenAudio::default()));
 
         // Create the player.
         let player_synth = Arc::clone(&synth);
         let player_midi_event_queue = Arc::clone(&midi_event_queue);
         let player_sample = Arc::clone(&sample);
         let player_play_state = Arc::clone(&play_state);
+        let player_gain_ramp = Arc::clone(&gain_ramp);
+        let player_monitor_gain = Arc::clone(&monitor_gain);
+        let player_mono_monitor = Arc::clone(&mono_monitor);
+        let player_frozen_audio = Arc::clone(&frozen_audio);
         let player = Player::new(
+            output_device.as_deref(),
             player_midi_event_queue,
             player_synth,
             player_sample,
             player_play_state,
+            player_gain_ramp,
+            player_monitor_gain,
+            player_mono_monitor,
+            player_frozen_audio,
         );
 
-        // Get the framerate.
-        let framerate = match &player {
-            Some(player) => player.framerate as f32,
-            None => 0.0,
+        // Get the framerate and the device name.
+        let (framerate, output_device) = match &player {
+            Some(player) => (player.framerate as f32, Some(player.device_name.clone())),
+            None => (0.0, None),
         };
-        Self {
+        gain_ramp.lock().set_length(gain_ramp_duration, framerate);
+        let conn = Self {
             export_state: Arc::new(Mutex::new(ExportState::NotExporting)),
+            export_cancelled: Arc::new(AtomicBool::new(false)),
+            export_file: Arc::new(Mutex::new((0, 0))),
             _player: player,
             framerate,
+            output_device,
             sample,
             synth,
+            gain_ramp,
             midi_event_queue,
             soundfonts: HashMap::default(),
             state: SynthState::default(),
             exporter: Exporter::default(),
             play_state,
-        }
+            preview: None,
+            limiter_ceiling,
+            audition: None,
+            post_export,
+            post_export_result: Arc::new(Mutex::new(None)),
+            monitor_gain_db: 0,
+            monitor_gain,
+            mono_monitor,
+            empty_export_silence,
+            synth_headroom,
+            frozen_audio,
+            frozen_audio_buffers: HashMap::default(),
+            sounding_notes: HashMap::default(),
+        };
+        conn.set_player_framerate();
+        conn
     }
-}
 
-impl Conn {
     /// Do all note-on events created by user input on this app frame.
     pub fn note_ons(&mut self, state: &State, note_ons: &[[u8; 3]]) {
         if let Some(track) = state.music.get_selected_track() {
             if !note_ons.is_empty() {
                 let mut synth = self.synth.lock();
                 let gain = track.gain as f32 / MAX_VOLUME as f32;
+                let sounding_notes = self.sounding_notes.entry(track.channel).or_default();
                 for note_on in note_ons.iter() {
+                    let vel = (note_on[2] as f32 * gain) as u8;
                     let _ = synth.send_event(MidiEvent::NoteOn {
                         channel: track.channel,
                         key: note_on[1],
-                        vel: (note_on[2] as f32 * gain) as u8,
+                        vel,
                     });
+                    sounding_notes.retain(|n| n.0 != note_on[1]);
+                    sounding_notes.push((note_on[1], vel));
                 }
                 // Play audio.
                 let mut play_state = self.play_state.lock();
@@ -147,6 +259,66 @@ impl Conn {
                         key: *note_off,
                     });
                 }
+                if let Some(sounding_notes) = self.sounding_notes.get_mut(&track.channel) {
+                    sounding_notes.retain(|n| !note_offs.contains(&n.0));
+                }
+            }
+        }
+    }
+
+    /// Briefly play a note on the selected track's channel.
+    /// Only one note is previewed at a time, so rapid navigation doesn't stack overlapping notes.
+    /// This does nothing if music is playing or if we're exporting.
+    pub fn preview_note(&mut self, state: &State, note: &Note) {
+        self.preview_notes(state, &[(note.note, note.velocity)]);
+    }
+
+    /// Briefly play notes on the selected track's channel. Any notes that are still being previewed are released first.
+    /// This does nothing if music is playing or if we're exporting.
+    ///
+    /// - `notes` The key and velocity of each note.
+    pub fn preview_notes(&mut self, state: &State, notes: &[(u8, u8)]) {
+        if notes.is_empty() || self.exporting() {
+            return;
+        }
+        if let PlayState::Playing(_) = *self.play_state.lock() {
+            return;
+        }
+        if let Some(track) = state.music.get_selected_track() {
+            if self.state.programs.get(&track.channel).is_none() {
+                return;
+            }
+            // Release the previous preview.
+            self.end_preview();
+            let gain = track.gain as f32 / MAX_VOLUME as f32;
+            let mut synth = self.synth.lock();
+            for (key, velocity) in notes.iter() {
+                let _ = synth.send_event(MidiEvent::NoteOn {
+                    channel: track.channel,
+                    key: *key,
+                    vel: (*velocity as f32 * gain) as u8,
+                });
+            }
+            drop(synth);
+            let keys = notes.iter().map(|(key, _)| *key).collect();
+            self.preview = Some((track.channel, keys, None));
+            // Play audio.
+            *self.play_state.lock() = PlayState::Decaying;
+        }
+    }
+
+    /// Release the previewed notes if they have been playing for long enough. Call this once per frame.
+    ///
+    /// - `now` The current time. The preview is timed from the first call after it started.
+    pub fn update_preview(&mut self, now: Duration) {
+        if let Some((_, _, t0)) = &mut self.preview {
+            match t0 {
+                Some(t0) => {
+                    if now.saturating_sub(*t0) >= PREVIEW_DURATION {
+                        self.end_preview();
+                    }
+                }
+                None => *t0 = Some(now),
             }
         }
     }
@@ -206,63 +378,350 @@ impl Conn {
                 Command::UnsetProgram { channel } => {
                     self.state.programs.remove(channel);
                 }
+                Command::RetriggerActiveNotes { channel } => {
+                    if let Some(sounding_notes) = self.sounding_notes.get(channel) {
+                        let mut synth = self.synth.lock();
+                        for (key, vel) in sounding_notes.iter() {
+                            let _ = synth.send_event(MidiEvent::NoteOff {
+                                channel: *channel,
+                                key: *key,
+                            });
+                            let _ = synth.send_event(MidiEvent::NoteOn {
+                                channel: *channel,
+                                key: *key,
+                                vel: *vel,
+                            });
+                        }
+                    }
+                }
                 Command::SetGain { gain } => {
-                    let mut synth = self.synth.lock();
-                    synth.set_gain(*gain as f32 / MAX_VOLUME as f32);
+                    // Don't ramp if nothing is being played.
+                    let idle = self.exporting() || *self.play_state.lock() == PlayState::NotPlaying;
+                    let g = {
+                        let mut gain_ramp = self.gain_ramp.lock();
+                        gain_ramp
+                            .set_target(*gain as f32 / MAX_VOLUME as f32 * self.synth_headroom);
+                        if idle {
+                            Some(gain_ramp.finish())
+                        } else {
+                            None
+                        }
+                    };
+                    if let Some(g) = g {
+                        self.synth.lock().set_gain(g);
+                    }
                     self.state.gain = *gain;
                 }
+                Command::StopExport => {
+                    if self.exporting() {
+                        self.export_cancelled.store(true, Ordering::Relaxed);
+                    }
+                }
             }
         }
     }
 
     /// Start to play music if music isn't playing. Stop music if music is playing.
     pub fn set_music(&mut self, state: &State) {
+        self.set_music_from(state, state.time.playback);
+    }
+
+    /// Start to play music from time `t0` if music isn't playing. Stop music if music is playing.
+    ///
+    /// - `state` The app state.
+    /// - `t0` The start time in PPQ. Notes that start before this time won't be played.
+    pub fn set_music_from(&mut self, state: &State, t0: u64) {
         let play_state = *self.play_state.lock();
         match play_state {
-            PlayState::NotPlaying => self.start_music(state),
+            PlayState::NotPlaying => self.start_music(state, t0),
             _ => self.stop_music(&state.music),
         }
     }
 
+    /// Stop any ongoing music and play music from time `t0`.
+    ///
+    /// - `state` The app state.
+    /// - `t0` The start time in PPQ. Notes that start before this time won't be played.
+    pub fn restart_music_from(&mut self, state: &State, t0: u64) {
+        if *self.play_state.lock() != PlayState::NotPlaying {
+            self.stop_music(&state.music);
+        }
+        self.start_music(state, t0);
+    }
+
+    /// Release the previewed notes, if any.
+    fn end_preview(&mut self) {
+        if let Some((channel, keys, _)) = self.preview.take() {
+            let mut synth = self.synth.lock();
+            for key in keys {
+                let _ = synth.send_event(MidiEvent::NoteOff { channel, key });
+            }
+        }
+    }
+
+    /// If music is playing and looping is enabled, play from the start of the loop region when playback reaches its end.
+    /// Call this once per frame.
+    pub fn update_loop(&mut self, state: &State) {
+        if !state.time.looping || state.time.loop_end <= state.time.loop_start {
+            return;
+        }
+        if let Some(t) = self.playback_position_ppq(&state.time) {
+            if t >= state.time.loop_end {
+                self.stop_music(&state.music);
+                self.start_music(state, state.time.loop_start);
+            }
+        }
+    }
+
+    /// Temporarily solo the selected track during playback, or resume normal playback.
+    /// This doesn't change the tracks' mute and solo values. It does nothing if music isn't playing.
+    /// Call this once per frame.
+    ///
+    /// Playback restarts from the current position with the new set of tracks, so notes that are already sounding are cut off.
+    ///
+    /// - `state` The app state.
+    /// - `audition` If true, only the selected track is heard.
+    pub fn set_audition(&mut self, state: &State, audition: bool) {
+        let track = if audition { state.music.selected } else { None };
+        match self.playback_position_ppq(&state.time) {
+            Some(t) => {
+                if track != self.audition {
+                    self.stop_music(&state.music);
+                    self.audition = track;
+                    self.start_music(state, t);
+                }
+            }
+            None => self.audition = None,
+        }
+    }
+
+    /// Set the monitor gain.
+    ///
+    /// The monitor gain is applied to what the user hears but not to exported audio, and it isn't saved.
+    /// Unlike the music gain, it can't accidentally make an export louder or quieter.
+    ///
+    /// - `db` The gain in dB. This will be clamped between `MIN_MONITOR_GAIN_DB` and `MAX_MONITOR_GAIN_DB`.
+    pub fn set_monitor_gain(&mut self, db: i8) {
+        self.monitor_gain_db = db.clamp(MIN_MONITOR_GAIN_DB, MAX_MONITOR_GAIN_DB);
+        *self.monitor_gain.lock() = 10f32.powf(self.monitor_gain_db as f32 / 20.0);
+    }
+
+    /// Returns the monitor gain in dB.
+    pub fn get_monitor_gain(&self) -> i8 {
+        self.monitor_gain_db
+    }
+
+    /// Sum the left and right channels of what the user hears, for example to check mono compatibility.
+    ///
+    /// Like the monitor gain, this isn't applied to exported audio and it isn't saved.
+    pub fn set_mono_monitor(&mut self, mono: bool) {
+        *self.mono_monitor.lock() = mono;
+    }
+
+    /// Returns true if what the user hears is summed to mono.
+    pub fn get_mono_monitor(&self) -> bool {
+        *self.mono_monitor.lock()
+    }
+
+    /// Returns the current playback time in PPQ, or None if music isn't playing.
+    ///
+    /// - `time` The time state. This is used to convert samples to PPQ.
+    pub fn playback_position_ppq(&self, time: &Time) -> Option<u64> {
+        match *self.play_state.lock() {
+            PlayState::Playing(samples) => Some(time.samples_to_ppq(samples, self.framerate)),
+            _ => None,
+        }
+    }
+
     pub fn exporting(&self) -> bool {
         *self.export_state.lock() != ExportState::NotExporting
     }
 
+    /// Returns the index of the file that is being exported and the total number of files.
+    /// Returns None if we're not exporting or if only one file is being exported.
+    pub fn get_export_file(&self) -> Option<(usize, usize)> {
+        let (index, num_files) = *self.export_file.lock();
+        if self.exporting() && num_files > 1 {
+            Some((index, num_files))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of files that have been written during the current or most recent export, and the total number of files.
+    pub fn get_exported_files(&self) -> (usize, usize) {
+        *self.export_file.lock()
+    }
+
+    /// Returns the progress of the whole export, between 0 and 1, across all of the files.
+    /// Returns None if we're not exporting.
+    pub fn get_export_progress(&self) -> Option<f32> {
+        let (index, num_files) = self.get_exported_files();
+        self.export_state
+            .lock()
+            .get_total_progress(index, num_files)
+    }
+
+    /// Set the synthesizer's sample rate to the playback framerate.
+    ///
+    /// Live playback and previewed notes always use the playback framerate, which is set by the audio device.
+    /// The exporter's framerate, which might be different and is stored in save files, is only used while exporting.
+    pub fn set_player_framerate(&self) {
+        // There is no audio device.
+        if self.framerate > 0.0 {
+            self.synth.lock().set_sample_rate(self.framerate);
+        }
+    }
+
+    /// Render a track to a .wav file so that it can be frozen. Returns false if the track can't be rendered.
+    ///
+    /// During live playback, if a track's `frozen_audio` is a path to this file, the file is played instead of the track's notes. This doesn't affect exports.
+    /// The audio is rendered at the playback framerate and doesn't change if the track's notes, gain, or program change.
+    ///
+    /// Rendering happens on this thread, so long tracks will block the app until they're done.
+    ///
+    /// - `state` The app state.
+    /// - `index` The index of the track.
+    /// - `path` Write the audio to this path.
+    pub fn freeze_track(&mut self, state: &State, index: usize, path: &Path) -> bool {
+        let track = match state.music.midi_tracks.get(index) {
+            Some(track) => track,
+            None => return false,
+        };
+        // There's nothing to render, or there isn't an audio device.
+        if track.notes.is_empty()
+            || !self.state.programs.contains_key(&track.channel)
+            || self.framerate <= 0.0
+            || self.exporting()
+            || matches!(*self.play_state.lock(), PlayState::Playing(_))
+        {
+            return false;
+        }
+        self.reset_channel_volumes(&state.music);
+        let mut events = MidiEventQueue::default();
+        let mut t1 = 0;
+        self.enqueue_track_events(
+            track,
+            &state.time,
+            &mut events,
+            &mut t1,
+            track.get_gain_f(),
+            None,
+            self.framerate,
+        );
+        events.sort();
+        let mut left = vec![0.0f32; t1 as usize];
+        let mut right = vec![0.0f32; t1 as usize];
+        let mut synth = self.synth.lock();
+        // Render at a gain of 1. `Player` applies the synthesizer's gain to the frozen audio.
+        let gain = synth.gain();
+        synth.set_gain(1.0);
+        for t in 0..t1 {
+            for event in events.dequeue(t).iter() {
+                let _ = synth.send_event(*event);
+            }
+            let t = t as usize;
+            (left[t], right[t]) = synth.read_next();
+        }
+        // Append decaying silence.
+        let mut decayer = Decayer::default();
+        decayer.decaying = true;
+        while decayer.decaying {
+            decayer.decay_two_channels(&mut left, &mut right, &mut synth);
+        }
+        synth.set_gain(gain);
+        drop(synth);
+        let audio = [left, right];
+        if write_frozen_audio(path, self.framerate as u32, &audio) {
+            self.frozen_audio_buffers
+                .insert(path.to_path_buf(), Arc::new(audio));
+            true
+        } else {
+            false
+        }
+    }
+
     /// Schedule MIDI events and start to play music.
-    fn start_music(&mut self, state: &State) {
+    fn start_music(&mut self, state: &State, t0: u64) {
         // Get the start time.
-        let start = state
-            .time
-            .ppq_to_samples(state.time.playback, self.framerate);
+        let start = state.time.ppq_to_samples(t0, self.framerate);
 
         // Set the playback framerate.
-        let mut synth = self.synth.lock();
-        synth.set_sample_rate(self.framerate);
-        drop(synth);
+        self.set_player_framerate();
+        self.reset_channel_volumes(&state.music);
+
+        // Get the notes. If a track is being auditioned, only play its notes.
+        let mut notes: Vec<(&MidiTrack, &Note)> = match self
+            .audition
+            .and_then(|index| state.music.midi_tracks.get(index))
+        {
+            Some(track) => track.notes.iter().map(|note| (track, note)).collect(),
+            None => state.music.iter_notes_sorted().collect(),
+        };
+
+        // Play the audio of frozen tracks instead of their notes.
+        let mut frozen_channels = vec![];
+        let mut frozen_buffers = vec![];
+        for (track, _) in notes.iter() {
+            if !frozen_channels.contains(&track.channel) {
+                if let Some(buffer) = self.get_frozen_audio(track) {
+                    frozen_channels.push(track.channel);
+                    frozen_buffers.push(buffer);
+                }
+            }
+        }
+        notes.retain(|(track, _)| !frozen_channels.contains(&track.channel));
+        *self.frozen_audio.lock() = FrozenAudio::new(frozen_buffers);
 
         // Enqueue note events.
         let mut midi_event_queue = self.midi_event_queue.lock();
-        for track in state.music.get_playable_tracks().iter() {
-            for note in track.get_playback_notes(state.time.playback) {
-                // Note-on event.
-                midi_event_queue.enqueue(
-                    state.time.ppq_to_samples(note.start, self.framerate),
-                    MidiEvent::NoteOn {
-                        channel: track.channel,
-                        key: note.note,
-                        vel: note.velocity,
-                    },
-                );
-                // Note-off event.
-                midi_event_queue.enqueue(
-                    state.time.ppq_to_samples(note.end, self.framerate),
-                    MidiEvent::NoteOff {
-                        channel: track.channel,
-                        key: note.note,
-                    },
+        // Enqueue volume envelope events.
+        let mut channels = vec![];
+        for (track, _) in notes.iter() {
+            if !channels.contains(&track.channel) {
+                channels.push(track.channel);
+                Self::enqueue_volume_events(
+                    track,
+                    &state.time,
+                    &mut midi_event_queue,
+                    (t0, u64::MAX),
+                    0,
+                    self.framerate,
                 );
             }
         }
+        // Key = The channel. Value = The track's tied notes.
+        let mut ties: HashMap<u8, Ties> = HashMap::new();
+        for (track, note) in notes.into_iter().filter(|(_, n)| n.start >= t0) {
+            // Join tied notes. This removes the note-off and note-on between them.
+            let track_ties = ties
+                .entry(track.channel)
+                .or_insert_with(|| Ties::new(&track.notes));
+            let note = match note.get_tied(track_ties, t0) {
+                Some(note) => note,
+                None => continue,
+            };
+            // Apply the articulation.
+            let note = note.get_articulated(&track.notes);
+            // Note-on event.
+            midi_event_queue.enqueue(
+                state.time.ppq_to_samples(note.start, self.framerate),
+                MidiEvent::NoteOn {
+                    channel: track.channel,
+                    key: track.note_range.apply(note.note),
+                    vel: (self.exporter.get_floored_velocity(note.velocity) as f32
+                        * track.get_gain_f()) as u8,
+                },
+            );
+            // Note-off event.
+            midi_event_queue.enqueue(
+                state.time.ppq_to_samples(note.end, self.framerate),
+                MidiEvent::NoteOff {
+                    channel: track.channel,
+                    key: track.note_range.apply(note.note),
+                },
+            );
+        }
         // Sort the events by start time.
         midi_event_queue.sort();
         drop(midi_event_queue);
@@ -289,11 +748,80 @@ impl Conn {
             {}
         }
         drop(synth);
+        // Remove any scheduled events.
+        self.midi_event_queue.lock().clear();
+        self.frozen_audio.lock().clear();
         // Let the audio decay.
         let mut play_state = self.play_state.lock();
         *play_state = PlayState::Decaying;
     }
 
+    /// Silence any music that is still playing or decaying, without letting it decay.
+    /// This is called before an export so that lingering voices don't bleed into the start of the exported audio.
+    fn finish_decay(&mut self, music: &Music) {
+        if *self.play_state.lock() == PlayState::NotPlaying {
+            return;
+        }
+        // Send all-notes-off and all-sound-off, which stops the voices immediately.
+        self.stop_music(music);
+        *self.play_state.lock() = PlayState::NotPlaying;
+    }
+
+    /// Returns the audio of a frozen track, or None if the track isn't frozen or if its audio can't be read.
+    fn get_frozen_audio(&mut self, track: &MidiTrack) -> Option<Arc<AudioBuffer>> {
+        let path = track.frozen_audio.as_ref()?;
+        if !self.frozen_audio_buffers.contains_key(path) {
+            let audio = read_frozen_audio(path, self.framerate as u32)?;
+            self.frozen_audio_buffers
+                .insert(path.clone(), Arc::new(audio));
+        }
+        self.frozen_audio_buffers.get(path).cloned()
+    }
+
+    /// Set the volume of each track's channel to the default value, in case a volume envelope changed it.
+    fn reset_channel_volumes(&self, music: &Music) {
+        let mut synth = self.synth.lock();
+        for track in music.midi_tracks.iter() {
+            let _ = synth.send_event(MidiEvent::ControlChange {
+                channel: track.channel,
+                ctrl: CHANNEL_VOLUME_CC,
+                value: DEFAULT_CHANNEL_VOLUME,
+            });
+        }
+    }
+
+    /// Enqueue the events of a track's volume envelope. See `MidiTrack::get_volume_events`.
+    ///
+    /// - `track` The track.
+    /// - `time` This is used to convert PPQ to samples.
+    /// - `events` Add the events to this queue.
+    /// - `(t0, t1)` The start and end times in PPQ. Events at or after `t1` aren't enqueued.
+    /// - `offset` Subtract this many samples from the time of each event.
+    /// - `framerate` The framerate that is used to convert PPQ to samples.
+    fn enqueue_volume_events(
+        track: &MidiTrack,
+        time: &Time,
+        events: &mut MidiEventQueue,
+        (t0, t1): (u64, u64),
+        offset: u64,
+        framerate: f32,
+    ) {
+        for (t, value) in track
+            .get_volume_events(t0)
+            .into_iter()
+            .filter(|(t, _)| *t < t1)
+        {
+            events.enqueue(
+                time.ppq_to_samples(t, framerate) - offset,
+                MidiEvent::ControlChange {
+                    channel: track.channel,
+                    ctrl: CHANNEL_VOLUME_CC,
+                    value,
+                },
+            );
+        }
+    }
+
     /// Set the synthesizer program to a default program.
     fn set_program_default(&mut self, channel: u8, path: &Path) {
         let soundfont = &self.soundfonts[path];
@@ -337,10 +865,121 @@ impl Conn {
         }
     }
 
-    pub fn start_export(&mut self, state: &State, paths_state: &PathsState) {
-        let mut exportables = vec![];
-        let tracks = state.music.get_playable_tracks();
+    /// Returns true if music without any notes is exported as silence. See `empty_export_silence`.
+    pub fn exports_silence(&self) -> bool {
+        self.empty_export_silence > 0.0
+    }
+
+    /// Start to export audio in a separate thread.
+    ///
+    /// Returns false if there is nothing to export. See `get_exportables_or_silence`.
+    pub fn start_export(&mut self, state: &State, paths_state: &PathsState) -> bool {
+        let exportables = match self.get_exportables_or_silence(state) {
+            Some(exportables) => exportables,
+            None => return false,
+        };
+        self.finish_decay(&state.music);
         self.set_export_framerate();
+        self.reset_channel_volumes(&state.music);
+
+        // Don't ramp the gain while exporting.
+        let gain = self.gain_ramp.lock().finish();
+        self.synth.lock().set_gain(gain);
+
+        self.export_cancelled.store(false, Ordering::Relaxed);
+        *self.export_file.lock() = (0, exportables.len());
+        // Set the export state now so that `exporting()` is true before the export thread starts.
+        Self::set_export_state_wav(&exportables[0], &self.export_state, 0);
+        let export_state = Arc::clone(&self.export_state);
+        let export_cancelled = Arc::clone(&self.export_cancelled);
+        let export_file = Arc::clone(&self.export_file);
+        let synth = Arc::clone(&self.synth);
+        let exporter = self.exporter.clone();
+        let path = paths_state.exports.get_path();
+        let player_framerate = self.framerate;
+        let limiter_ceiling = self.limiter_ceiling;
+        let post_export = self.post_export.clone();
+        let post_export_result = Arc::clone(&self.post_export_result);
+        spawn(move || {
+            let paths = Self::export(ExportJob {
+                exportables,
+                export_state,
+                export_cancelled,
+                export_file,
+                synth,
+                exporter,
+                path,
+                player_framerate,
+                limiter_ceiling,
+            });
+            // The UI is responsive again by now, so it's ok to wait for the command.
+            if let (Some(paths), Some(post_export)) = (paths, post_export) {
+                *post_export_result.lock() = Some(post_export.run_each(&paths));
+            }
+        });
+        true
+    }
+
+    /// Export to .mid. If `self.exporter.multi_file` is true, each exported track is written to a separate file.
+    ///
+    /// - `path` The user-defined export path.
+    /// - `music` The music that will be exported.
+    /// - `time` The time state.
+    pub fn export_mid(&self, path: &Path, music: &Music, time: &Time) {
+        let mut paths = vec![];
+        if self.exporter.multi_file {
+            for track in music.get_export_tracks() {
+                let music = Music {
+                    midi_tracks: vec![track.clone()],
+                    selected: None,
+                };
+                let suffix = Some(self.get_export_file_suffix(track));
+                let path = Self::get_export_path(path, &suffix, &Extension::Mid);
+                self.exporter.mid(&path, &music, time, &self.state);
+                paths.push(path);
+            }
+        } else {
+            self.exporter.mid(path, music, time, &self.state);
+            paths.push(path.to_path_buf());
+        }
+        if let Some(post_export) = self.post_export.clone() {
+            let post_export_result = Arc::clone(&self.post_export_result);
+            spawn(move || *post_export_result.lock() = Some(post_export.run_each(&paths)));
+        }
+    }
+
+    /// Returns the result of the most recent post-export command if it finished since the last time this was called.
+    /// Returns true if the command succeeded.
+    pub fn take_post_export_result(&self) -> Option<bool> {
+        self.post_export_result.lock().take()
+    }
+
+    /// Returns the estimated total number of exported samples, including a decay tail per exported file.
+    pub fn estimate_export_samples(&self, state: &State) -> u64 {
+        let decay = (self.exporter.framerate.get_f() * ESTIMATED_DECAY_SECONDS) as u64;
+        self.get_exportables(state)
+            .iter()
+            .map(|exportable| exportable.total_samples + decay)
+            .sum()
+    }
+
+    /// Returns the start and end time in PPQ of the music that will be exported, or None if all of the music will be exported.
+    ///
+    /// If `exporter.selection_only` is true and many notes are selected, only the time span of the selection is exported. See `SelectMode::get_time_range`.
+    /// .mid files don't have this setting and always export all of the music.
+    pub fn get_export_range(&self, state: &State) -> Option<(u64, u64)> {
+        if self.exporter.selection_only && self.exporter.export_type.get() != ExportType::Mid {
+            state.select_mode.get_time_range(&state.music)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the scheduled events of each file that will be exported. See `get_export_range`.
+    fn get_exportables(&self, state: &State) -> Vec<Exportable> {
+        let mut exportables = vec![];
+        let tracks = state.music.get_export_tracks();
+        let range = self.get_export_range(state);
 
         // Export each track as a separate file.
         if self.exporter.multi_file {
@@ -348,7 +987,15 @@ impl Conn {
                 let mut events = MidiEventQueue::default();
                 let mut t1 = 0;
                 let gain = track.get_gain_f();
-                self.enqueue_track_events(track, &state.time, &mut events, &mut t1, gain);
+                self.enqueue_track_events(
+                    track,
+                    &state.time,
+                    &mut events,
+                    &mut t1,
+                    gain,
+                    range,
+                    self.exporter.framerate.get_f(),
+                );
                 events.sort();
                 let suffix = Some(self.get_export_file_suffix(track));
                 // Add an exportable.
@@ -365,7 +1012,15 @@ impl Conn {
             let mut events = MidiEventQueue::default();
             for track in tracks {
                 let gain = track.get_gain_f();
-                self.enqueue_track_events(track, &state.time, &mut events, &mut t1, gain);
+                self.enqueue_track_events(
+                    track,
+                    &state.time,
+                    &mut events,
+                    &mut t1,
+                    gain,
+                    range,
+                    self.exporter.framerate.get_f(),
+                );
             }
             events.sort();
             // Add an exportable.
@@ -375,24 +1030,43 @@ impl Conn {
                 suffix: None,
             });
         }
+        exportables
+    }
 
-        let export_state = Arc::clone(&self.export_state);
-        let synth = Arc::clone(&self.synth);
-        let exporter = self.exporter.clone();
-        let path = paths_state.exports.get_path();
-        let player_framerate = self.framerate;
-        spawn(move || {
-            Self::export(
-                exportables,
-                export_state,
-                synth,
-                exporter,
-                path,
-                player_framerate,
-            )
-        });
+    /// Returns the exportables. If there aren't any notes to export, this returns silent exportables that are `self.empty_export_silence` seconds long.
+    ///
+    /// Returns None if there are no files to export, or if there aren't any notes and `self.empty_export_silence` is 0.
+    fn get_exportables_or_silence(&self, state: &State) -> Option<Vec<Exportable>> {
+        let mut exportables = self.get_exportables(state);
+        if exportables.is_empty() {
+            None
+        } else if exportables
+            .iter()
+            .any(|exportable| exportable.total_samples > 0)
+        {
+            Some(exportables)
+        } else if self.empty_export_silence > 0.0 {
+            let total_samples =
+                (self.exporter.framerate.get_f() * self.empty_export_silence) as u64;
+            exportables
+                .iter_mut()
+                .for_each(|exportable| exportable.total_samples = total_samples);
+            Some(exportables)
+        } else {
+            None
+        }
     }
 
+    /// Enqueue the events of a track for export.
+    ///
+    /// - `track` The track.
+    /// - `time` This is used to convert PPQ to samples.
+    /// - `events` Add the events to this queue.
+    /// - `t1` This is set to the time of the last note-off event in samples.
+    /// - `gain` Multiply the velocity of each note by this value.
+    /// - `range` If not None, this is a start and end time in PPQ. Only notes within the range are enqueued, and the events are offset so that the range starts at 0. Notes that start before the range and sustain into it are played at the start of the range. Notes that sustain past the range are cut off at the end of the range.
+    /// - `framerate` The framerate that is used to convert PPQ to samples.
+    #[allow(clippy::too_many_arguments)]
     fn enqueue_track_events(
         &self,
         track: &MidiTrack,
@@ -400,19 +1074,46 @@ impl Conn {
         events: &mut MidiEventQueue,
         t1: &mut u64,
         gain: f32,
+        range: Option<(u64, u64)>,
+        framerate: f32,
     ) {
-        let framerate = self.exporter.framerate.get_f();
-        for note in track.notes.iter() {
+        let (range_start, range_end) = range.unwrap_or((0, u64::MAX));
+        let offset = time.ppq_to_samples(range_start, framerate);
+        Self::enqueue_volume_events(
+            track,
+            time,
+            events,
+            (range_start, range_end),
+            offset,
+            framerate,
+        );
+        // Resolve overlapping notes of the same pitch.
+        let notes = self.exporter.overlap_policy.apply(&track.notes);
+        let ties = Ties::new(&notes);
+        for note in notes.iter() {
+            // Join tied notes.
+            let note = match note.get_tied(&ties, 0) {
+                Some(note) => note,
+                None => continue,
+            };
+            // Apply the articulation.
+            let mut note = note.get_articulated(&notes);
+            // Clamp the note to the range.
+            if note.end <= range_start || note.start >= range_end {
+                continue;
+            }
+            note.start = note.start.max(range_start);
+            note.end = note.end.min(range_end);
             // Note-on.
             events.enqueue(
-                time.ppq_to_samples(note.start, framerate),
+                time.ppq_to_samples(note.start, framerate) - offset,
                 MidiEvent::NoteOn {
                     channel: track.channel,
-                    key: note.note,
-                    vel: (note.velocity as f32 * gain) as u8,
+                    key: track.note_range.apply(note.note),
+                    vel: (self.exporter.get_floored_velocity(note.velocity) as f32 * gain) as u8,
                 },
             );
-            let end = time.ppq_to_samples(note.end, framerate);
+            let end = time.ppq_to_samples(note.end, framerate) - offset;
             // This is the last known event.
             if *t1 < end {
                 *t1 = end;
@@ -421,23 +1122,38 @@ impl Conn {
                 end,
                 MidiEvent::NoteOff {
                     channel: track.channel,
-                    key: note.note,
+                    key: track.note_range.apply(note.note),
                 },
             );
         }
     }
 
-    fn export(
-        mut exportables: Vec<Exportable>,
-        export_state: SharedExportState,
-        synth: SharedSynth,
-        exporter: Exporter,
-        path: PathBuf,
-        player_framerate: f32,
-    ) {
+    /// Export audio. This is called in a separate thread.
+    ///
+    /// Returns the paths of the exported files, or None if the export was cancelled. See `Command::StopExport`.
+    fn export(job: ExportJob) -> Option<Vec<PathBuf>> {
+        let ExportJob {
+            mut exportables,
+            export_state,
+            export_cancelled,
+            export_file,
+            synth,
+            exporter,
+            path,
+            player_framerate,
+            limiter_ceiling,
+        } = job;
         let mut decayer = Decayer::default();
+        let limiter = if exporter.limiter {
+            Some(Limiter::new(limiter_ceiling, exporter.framerate.get_f()))
+        } else {
+            None
+        };
         let extension: Extension = exporter.export_type.get().into();
-        for exportable in exportables.iter_mut() {
+        let num_files = exportables.len();
+        let mut paths = vec![];
+        for (index, exportable) in exportables.iter_mut().enumerate() {
+            *export_file.lock() = (index, num_files);
             let total_samples = exportable.total_samples;
             // Get the audio buffers.
             let mut left = vec![0.0f32; total_samples as usize];
@@ -446,6 +1162,10 @@ impl Conn {
             Self::set_export_state_wav(exportable, &export_state, 0);
             let mut synth = synth.lock();
             for t in 0..total_samples {
+                if export_cancelled.load(Ordering::Relaxed) {
+                    Self::cancel_export(&export_state, &mut synth, player_framerate);
+                    return None;
+                }
                 // Get and send each event at this time.
                 for event in exportable.events.dequeue(t).iter() {
                     let _ = synth.send_event(*event);
@@ -459,46 +1179,134 @@ impl Conn {
             Self::set_export_state(&export_state, ExportState::AppendingDecay);
             decayer.decaying = true;
             while decayer.decaying {
+                if export_cancelled.load(Ordering::Relaxed) {
+                    Self::cancel_export(&export_state, &mut synth, player_framerate);
+                    return None;
+                }
                 decayer.decay_two_channels(&mut left, &mut right, &mut synth);
             }
             // Convert.
             Self::set_export_state(&export_state, ExportState::WritingToDisk);
-            let filename = path.file_stem().unwrap().to_str().unwrap();
-            let extension = extension.to_str(true);
-            let path = match &exportable.suffix {
-                Some(suffix) => path
-                    .parent()
-                    .unwrap()
-                    .join(format!("{}_{}{}", filename, suffix, extension))
-                    .to_path_buf(),
-                None => path
-                    .parent()
-                    .unwrap()
-                    .join(format!("{}{}", filename, extension))
-                    .to_path_buf(),
+            let mut audio = [left, right];
+            // Sum to mono.
+            if exporter.is_mono() {
+                Self::sum_to_mono(&mut audio);
+            }
+            // Normalize. If this is a multi-file export, each file is normalized independently.
+            match exporter.normalization.get() {
+                Normalization::None => (),
+                Normalization::Loudness => normalize(
+                    &mut audio,
+                    exporter.get_loudness_target() as f32,
+                    exporter.limiter,
+                    exporter.framerate.get_f(),
+                    exporter.is_mono(),
+                ),
+                Normalization::Peak => normalize_peak(&mut audio, exporter.get_peak_target()),
+            }
+            // Limit the peaks.
+            if let Some(limiter) = &limiter {
+                limiter.apply(&mut audio);
+            }
+            let [left, right] = audio;
+            // Copy the samples that were written after the last note-off.
+            let tail = if exporter.multi_file && exporter.multi_file_tail {
+                let t = total_samples as usize;
+                Some([left[t..].to_vec(), right[t..].to_vec()])
+            } else {
+                None
             };
+            // Don't write the file if the export was cancelled while it was being converted.
+            if export_cancelled.load(Ordering::Relaxed) {
+                Self::cancel_export(&export_state, &mut synth, player_framerate);
+                return None;
+            }
+            let suffix = exportable.suffix.clone();
             let audio = [left, right];
-            match &exporter.export_type.get() {
-                ExportType::Mid => {
-                    panic!("Tried exporting a .mid from the synthesizer")
-                }
-                // Export to a .wav file.
-                ExportType::Wav => {
-                    exporter.wav(&path, &audio);
-                }
-                ExportType::MP3 => {
-                    exporter.mp3(&path, &audio);
-                }
-                ExportType::Ogg => {
-                    exporter.ogg(&path, &audio);
-                }
-                ExportType::Flac => exporter.flac(&path, &audio),
+            let export_path = Self::get_export_path(&path, &suffix, &extension);
+            Self::write_audio(&exporter, &export_path, &audio);
+            paths.push(export_path);
+            // Write the tail to a separate file.
+            if let Some(tail) = tail {
+                let suffix = match &suffix {
+                    Some(suffix) => format!("{}_tail", suffix),
+                    None => "tail".to_string(),
+                };
+                let tail_path = Self::get_export_path(&path, &Some(suffix), &extension);
+                Self::write_audio(&exporter, &tail_path, &tail);
+                paths.push(tail_path);
             }
             // Done.
             Self::set_export_state(&export_state, ExportState::Done);
+            *export_file.lock() = (index + 1, num_files);
         }
         Self::set_export_state(&export_state, ExportState::NotExporting);
         synth.lock().set_sample_rate(player_framerate);
+        Some(paths)
+    }
+
+    /// Stop an export. Silence the synthesizer, set the export state to `NotExporting`, and restore the playback framerate.
+    fn cancel_export(export_state: &SharedExportState, synth: &mut Synth, player_framerate: f32) {
+        // Stop the voices immediately so that they aren't heard during playback.
+        for channel in 0..16 {
+            let _ = synth.send_event(MidiEvent::AllSoundOff { channel });
+        }
+        Self::set_export_state(export_state, ExportState::NotExporting);
+        synth.set_sample_rate(player_framerate);
+    }
+
+    /// Replace both channels with the average of the left and right channels. The encoders only use the left channel of mono audio.
+    fn sum_to_mono(audio: &mut AudioBuffer) {
+        let [left, right] = audio;
+        for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+            *l = (*l + *r) * 0.5;
+            *r = *l;
+        }
+    }
+
+    /// Returns the path of an exported file.
+    ///
+    /// - `path` The user-defined export path.
+    /// - `suffix` An optional filename suffix.
+    /// - `extension` The file extension.
+    fn get_export_path(path: &Path, suffix: &Option<String>, extension: &Extension) -> PathBuf {
+        let filename = path.file_stem().unwrap().to_str().unwrap();
+        let extension = extension.to_str(true);
+        match suffix {
+            Some(suffix) => path
+                .parent()
+                .unwrap()
+                .join(format!("{}_{}{}", filename, suffix, extension)),
+            None => path
+                .parent()
+                .unwrap()
+                .join(format!("{}{}", filename, extension)),
+        }
+    }
+
+    /// Write audio to a file.
+    ///
+    /// - `exporter` The export settings.
+    /// - `path` The output path.
+    /// - `audio` The audio buffers.
+    fn write_audio(exporter: &Exporter, path: &Path, audio: &AudioBuffer) {
+        match &exporter.export_type.get() {
+            ExportType::Mid => {
+                panic!("Tried exporting a .mid from the synthesizer")
+            }
+            // Export to a .wav file.
+            ExportType::Wav => {
+                exporter.wav(path, audio);
+            }
+            ExportType::MP3 => {
+                exporter.mp3(path, audio);
+            }
+            ExportType::Ogg => {
+                exporter.ogg(path, audio);
+            }
+            ExportType::Flac => exporter.flac(path, audio),
+            ExportType::Opus => exporter.opus(path, audio),
+        }
     }
 
     /// Set the exporter's framerate.
@@ -546,3 +1354,692 @@ impl Conn {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::conn_settings::DEFAULT_SYNTH_HEADROOM;
+    use crate::export::{ExportJob, ExportState, MultiFileSuffix, PostExport};
+    use crate::play_state::PlayState;
+    use crate::program::Program;
+    use crate::{Command, Conn};
+    use common::open_file::FileAndDirectory;
+    use common::{Music, PathsState, SelectMode, State, Time, U64orF32, MAX_VOLUME, PPQ_U};
+    use id3::TagLike;
+    use ini::Ini;
+    use midly::{MidiMessage, Smf, TrackEventKind};
+    use oggvorbismeta::VorbisComments;
+    use oxisynth::MidiEvent;
+    use std::fs::{read, remove_file, File};
+    use std::path::PathBuf;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    #[test]
+    fn export_mid_multi_file() {
+        let mut conn = Conn::default();
+        conn.exporter.multi_file = true;
+        set_channel_suffix(&mut conn);
+        let mut music = Music::default();
+        music.add_track(0).add_note(60, 127, 0, PPQ_U);
+        music
+            .add_track(1)
+            .add_note(64, 127, 0, PPQ_U)
+            .add_note(67, 127, PPQ_U, PPQ_U * 2);
+        for track in music.midi_tracks.iter() {
+            conn.state.programs.insert(track.channel, get_program());
+        }
+        let directory = std::env::temp_dir();
+        let path = directory.join("export_mid_multi_file.mid");
+        // This command fails if the file doesn't exist.
+        conn.post_export = PostExport::new("test -f \"{path}\"");
+        conn.export_mid(&path, &music, &Time::default());
+        // The command ran once per exported file.
+        assert_eq!(wait_for_post_export(&conn), Some(true));
+        // There is one file per track.
+        assert!(!path.exists());
+        for track in music.midi_tracks.iter() {
+            let path = directory.join(format!("export_mid_multi_file_{}.mid", track.channel));
+            let bytes = read(&path).unwrap();
+            let smf = Smf::parse(&bytes).unwrap();
+            // Each file only has this track's notes.
+            let notes: Vec<(u8, u8)> = smf
+                .tracks
+                .iter()
+                .flatten()
+                .filter_map(|e| match e.kind {
+                    TrackEventKind::Midi {
+                        channel,
+                        message: MidiMessage::NoteOn { key, .. },
+                    } => Some((channel.as_int(), key.as_int())),
+                    _ => None,
+                })
+                .collect();
+            let expected: Vec<(u8, u8)> = track
+                .notes
+                .iter()
+                .map(|n| (track.channel, n.note))
+                .collect();
+            assert_eq!(notes, expected);
+            remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn monitor_gain() {
+        let mut conn = Conn::default();
+        assert_eq!(conn.get_monitor_gain(), 0);
+        assert_eq!(*conn.monitor_gain.lock(), 1.0);
+        conn.set_monitor_gain(-6);
+        assert_eq!(conn.get_monitor_gain(), -6);
+        assert!((*conn.monitor_gain.lock() - 0.501).abs() < 0.001);
+        // The gain is clamped.
+        conn.set_monitor_gain(i8::MAX);
+        assert_eq!(conn.get_monitor_gain(), super::MAX_MONITOR_GAIN_DB);
+        conn.set_monitor_gain(i8::MIN);
+        assert_eq!(conn.get_monitor_gain(), super::MIN_MONITOR_GAIN_DB);
+        // Mono monitoring.
+        assert!(!conn.get_mono_monitor());
+        conn.set_mono_monitor(true);
+        assert!(*conn.mono_monitor.lock());
+        conn.set_mono_monitor(false);
+        assert!(!conn.get_mono_monitor());
+    }
+
+    #[test]
+    fn live_framerate() {
+        let mut conn = Conn::default();
+        conn.framerate = 44100.0;
+        // This is what happens when a save file with a different export framerate is loaded.
+        conn.exporter.framerate = U64orF32::from(48000);
+        let mut state = get_state();
+        state.music.add_track(0).add_note(60, 127, PPQ_U, PPQ_U * 2);
+        conn.start_music(&state, 0);
+        // Live playback is scheduled at the playback framerate.
+        let t = state.time.ppq_to_samples(PPQ_U, 44100.0);
+        assert_ne!(t, state.time.ppq_to_samples(PPQ_U, 48000.0));
+        assert_eq!(conn.midi_event_queue.lock().get_next_time(), Some(t));
+        // The export framerate is unchanged.
+        assert_eq!(conn.exporter.framerate.get_u(), 48000);
+    }
+
+    #[test]
+    fn export_empty() {
+        let state = get_state();
+        let (path, paths_state) = get_paths_state("export_empty.wav");
+        let mut conn = Conn::default();
+        // By default, an empty project isn't exported.
+        assert!(!conn.start_export(&state, &paths_state));
+        assert!(!conn.exporting());
+        assert!(!path.exists());
+        // Export one second of silence.
+        conn.empty_export_silence = 1.0;
+        assert!(conn.start_export(&state, &paths_state));
+        wait_for_export(&conn);
+        let reader = hound::WavReader::open(&path).unwrap();
+        let framerate = conn.exporter.framerate.get_u() as u32;
+        assert!(reader.duration() >= framerate);
+        assert!(reader
+            .into_samples::<i16>()
+            .all(|sample| sample.unwrap() == 0));
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn export_after_stop() {
+        let mut state = get_state();
+        // The exported note starts after one bar of silence.
+        let bar = PPQ_U * 4;
+        state.music.add_track(0).add_note(60, 127, bar, bar + PPQ_U);
+        let (path, paths_state) = get_paths_state("export_after_stop.wav");
+        let mut conn = Conn::default();
+        conn.do_commands(&[Command::LoadSoundFont {
+            channel: 0,
+            path: PathBuf::from("../data/CT1MBGMRSV1.06.sf2"),
+        }]);
+        // Play a long note that is still sounding when playback stops.
+        conn.start_music(&state, 0);
+        {
+            let mut synth = conn.synth.lock();
+            synth
+                .send_event(MidiEvent::NoteOn {
+                    channel: 0,
+                    key: 48,
+                    vel: 127,
+                })
+                .unwrap();
+            assert!((0..4410).any(|_| synth.read_next() != (0.0, 0.0)));
+        }
+        // Stop playback and immediately export.
+        conn.stop_music(&state.music);
+        assert_eq!(*conn.play_state.lock(), PlayState::Decaying);
+        assert!(conn.start_export(&state, &paths_state));
+        // The decay was cut off.
+        assert_eq!(*conn.play_state.lock(), PlayState::NotPlaying);
+        wait_for_export(&conn);
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        let framerate = reader.spec().sample_rate as f32;
+        let t0 = state.time.ppq_to_samples(bar, framerate) as usize * 2;
+        let samples: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+        // The lingering note doesn't bleed into the start of the export.
+        assert!(samples[..t0].iter().all(|s| *s == 0));
+        // The exported note isn't silent.
+        assert!(samples[t0..].iter().any(|s| *s != 0));
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn export_cancel() {
+        let mut state = get_state();
+        state.music.add_track(0).add_note(60, 127, 0, PPQ_U);
+        state.music.add_track(1).add_note(64, 127, 0, PPQ_U);
+        let (path, _) = get_paths_state("export_cancel.wav");
+        let mut conn = Conn::default();
+        conn.exporter.multi_file = true;
+        set_channel_suffix(&mut conn);
+        let paths: Vec<PathBuf> = (0..2)
+            .map(|channel| get_paths_state(&format!("export_cancel_{}.wav", channel)).0)
+            .collect();
+        // The export can only be stopped while exporting.
+        conn.do_commands(&[Command::StopExport]);
+        assert!(!conn.export_cancelled.load(Ordering::Relaxed));
+        let export = |conn: &Conn| {
+            Conn::export(ExportJob {
+                exportables: conn.get_exportables(&state),
+                export_state: Arc::clone(&conn.export_state),
+                export_cancelled: Arc::clone(&conn.export_cancelled),
+                export_file: Arc::clone(&conn.export_file),
+                synth: Arc::clone(&conn.synth),
+                exporter: conn.exporter.clone(),
+                path: path.clone(),
+                player_framerate: conn.framerate,
+                limiter_ceiling: conn.limiter_ceiling,
+            })
+        };
+        // Cancel the export before it starts.
+        *conn.export_state.lock() = ExportState::AppendingDecay;
+        conn.do_commands(&[Command::StopExport]);
+        assert!(conn.export_cancelled.load(Ordering::Relaxed));
+        assert!(export(&conn).is_none());
+        assert_eq!(*conn.export_state.lock(), ExportState::NotExporting);
+        // No files were written.
+        assert_eq!(conn.get_exported_files(), (0, 2));
+        assert!(paths.iter().all(|path| !path.exists()));
+        // Export both files.
+        conn.export_cancelled.store(false, Ordering::Relaxed);
+        assert_eq!(export(&conn), Some(paths.clone()));
+        assert_eq!(conn.get_exported_files(), (2, 2));
+        for path in paths {
+            remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn export_mono() {
+        let state = get_state();
+        let mut conn = Conn::default();
+        conn.empty_export_silence = 1.0;
+        let mut export = |name: &str, channels: u16| {
+            let (path, paths_state) = get_paths_state(name);
+            conn.exporter.channels = channels;
+            assert!(conn.start_export(&state, &paths_state));
+            wait_for_export(&conn);
+            let reader = hound::WavReader::open(&path).unwrap();
+            let spec = reader.spec();
+            // The number of bytes of samples in the data chunk.
+            let bytes = reader.len() * spec.bits_per_sample as u32 / 8;
+            let duration = reader.duration();
+            remove_file(path).unwrap();
+            (spec.channels, duration, bytes)
+        };
+        let (channels, stereo_duration, stereo_bytes) = export("export_stereo.wav", 2);
+        assert_eq!(channels, 2);
+        let (channels, mono_duration, mono_bytes) = export("export_mono.wav", 1);
+        assert_eq!(channels, 1);
+        // The mono file is as long as the stereo file, but each frame is half as many bytes.
+        assert_eq!(mono_duration, stereo_duration);
+        assert_eq!(mono_bytes * 2, stereo_bytes);
+        // Mono audio is the average of the two channels.
+        let mut audio = [vec![1.0, 0.5], vec![0.0, -0.5]];
+        Conn::sum_to_mono(&mut audio);
+        assert_eq!(audio, [vec![0.5, 0.0], vec![0.5, 0.0]]);
+    }
+
+    #[test]
+    fn export_wav_bit_depth() {
+        let mut conn = Conn::default();
+        let audio = [vec![0.5, -1.0, 0.123], vec![-0.5, 1.0, 0.0]];
+        let path = std::env::temp_dir().join("export_wav_bit_depth.wav");
+        // 24-bit.
+        conn.exporter.wav_bit_depth.index.set(1);
+        conn.exporter.wav(&path, &audio);
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 24);
+        assert_eq!(reader.spec().sample_format, hound::SampleFormat::Int);
+        let samples: Vec<i32> = reader.samples::<i32>().map(|s| s.unwrap()).collect();
+        assert_eq!(samples[..4], [4194303, -4194304, -8388608, 8388607]);
+        // 32-bit float.
+        conn.exporter.wav_bit_depth.index.set(2);
+        conn.exporter.wav(&path, &audio);
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 32);
+        assert_eq!(reader.spec().sample_format, hound::SampleFormat::Float);
+        let samples: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+        // The samples aren't scaled.
+        assert_eq!(samples, vec![0.5, -0.5, -1.0, 1.0, 0.123, 0.0]);
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn export_dither() {
+        let mut conn = Conn::default();
+        // Dither is off by default.
+        assert!(!conn.exporter.dither);
+        conn.exporter.dither = true;
+        // A very quiet signal in both channels, followed by silence.
+        let mut channel = vec![0.0001; 1000];
+        channel.extend([0.0; 1000]);
+        let audio = [channel.clone(), channel];
+        let path = std::env::temp_dir().join("export_dither.wav");
+        let export = |conn: &Conn| {
+            conn.exporter.wav(&path, &audio);
+            let samples: Vec<i16> = hound::WavReader::open(&path)
+                .unwrap()
+                .into_samples::<i16>()
+                .map(|s| s.unwrap())
+                .collect();
+            remove_file(&path).unwrap();
+            samples
+        };
+        let samples = export(&conn);
+        let (left, right): (Vec<i16>, Vec<i16>) =
+            samples.chunks(2).map(|frame| (frame[0], frame[1])).unzip();
+        // The dither is different in each channel.
+        assert_ne!(left[..1000], right[..1000]);
+        assert!(left[..1000].iter().all(|s| (2..=4).contains(s)));
+        // Silence isn't dithered.
+        assert!(samples[2000..].iter().all(|s| *s == 0));
+        // Without dither, the output is bit-exact.
+        conn.exporter.dither = false;
+        assert!(export(&conn)[..2000].iter().all(|s| *s == 3));
+    }
+
+    #[test]
+    fn flac_metadata() {
+        let mut conn = Conn::default();
+        conn.exporter.metadata.title = "My Title".to_string();
+        conn.exporter.metadata.album = Some("My Album".to_string());
+        conn.exporter.metadata.track_number = Some(3);
+        conn.exporter.copyright = true;
+        let path = std::env::temp_dir().join("flac_metadata.flac");
+        conn.exporter
+            .flac(&path, &[vec![0.0; 4096], vec![0.0; 4096]]);
+        let tag = metaflac::Tag::read_from_path(&path).unwrap();
+        let comments = &tag.vorbis_comments().unwrap().comments;
+        let get = |key: &str| {
+            comments
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v.join(""))
+        };
+        assert_eq!(get("title"), Some("My Title".to_string()));
+        assert_eq!(get("album"), Some("My Album".to_string()));
+        assert_eq!(get("tracknumber"), Some("3".to_string()));
+        // There is no artist, so there is no artist or copyright comment.
+        assert!(get("artist").is_none());
+        assert!(get("copyright").is_none());
+        assert!(get("description").is_none());
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn export_genre_and_comment() {
+        let mut conn = Conn::default();
+        conn.exporter.metadata.genre = Some("My Genre".to_string());
+        conn.exporter.metadata.comment = Some("My Comment".to_string());
+        let audio = [vec![0.0; 4096], vec![0.0; 4096]];
+        // .mp3
+        let path = std::env::temp_dir().join("export_genre_and_comment.mp3");
+        conn.exporter.mp3(&path, &audio);
+        let tag = id3::Tag::read_from_path(&path).unwrap();
+        assert_eq!(tag.genre(), Some("My Genre"));
+        let comments: Vec<&str> = tag.comments().map(|c| c.text.as_str()).collect();
+        assert_eq!(comments, vec!["My Comment"]);
+        remove_file(path).unwrap();
+        // .ogg
+        let path = std::env::temp_dir().join("export_genre_and_comment.ogg");
+        conn.exporter.ogg(&path, &audio);
+        let comments = oggvorbismeta::read_comment_header(File::open(&path).unwrap());
+        assert_eq!(
+            comments.get_tag_single("genre"),
+            Some("My Genre".to_string())
+        );
+        assert_eq!(
+            comments.get_tag_single("description"),
+            Some("My Comment".to_string())
+        );
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn export_mp3_framerate() {
+        let mut conn = Conn::default();
+        let audio = [vec![0.25; 96000], vec![-0.25; 96000]];
+        let path = std::env::temp_dir().join("export_mp3_framerate.mp3");
+        // Returns the framerate of the first MPEG-1 frame after the ID3 tag.
+        let get_framerate = |path: &PathBuf| {
+            let bytes = read(path).unwrap();
+            assert!(bytes.starts_with(b"ID3"));
+            let tag_size = 10
+                + bytes[6..10]
+                    .iter()
+                    .fold(0usize, |size, b| (size << 7) | *b as usize);
+            let header = &bytes[tag_size..tag_size + 4];
+            assert_eq!(header[0], 0xFF);
+            // MPEG-1.
+            assert_eq!(header[1] & 0xF8, 0xF8);
+            [44100, 48000, 32000][((header[2] >> 2) & 0x3) as usize]
+        };
+        // The LAME encoder supports this framerate.
+        conn.exporter.framerate = U64orF32::from(48000);
+        conn.exporter.mp3(&path, &audio);
+        assert_eq!(get_framerate(&path), 48000);
+        // The LAME encoder doesn't support these framerates, so the audio is resampled.
+        for framerate in [88200, 96000] {
+            conn.exporter.framerate = U64orF32::from(framerate);
+            conn.exporter.mp3(&path, &audio);
+            assert_eq!(get_framerate(&path), 48000);
+        }
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn export_opus() {
+        let mut conn = Conn::default();
+        conn.exporter.metadata.genre = Some("My Genre".to_string());
+        let audio = [vec![0.25; 44100], vec![-0.25; 44100]];
+        let path = std::env::temp_dir().join("export_opus.opus");
+        conn.exporter.opus(&path, &audio);
+        let mut reader = ogg::reading::PacketReader::new(File::open(&path).unwrap());
+        let head = reader.read_packet_expect().unwrap();
+        assert!(head.data.starts_with(b"OpusHead"));
+        // Stereo.
+        assert_eq!(head.data[9], 2);
+        // The input framerate.
+        assert_eq!(
+            u32::from_le_bytes(head.data[12..16].try_into().unwrap()),
+            44100
+        );
+        let pre_skip = u16::from_le_bytes(head.data[10..12].try_into().unwrap()) as u64;
+        let tags = reader.read_packet_expect().unwrap();
+        assert!(tags.data.starts_with(b"OpusTags"));
+        assert!(tags.data.windows(14).any(|w| w == b"genre=My Genre"));
+        // The audio was resampled to 48 kHz.
+        let mut granule = 0;
+        while let Some(packet) = reader.read_packet().unwrap() {
+            granule = packet.absgp_page();
+        }
+        assert_eq!(granule - pre_skip, 48000);
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn synth_headroom() {
+        let mut conn = Conn::default();
+        assert_eq!(conn.gain_ramp.lock().finish(), DEFAULT_SYNTH_HEADROOM);
+        // The music gain is scaled into the headroom.
+        conn.do_commands(&[Command::SetGain { gain: MAX_VOLUME }]);
+        assert_eq!(conn.state.gain, MAX_VOLUME);
+        assert_eq!(conn.gain_ramp.lock().finish(), DEFAULT_SYNTH_HEADROOM);
+        conn.do_commands(&[Command::SetGain {
+            gain: MAX_VOLUME / 2,
+        }]);
+        let gain = (MAX_VOLUME / 2) as f32 / MAX_VOLUME as f32 * DEFAULT_SYNTH_HEADROOM;
+        assert_eq!(conn.gain_ramp.lock().finish(), gain);
+    }
+
+    #[test]
+    fn velocity_floor() {
+        let mut conn = Conn::default();
+        conn.exporter.velocity_floor.set(20);
+        let mut state = get_state();
+        state
+            .music
+            .add_track(0)
+            .add_note(60, 5, 0, PPQ_U)
+            .add_note(64, 80, 0, PPQ_U);
+        let mut exportables = conn.get_exportables(&state);
+        let velocities: Vec<u8> = exportables[0]
+            .events
+            .dequeue(0)
+            .iter()
+            .filter_map(|e| match e {
+                MidiEvent::NoteOn { vel, .. } => Some(*vel),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(velocities, vec![20, 80]);
+        // The notes are unchanged.
+        assert_eq!(state.music.midi_tracks[0].notes[0].velocity, 5);
+    }
+
+    #[test]
+    fn export_range() {
+        let mut conn = Conn::default();
+        let mut state = get_state();
+        let bar = PPQ_U * 4;
+        // A 4-bar pattern with a note that sustains through all of it.
+        state
+            .music
+            .add_track(0)
+            .add_note(60, 100, 0, bar)
+            .add_note(62, 100, bar, bar * 2)
+            .add_note(64, 100, bar * 2, bar * 3)
+            .add_note(65, 100, bar * 3, bar * 4)
+            .add_note(48, 100, 0, bar * 4);
+        state.music.selected = Some(0);
+        let framerate = conn.exporter.framerate.get_f();
+        // Export the whole piece.
+        let exportables = conn.get_exportables(&state);
+        assert_eq!(
+            exportables[0].total_samples,
+            state.time.ppq_to_samples(bar * 4, framerate)
+        );
+        // The selection isn't exported unless `selection_only` is enabled.
+        state.select_mode = SelectMode::Many(Some(vec![1]));
+        assert!(conn.get_export_range(&state).is_none());
+        let exportables = conn.get_exportables(&state);
+        assert_eq!(
+            exportables[0].total_samples,
+            state.time.ppq_to_samples(bar * 4, framerate)
+        );
+        // Export only the second bar.
+        conn.exporter.selection_only = true;
+        assert_eq!(conn.get_export_range(&state), Some((bar, bar * 2)));
+        let mut exportables = conn.get_exportables(&state);
+        assert_eq!(
+            exportables[0].total_samples,
+            state.time.ppq_to_samples(bar, framerate)
+        );
+        // The selected note and the sustained note start at the beginning of the range.
+        let mut keys: Vec<u8> = exportables[0]
+            .events
+            .dequeue(0)
+            .iter()
+            .filter_map(|e| match e {
+                MidiEvent::NoteOn { key, .. } => Some(*key),
+                _ => None,
+            })
+            .collect();
+        keys.sort();
+        assert_eq!(keys, vec![48, 62]);
+        // Both notes are cut off at the end of the range, and there are no other events.
+        let t1 = exportables[0].total_samples;
+        assert_eq!(exportables[0].events.get_next_time(), Some(t1));
+        assert_eq!(exportables[0].events.dequeue(t1).len(), 2);
+        assert!(exportables[0].events.get_next_time().is_none());
+        // .mid files always export all of the music.
+        conn.exporter.export_type.index.set(1);
+        assert_eq!(conn.exporter.export_type.get(), ExportType::Mid);
+        assert!(conn.get_export_range(&state).is_none());
+    }
+
+    #[test]
+    fn freeze_track() {
+        let mut conn = Conn::default();
+        conn.framerate = 44100.0;
+        let mut state = get_state();
+        state.music.add_track(0).add_note(60, 127, 0, PPQ_U);
+        let path = std::env::temp_dir().join("freeze_track.wav");
+        // There is no program.
+        assert!(!conn.freeze_track(&state, 0, &path));
+        conn.state.programs.insert(0, get_program());
+        assert!(conn.freeze_track(&state, 0, &path));
+        assert!(path.exists());
+        // Play the frozen audio instead of the notes.
+        state.music.midi_tracks[0].frozen_audio = Some(path.clone());
+        conn.start_music(&state, 0);
+        assert!(conn.midi_event_queue.lock().get_next_time().is_none());
+        assert!(conn.frozen_audio.lock().is_playing(0));
+        conn.stop_music(&state.music);
+        assert!(!conn.frozen_audio.lock().is_playing(0));
+        // Unfreeze.
+        state.music.midi_tracks[0].frozen_audio = None;
+        conn.start_music(&state, 0);
+        assert_eq!(conn.midi_event_queue.lock().get_next_time(), Some(0));
+        assert!(!conn.frozen_audio.lock().is_playing(0));
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn update_preview() {
+        let mut conn = Conn::default();
+        conn.do_commands(&[Command::LoadSoundFont {
+            channel: 0,
+            path: PathBuf::from("../data/CT1MBGMRSV1.06.sf2"),
+        }]);
+        let mut state = get_state();
+        state.music.add_track(0);
+        state.music.selected = Some(0);
+        conn.preview_notes(&state, &[(60, 100)]);
+        assert!(conn.preview.is_some());
+        // The preview is timed from the first update.
+        let t0 = Duration::from_secs(10);
+        conn.update_preview(t0);
+        conn.update_preview(t0 + super::PREVIEW_DURATION / 2);
+        assert!(conn.preview.is_some());
+        conn.update_preview(t0 + super::PREVIEW_DURATION);
+        assert!(conn.preview.is_none());
+    }
+
+    #[test]
+    fn sounding_notes() {
+        let mut conn = Conn::default();
+        conn.do_commands(&[Command::LoadSoundFont {
+            channel: 0,
+            path: PathBuf::from("../data/CT1MBGMRSV1.06.sf2"),
+        }]);
+        let mut state = get_state();
+        state.music.add_track(0);
+        state.music.selected = Some(0);
+        conn.note_ons(&state, &[[144, 60, 100], [144, 64, 90], [144, 60, 80]]);
+        // Replaying a key replaces its velocity.
+        assert_eq!(conn.sounding_notes[&0], vec![(64, 90), (60, 80)]);
+        conn.note_offs(&state, &[64]);
+        assert_eq!(conn.sounding_notes[&0], vec![(60, 80)]);
+        // Returns the peak amplitude of the next samples.
+        let peak = |conn: &Conn| {
+            let mut synth = conn.synth.lock();
+            (0..4410)
+                .map(|_| {
+                    let (left, right) = synth.read_next();
+                    left.abs().max(right.abs())
+                })
+                .fold(0.0, f32::max)
+        };
+        // Silence the synth without releasing the held note, and let the effects decay.
+        conn.synth
+            .lock()
+            .send_event(MidiEvent::AllSoundOff { channel: 0 })
+            .unwrap();
+        for _ in 0..10 {
+            peak(&conn);
+        }
+        let silent = peak(&conn);
+        // The held note is played again.
+        conn.do_commands(&[Command::RetriggerActiveNotes { channel: 0 }]);
+        assert_eq!(conn.sounding_notes[&0], vec![(60, 80)]);
+        assert!(peak(&conn) > silent);
+        conn.note_offs(&state, &[60]);
+        assert!(conn.sounding_notes[&0].is_empty());
+    }
+
+    /// Returns a new state with the default config.
+    fn get_state() -> State {
+        State::new(&Ini::load_from_file("../data/config.ini").unwrap())
+    }
+
+    /// Returns a program that doesn't have a SoundFont.
+    fn get_program() -> Program {
+        Program {
+            path: PathBuf::new(),
+            num_banks: 1,
+            bank_index: 0,
+            bank: 0,
+            num_presets: 1,
+            preset: 0,
+            preset_index: 0,
+            preset_name: "Piano".to_string(),
+        }
+    }
+
+    /// Returns the path of an export file in the temp directory, and a `PathsState` that exports to it. If the file already exists, it's removed.
+    fn get_paths_state(filename: &str) -> (PathBuf, PathsState) {
+        let path = std::env::temp_dir().join(filename);
+        if path.exists() {
+            remove_file(&path).unwrap();
+        }
+        let paths_state = PathsState {
+            exports: FileAndDirectory::new_path(path.clone()),
+            ..Default::default()
+        };
+        (path, paths_state)
+    }
+
+    /// Use each track's channel as the suffix of multi-file exports.
+    fn set_channel_suffix(conn: &mut Conn) {
+        conn.exporter.multi_file_suffix.index.set(
+            conn.exporter
+                .multi_file_suffix
+                .get_values()
+                .0
+                .iter()
+                .position(|s| *s == MultiFileSuffix::Channel)
+                .unwrap(),
+        );
+    }
+
+    /// Wait for the export thread to finish.
+    fn wait_for_export(conn: &Conn) {
+        for _ in 0..500 {
+            if !conn.exporting() {
+                return;
+            }
+            sleep(Duration::from_millis(10));
+        }
+        panic!("The export didn't finish.");
+    }
+
+    /// Wait for the post-export command to finish and return its result.
+    fn wait_for_post_export(conn: &Conn) -> Option<bool> {
+        for _ in 0..500 {
+            let result = conn.take_post_export_result();
+            if result.is_some() {
+                return result;
+            }
+            sleep(Duration::from_millis(10));
+        }
+        None
+    }
+}
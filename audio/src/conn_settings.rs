@@ -0,0 +1,37 @@
+use crate::export::PostExport;
+use crate::gain_ramp::DEFAULT_GAIN_RAMP_MS;
+use crate::limiter::DEFAULT_LIMITER_CEILING_DB;
+use std::time::Duration;
+
+/// The default synthesizer gain at maximum music gain. See `ConnSettings.synth_headroom`.
+pub(crate) const DEFAULT_SYNTH_HEADROOM: f32 = 0.5;
+
+/// The settings used to create a new `Conn`. See `Conn::new`.
+#[derive(Clone)]
+pub struct ConnSettings {
+    /// The name of the preferred audio output device. If None, or if the device doesn't exist, the default output device is used.
+    pub output_device: Option<String>,
+    /// During playback, gain changes are interpolated over this duration.
+    pub gain_ramp_duration: Duration,
+    /// If the export limiter is enabled, exported samples never exceed this value in dBFS.
+    pub limiter_ceiling: f32,
+    /// If not None, this command runs once per exported file after each export.
+    pub post_export: Option<PostExport>,
+    /// If there aren't any notes to export, each exported file is this many seconds of silence. If this is 0, the export doesn't start.
+    pub empty_export_silence: f32,
+    /// The synthesizer gain when the music gain is at its maximum. This is clamped to be between 0 and 1.
+    pub synth_headroom: f32,
+}
+
+impl Default for ConnSettings {
+    fn default() -> Self {
+        Self {
+            output_device: None,
+            gain_ramp_duration: Duration::from_millis(DEFAULT_GAIN_RAMP_MS),
+            limiter_ceiling: DEFAULT_LIMITER_CEILING_DB,
+            post_export: None,
+            empty_export_silence: 0.0,
+            synth_headroom: DEFAULT_SYNTH_HEADROOM,
+        }
+    }
+}
@@ -1,13 +1,23 @@
+mod export_job;
 mod export_setting;
 mod export_state;
 mod export_type;
 mod exportable;
 mod metadata;
 mod multi_file_suffix;
+mod normalization;
+mod overlap_policy;
+mod post_export;
+mod wav_bit_depth;
 
+pub(crate) use export_job::ExportJob;
 pub use export_setting::ExportSetting;
 pub use export_state::ExportState;
 pub use export_type::ExportType;
 pub(crate) use exportable::Exportable;
 pub use metadata::Metadata;
 pub use multi_file_suffix::MultiFileSuffix;
+pub use normalization::Normalization;
+pub use overlap_policy::OverlapPolicy;
+pub use post_export::PostExport;
+pub use wav_bit_depth::WavBitDepth;
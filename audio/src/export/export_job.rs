@@ -0,0 +1,27 @@
+use crate::export::Exportable;
+use crate::exporter::Exporter;
+use crate::types::{SharedExportCancelled, SharedExportFile};
+use crate::{SharedExportState, SharedSynth};
+use std::path::PathBuf;
+
+/// Everything that the export thread needs. See `Conn::export`.
+pub(crate) struct ExportJob {
+    /// The files that will be exported.
+    pub exportables: Vec<Exportable>,
+    /// The current export state.
+    pub export_state: SharedExportState,
+    /// If true, the export stops as soon as possible.
+    pub export_cancelled: SharedExportCancelled,
+    /// The index of the file that is being exported, and the total number of files.
+    pub export_file: SharedExportFile,
+    /// The synthesizer.
+    pub synth: SharedSynth,
+    /// A copy of the export settings.
+    pub exporter: Exporter,
+    /// The export path. If this is a multi-file export, each file's suffix is added to this path.
+    pub path: PathBuf,
+    /// The playback framerate. This is restored after the export.
+    pub player_framerate: f32,
+    /// If `exporter.limiter` is true, exported samples never exceed this value in dBFS.
+    pub limiter_ceiling: f32,
+}
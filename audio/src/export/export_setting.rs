@@ -15,6 +15,18 @@ pub enum ExportSetting {
     Mp3BitRate,
     Mp3Quality,
     OggQuality,
+    OpusBitRate,
     MultiFile,
     MultiFileSuffix,
+    MultiFileTail,
+    SelectionOnly,
+    Normalization,
+    LoudnessTarget,
+    PeakTarget,
+    Limiter,
+    MidiRunningStatus,
+    VelocityFloor,
+    Channels,
+    WavBitDepth,
+    Dither,
 }
@@ -13,3 +13,81 @@ pub enum ExportState {
     /// Done exporting.
     Done,
 }
+
+impl ExportState {
+    /// Returns the progress of the export of the current file, between 0 and 1.
+    /// Only rendering the notes reports its progress, so the file is fully rendered once we're appending the decay.
+    /// Returns None if we're not exporting.
+    pub fn progress(&self) -> Option<f32> {
+        match self {
+            ExportState::NotExporting => None,
+            ExportState::WritingWav {
+                total_samples,
+                exported_samples,
+            } => Some(if *total_samples == 0 {
+                0.0
+            } else {
+                (*exported_samples as f32 / *total_samples as f32).min(1.0)
+            }),
+            ExportState::AppendingDecay | ExportState::WritingToDisk | ExportState::Done => {
+                Some(1.0)
+            }
+        }
+    }
+
+    /// Returns the progress of the whole export, between 0 and 1, given the index of the current file and the total number of files.
+    /// Returns None if we're not exporting.
+    pub fn get_total_progress(&self, index: usize, num_files: usize) -> Option<f32> {
+        self.progress()
+            .map(|progress| ((index as f32 + progress) / num_files.max(1) as f32).min(1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExportState;
+
+    #[test]
+    fn export_progress() {
+        assert_eq!(ExportState::NotExporting.progress(), None);
+        let progress = |exported_samples| {
+            ExportState::WritingWav {
+                total_samples: 100,
+                exported_samples,
+            }
+            .progress()
+            .unwrap()
+        };
+        assert_eq!(progress(0), 0.0);
+        assert!(progress(50) > 0.0);
+        assert!(progress(50) < progress(100));
+        assert_eq!(progress(100), 1.0);
+        // The notes are rendered.
+        assert_eq!(ExportState::AppendingDecay.progress(), Some(1.0));
+        assert_eq!(ExportState::WritingToDisk.progress(), Some(1.0));
+        assert_eq!(ExportState::Done.progress(), Some(1.0));
+        // There is nothing to write.
+        let state = ExportState::WritingWav {
+            total_samples: 0,
+            exported_samples: 0,
+        };
+        assert_eq!(state.progress(), Some(0.0));
+    }
+
+    #[test]
+    fn export_total_progress() {
+        assert_eq!(ExportState::NotExporting.get_total_progress(0, 4), None);
+        let state = ExportState::WritingWav {
+            total_samples: 100,
+            exported_samples: 50,
+        };
+        // A single file.
+        assert_eq!(state.get_total_progress(0, 1), Some(0.5));
+        // Halfway through the second of four files.
+        assert_eq!(state.get_total_progress(1, 4), Some(0.375));
+        // The last file is done.
+        assert_eq!(ExportState::Done.get_total_progress(3, 4), Some(1.0));
+        // There are no files.
+        assert_eq!(ExportState::Done.get_total_progress(0, 0), Some(1.0));
+    }
+}
@@ -10,6 +10,7 @@ pub enum ExportType {
     MP3,
     Ogg,
     Flac,
+    Opus,
 }
 
 impl From<ExportType> for Extension {
@@ -20,6 +21,7 @@ impl From<ExportType> for Extension {
             ExportType::MP3 => Extension::MP3,
             ExportType::Ogg => Extension::Ogg,
             ExportType::Flac => Extension::Flac,
+            ExportType::Opus => Extension::Opus,
         }
     }
 }
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+/// How exported audio is normalized. Loudness normalization and peak normalization are mutually exclusive.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Deserialize, Serialize, Hash)]
+pub enum Normalization {
+    /// The audio isn't normalized.
+    #[default]
+    None,
+    /// Scale the audio so that its integrated loudness is the loudness target.
+    Loudness,
+    /// Scale the audio so that its loudest sample is at the peak target.
+    Peak,
+}
@@ -0,0 +1,103 @@
+use common::Note;
+use serde::{Deserialize, Serialize};
+
+/// How should we handle overlapping notes of the same pitch when exporting?
+///
+/// Two overlapping notes of the same pitch on the same channel produce an ambiguous note-off.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Deserialize, Serialize, Hash)]
+pub enum OverlapPolicy {
+    /// Extend the first note to the end of the second note and remove the second note.
+    Merge,
+    /// Shorten the first note so that it ends when the second note starts.
+    #[default]
+    Trim,
+    /// Don't change the notes.
+    Leave,
+}
+
+impl OverlapPolicy {
+    /// Returns a copy of `notes`, sorted by start time, with overlapping notes of the same pitch resolved.
+    ///
+    /// - `notes` The notes of a track.
+    pub fn apply(&self, notes: &[Note]) -> Vec<Note> {
+        let mut notes = notes.to_vec();
+        if *self == OverlapPolicy::Leave {
+            return notes;
+        }
+        notes.sort_by(|a, b| a.start.cmp(&b.start));
+        let mut resolved: Vec<Note> = Vec::with_capacity(notes.len());
+        for note in notes {
+            // Get the most recent note with the same pitch.
+            match resolved.iter().rposition(|n| n.note == note.note) {
+                Some(index) if resolved[index].end > note.start => match self {
+                    OverlapPolicy::Merge => {
+                        if note.end > resolved[index].end {
+                            resolved[index].end = note.end;
+                        }
+                    }
+                    _ => {
+                        resolved[index].end = note.start;
+                        resolved.push(note);
+                    }
+                },
+                _ => resolved.push(note),
+            }
+        }
+        // Remove any notes that were trimmed to nothing.
+        resolved.retain(|n| n.end > n.start);
+        resolved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OverlapPolicy;
+    use common::{Note, PPQ_U};
+
+    #[test]
+    fn overlap_merge() {
+        let notes = OverlapPolicy::Merge.apply(&get_notes());
+        assert_eq!(notes.len(), 2, "{:?}", notes);
+        assert_eq!(notes[0].start, 0, "{:?}", notes);
+        assert_eq!(notes[0].end, PPQ_U * 3, "{:?}", notes);
+        assert_eq!(notes[1].note, 64, "{:?}", notes);
+    }
+
+    #[test]
+    fn overlap_trim() {
+        let notes = OverlapPolicy::Trim.apply(&get_notes());
+        assert_eq!(notes.len(), 3, "{:?}", notes);
+        assert_eq!(notes[0].end, PPQ_U, "{:?}", notes);
+        assert_eq!(notes[1].note, 64, "{:?}", notes);
+        assert_eq!(notes[2].start, PPQ_U, "{:?}", notes);
+        assert_eq!(notes[2].end, PPQ_U * 3, "{:?}", notes);
+        // A note that is trimmed to nothing is removed.
+        let notes = OverlapPolicy::Trim.apply(&[get_note(60, 0, PPQ_U), get_note(60, 0, PPQ_U)]);
+        assert_eq!(notes.len(), 1, "{:?}", notes);
+    }
+
+    #[test]
+    fn overlap_leave() {
+        let notes = get_notes();
+        assert_eq!(OverlapPolicy::Leave.apply(&notes), notes);
+    }
+
+    fn get_notes() -> Vec<Note> {
+        vec![
+            get_note(60, 0, PPQ_U * 2),
+            get_note(64, 0, PPQ_U * 2),
+            get_note(60, PPQ_U, PPQ_U * 3),
+        ]
+    }
+
+    fn get_note(note: u8, start: u64, end: u64) -> Note {
+        Note {
+            note,
+            velocity: 120,
+            start,
+            end,
+            articulation: None,
+            tied: false,
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// This token in the command is replaced with the export path.
+const PATH_TOKEN: &str = "{path}";
+
+/// A shell command that runs after an export is done, for example to upload or convert the exported file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostExport {
+    /// The command template.
+    command: String,
+}
+
+impl PostExport {
+    /// Returns None if `command` is empty.
+    ///
+    /// - `command` The command template. `{path}` is replaced with the export path.
+    pub fn new(command: &str) -> Option<Self> {
+        let command = command.trim();
+        if command.is_empty() {
+            None
+        } else {
+            Some(Self {
+                command: command.to_string(),
+            })
+        }
+    }
+
+    /// Run the command and wait for it to finish. Returns true if the command succeeded.
+    ///
+    /// This blocks the calling thread, so it should only be called from a background thread.
+    ///
+    /// - `path` The export path.
+    pub(crate) fn run(&self, path: &Path) -> bool {
+        let command = self.get_command(path);
+        let output = if cfg!(windows) {
+            Command::new("cmd").args(["/C", &command]).output()
+        } else {
+            Command::new("sh").args(["-c", &command]).output()
+        };
+        match output {
+            Ok(output) => {
+                if output.status.success() {
+                    true
+                } else {
+                    println!(
+                        "Post-export command failed ({}): {}\n{}",
+                        output.status,
+                        command,
+                        String::from_utf8_lossy(&output.stderr)
+                    );
+                    false
+                }
+            }
+            Err(error) => {
+                println!("Failed to run post-export command {}: {}", command, error);
+                false
+            }
+        }
+    }
+
+    /// Run the command once per exported file. Returns true if every command succeeded.
+    ///
+    /// This blocks the calling thread, so it should only be called from a background thread.
+    ///
+    /// - `paths` The paths of the exported files.
+    pub(crate) fn run_each(&self, paths: &[PathBuf]) -> bool {
+        paths
+            .iter()
+            .fold(true, |success, path| self.run(path) && success)
+    }
+
+    /// Returns the command with the path token replaced by `path`.
+    fn get_command(&self, path: &Path) -> String {
+        self.command
+            .replace(PATH_TOKEN, path.to_str().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PostExport;
+    use std::path::Path;
+
+    #[test]
+    fn post_export() {
+        assert!(PostExport::new("").is_none());
+        assert!(PostExport::new("  ").is_none());
+        let post_export = PostExport::new("cp \"{path}\" backup/ && echo {path}").unwrap();
+        assert_eq!(
+            post_export.get_command(Path::new("music/song.wav")),
+            "cp \"music/song.wav\" backup/ && echo music/song.wav"
+        );
+        // A command without a token is unchanged.
+        let post_export = PostExport::new("echo done").unwrap();
+        assert_eq!(post_export.get_command(Path::new("song.wav")), "echo done");
+    }
+}
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+/// The bit depth and sample format of exported .wav files.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Deserialize, Serialize, Hash)]
+pub enum WavBitDepth {
+    /// 16-bit integer samples.
+    #[default]
+    Int16,
+    /// 24-bit integer samples.
+    Int24,
+    /// 32-bit float samples. These aren't scaled or quantized.
+    Float32,
+}
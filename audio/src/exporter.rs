@@ -1,15 +1,22 @@
-use crate::export::{ExportSetting, ExportType, Metadata, MultiFileSuffix};
+use crate::dither::Dither;
+use crate::export::{
+    ExportSetting, ExportType, Metadata, MultiFileSuffix, Normalization, OverlapPolicy, WavBitDepth,
+};
+use crate::running_status::remove_running_status;
 use crate::{AudioBuffer, SynthState};
 use chrono::Datelike;
 use chrono::Local;
 use common::IndexedValues;
-use common::{Index, Music, Time, U64orF32, DEFAULT_FRAMERATE, PPQ_F, PPQ_U};
+use common::{
+    Index, Music, Note, Ties, Time, U64orF32, DEFAULT_FRAMERATE, MAX_VOLUME, PPQ_F, PPQ_U,
+};
 use flacenc::bitsink::ByteSink;
 use flacenc::component::BitRepr;
 use flacenc::config::Encoder as FlacEncoder;
 use flacenc::encode_with_fixed_block_size;
 use flacenc::source::MemSource;
 use hound::{SampleFormat, WavSpec, WavWriter};
+use id3::frame::Comment;
 use id3::{Tag, TagLike, Version};
 use metaflac::Tag as FlacTag;
 use midly::num::{u15, u24, u28, u4};
@@ -17,18 +24,26 @@ use midly::{
     write_std, Format, Header, MetaMessage, MidiMessage, Timing, Track, TrackEvent, TrackEventKind,
 };
 use mp3lame_encoder::*;
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
 use oggvorbismeta::*;
-use serde::{Deserialize, Serialize};
+use opus::{Application, Bitrate as OpusBitrate, Channels, Encoder as OpusEncoder};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::fs::OpenOptions;
 use std::io::Read;
 use std::io::{Cursor, Write};
 use std::path::Path;
 use vorbis_encoder::Encoder;
 
-/// The number of channels.
+/// The number of channels in an audio buffer.
 const NUM_CHANNELS: usize = 2;
+/// The MIDI controller number of the channel volume.
+pub(crate) const CHANNEL_VOLUME_CC: u8 = 7;
 /// Conversion factor for f32 to i16.
 const F32_TO_I16: f32 = 32767.5;
+/// Conversion factor for f32 to 24-bit integers.
+const F32_TO_I24: f32 = 8388607.5;
+/// The minimum and maximum values of a 24-bit sample.
+const I24_RANGE: [f32; 2] = [-8388608.0, 8388607.0];
 /// An ordered list of MP3 bit rates. We can't use `IndexedValues` because this enum isn't serializable.
 pub const MP3_BIT_RATES: [Bitrate; 16] = [
     Bitrate::Kbps8,
@@ -48,6 +63,24 @@ pub const MP3_BIT_RATES: [Bitrate; 16] = [
     Bitrate::Kbps256,
     Bitrate::Kbps320,
 ];
+/// The framerates that the LAME encoder supports. Audio at any other framerate is resampled to `MP3_MAX_FRAMERATE`.
+const MP3_FRAMERATES: [u32; 9] = [8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000];
+/// The highest .mp3 framerate.
+const MP3_MAX_FRAMERATE: u32 = 48000;
+/// An ordered list of Opus bit rates in kbps.
+pub const OPUS_BIT_RATES: [u16; 13] = [6, 8, 12, 16, 24, 32, 48, 64, 96, 128, 160, 192, 256];
+/// The framerates that the Opus encoder supports. Audio at any other framerate is resampled to `OPUS_MAX_FRAMERATE`.
+const OPUS_FRAMERATES: [u32; 5] = [8000, 12000, 16000, 24000, 48000];
+/// The highest Opus framerate. Ogg Opus granule positions are always at this framerate.
+const OPUS_MAX_FRAMERATE: u32 = 48000;
+/// The maximum size of an encoded Opus packet in bytes.
+const OPUS_MAX_PACKET_SIZE: usize = 4000;
+/// The number of Opus frames per second, i.e. each frame is 20 ms.
+const OPUS_FRAMES_PER_SECOND: u32 = 50;
+/// An ordered list of loudness normalization targets in LUFS.
+pub const LOUDNESS_TARGETS: [i8; 4] = [-23, -18, -16, -14];
+/// An ordered list of peak normalization targets in dBFS.
+pub const PEAK_TARGETS: [f32; 4] = [-0.1, -0.3, -1.0, -3.0];
 /// An ordererd list of mp3 qualities. We can't use `IndexedValues` because this enum isn't serializable.
 pub const MP3_QUALITIES: [Quality; 10] = [
     Quality::Worst,
@@ -84,86 +117,106 @@ pub struct Exporter {
     pub multi_file: bool,
     /// Multi-file suffix setting.
     pub multi_file_suffix: IndexedValues<MultiFileSuffix, 3>,
+    /// If true, and if `multi_file` is true, export the decay tail of each track to a separate file.
+    #[serde(default)]
+    pub multi_file_tail: bool,
+    /// If true, and if many notes are selected, only the time span of the selection is exported. See `SelectMode::get_time_range`.
+    #[serde(default)]
+    pub selection_only: bool,
+    /// If true, apply triangular dither when converting samples to 16-bit.
+    /// This reduces quantization distortion in quiet passages at the cost of a slightly higher noise floor.
+    /// If false, the output is bit-exact.
+    /// 24-bit and 32-bit float .wav files are never dithered. .mp3 files aren't dithered either because the encoder receives float samples.
+    #[serde(default)]
+    pub dither: bool,
+    /// If true, apply a look-ahead peak limiter to the exported audio so that no sample exceeds the ceiling.
+    /// If false, the output is bit-exact, and samples that are too loud are clipped.
+    #[serde(default)]
+    pub limiter: bool,
+    /// How exported audio is normalized.
+    #[serde(default = "default_normalization")]
+    pub normalization: IndexedValues<Normalization, 3>,
+    /// The index of the loudness normalization target in `LOUDNESS_TARGETS`. This is only used if `normalization` is `Loudness`.
+    #[serde(default = "default_loudness_target")]
+    pub loudness_target: Index<usize>,
+    /// The index of the peak normalization target in `PEAK_TARGETS`. This is only used if `normalization` is `Peak`.
+    #[serde(default = "default_peak_target")]
+    pub peak_target: Index<usize>,
+    /// If true, .mid files are written with running status, which omits repeated status bytes.
+    /// If false, every channel message has an explicit status byte. The files are larger, but some older devices need this.
+    #[serde(default = "default_midi_running_status")]
+    pub midi_running_status: bool,
+    /// During playback and audio export, notes quieter than this velocity are played at this velocity. This doesn't change the notes.
+    #[serde(default = "default_velocity_floor")]
+    pub velocity_floor: Index<usize>,
+    /// How overlapping notes of the same pitch are handled.
+    #[serde(default)]
+    pub overlap_policy: OverlapPolicy,
+    /// The number of exported audio channels: 1 (mono) or 2 (stereo). Mono audio is the sum of the left and right channels.
+    /// Any other saved value is clamped to this range; see `deserialize_channels`.
+    #[serde(
+        default = "default_channels",
+        deserialize_with = "deserialize_channels"
+    )]
+    pub channels: u16,
+    /// The bit depth of exported .wav files.
+    #[serde(default = "default_wav_bit_depth")]
+    pub wav_bit_depth: IndexedValues<WavBitDepth, 3>,
     /// The .ogg file quality index.
     pub ogg_quality: Index<usize>,
-    /// The export type.
-    pub export_type: IndexedValues<ExportType, 5>,
+    /// The index of the .opus bit rate in `OPUS_BIT_RATES`.
+    #[serde(default = "default_opus_bit_rate")]
+    pub opus_bit_rate: Index<usize>,
+    /// The export type. Older save files that don't have .opus exporting are fixed in `Save`.
+    pub export_type: IndexedValues<ExportType, 6>,
     /// Export settings for .mid files.
-    pub mid_settings: IndexedValues<ExportSetting, 3>,
+    /// The lists of export settings are constant. Only the index is read from a save file; see `deserialize_settings`.
+    #[serde(
+        default = "default_mid_settings",
+        deserialize_with = "deserialize_mid_settings"
+    )]
+    pub mid_settings: IndexedValues<ExportSetting, 6>,
     /// Export settings for .wav files.
-    pub wav_settings: IndexedValues<ExportSetting, 3>,
+    #[serde(
+        default = "default_wav_settings",
+        deserialize_with = "deserialize_wav_settings"
+    )]
+    pub wav_settings: IndexedValues<ExportSetting, 13>,
     /// Export settings for .mp3 files.
-    pub mp3_settings: IndexedValues<ExportSetting, 12>,
+    #[serde(
+        default = "default_mp3_settings",
+        deserialize_with = "deserialize_mp3_settings"
+    )]
+    pub mp3_settings: IndexedValues<ExportSetting, 20>,
     /// Export settings for .ogg files.
-    pub ogg_settings: IndexedValues<ExportSetting, 11>,
+    #[serde(
+        default = "default_ogg_settings",
+        deserialize_with = "deserialize_ogg_settings"
+    )]
+    pub ogg_settings: IndexedValues<ExportSetting, 20>,
     /// Export settings for .flac files.
-    /// Use a default if the save file is pre-0.1.3
-    #[serde(default = "default_flac_settings")]
-    pub flac_settings: IndexedValues<ExportSetting, 10>,
+    #[serde(
+        default = "default_flac_settings",
+        deserialize_with = "deserialize_flac_settings"
+    )]
+    pub flac_settings: IndexedValues<ExportSetting, 18>,
+    /// Export settings for .opus files.
+    #[serde(
+        default = "default_opus_settings",
+        deserialize_with = "deserialize_opus_settings"
+    )]
+    pub opus_settings: IndexedValues<ExportSetting, 20>,
 }
 
 impl Default for Exporter {
     fn default() -> Self {
-        let export_type = IndexedValues::new(
-            0,
-            [
-                ExportType::Wav,
-                ExportType::Mid,
-                ExportType::MP3,
-                ExportType::Ogg,
-                ExportType::Flac,
-            ],
-        );
-        let mid_settings = IndexedValues::new(
-            0,
-            [
-                ExportSetting::Title,
-                ExportSetting::Artist,
-                ExportSetting::Copyright,
-            ],
-        );
-        let wav_settings = IndexedValues::new(
-            0,
-            [
-                ExportSetting::Framerate,
-                ExportSetting::MultiFile,
-                ExportSetting::MultiFileSuffix,
-            ],
-        );
-        let mp3_settings = IndexedValues::new(
-            0,
-            [
-                ExportSetting::Framerate,
-                ExportSetting::Mp3Quality,
-                ExportSetting::Mp3BitRate,
-                ExportSetting::Title,
-                ExportSetting::Artist,
-                ExportSetting::Copyright,
-                ExportSetting::Album,
-                ExportSetting::TrackNumber,
-                ExportSetting::Genre,
-                ExportSetting::Comment,
-                ExportSetting::MultiFile,
-                ExportSetting::MultiFileSuffix,
-            ],
-        );
-        let ogg_settings = IndexedValues::new(
-            0,
-            [
-                ExportSetting::Framerate,
-                ExportSetting::OggQuality,
-                ExportSetting::Title,
-                ExportSetting::Artist,
-                ExportSetting::Copyright,
-                ExportSetting::Album,
-                ExportSetting::TrackNumber,
-                ExportSetting::Genre,
-                ExportSetting::Comment,
-                ExportSetting::MultiFile,
-                ExportSetting::MultiFileSuffix,
-            ],
-        );
+        let export_type = default_export_type();
+        let mid_settings = default_mid_settings();
+        let wav_settings = default_wav_settings();
+        let mp3_settings = default_mp3_settings();
+        let ogg_settings = default_ogg_settings();
         let flac_settings = default_flac_settings();
+        let opus_settings = default_opus_settings();
         let multi_file_suffix = IndexedValues::new(
             0,
             [
@@ -178,20 +231,54 @@ impl Default for Exporter {
             mp3_bit_rate: Index::new(12, MP3_BIT_RATES.len()),
             mp3_quality: Index::new(9, MP3_QUALITIES.len()),
             ogg_quality: Index::new(9, 10),
+            opus_bit_rate: default_opus_bit_rate(),
             wav_settings,
             mid_settings,
             mp3_settings,
             ogg_settings,
             flac_settings,
+            opus_settings,
             multi_file_suffix,
             metadata: Metadata::default(),
             copyright: false,
             multi_file: false,
+            multi_file_tail: false,
+            selection_only: false,
+            dither: false,
+            limiter: false,
+            midi_running_status: true,
+            velocity_floor: default_velocity_floor(),
+            normalization: default_normalization(),
+            loudness_target: default_loudness_target(),
+            peak_target: default_peak_target(),
+            overlap_policy: OverlapPolicy::default(),
+            channels: default_channels(),
+            wav_bit_depth: default_wav_bit_depth(),
         }
     }
 }
 
 impl Exporter {
+    /// Returns the loudness normalization target in LUFS.
+    pub fn get_loudness_target(&self) -> i8 {
+        LOUDNESS_TARGETS[self.loudness_target.get()]
+    }
+
+    /// Returns the peak normalization target in dBFS.
+    pub fn get_peak_target(&self) -> f32 {
+        PEAK_TARGETS[self.peak_target.get()]
+    }
+
+    /// Returns true if audio is exported as mono.
+    pub fn is_mono(&self) -> bool {
+        self.channels == 1
+    }
+
+    /// Returns the velocity of a note during playback and audio export. See `velocity_floor`.
+    pub(crate) fn get_floored_velocity(&self, velocity: u8) -> u8 {
+        velocity.max(self.velocity_floor.get() as u8)
+    }
+
     /// Export to a .mid file.
     /// - `path` Output to this path.
     /// - `music` This is what we're saving.
@@ -218,7 +305,14 @@ impl Exporter {
 
         let mut tracks = vec![];
         let mut track_0 = Track::new();
-        for (i, midi_track) in music.midi_tracks.iter().enumerate() {
+        // If any tracks are selected for export, only export those tracks.
+        let export_selected = music.midi_tracks.iter().any(|t| t.export_selected);
+        for (i, midi_track) in music
+            .midi_tracks
+            .iter()
+            .filter(|t| !export_selected || t.export_selected)
+            .enumerate()
+        {
             if let Some(program) = synth_state.programs.get(&midi_track.channel) {
                 // Get track 0 or start a new track.
                 let mut track = Vec::new();
@@ -252,39 +346,56 @@ impl Exporter {
                     },
                 });
 
-                // Iterate through the notes.
-                let mut notes = midi_track.notes.clone();
-                // Sort the notes by start time.
-                notes.sort_by(|a, b| a.start.cmp(&b.start));
+                // Get the notes, sorted by start time, with overlapping notes resolved.
+                let notes = self.overlap_policy.apply(&midi_track.notes);
+                // Join tied notes.
+                let ties = Ties::new(&notes);
+                let notes: Vec<Note> = notes.iter().filter_map(|n| n.get_tied(&ties, 0)).collect();
                 // Get the start and end time.
                 let t0 = notes.iter().map(|n| n.start).min().unwrap();
+                // Get the volume envelope events.
+                let volume_events = midi_track.get_volume_events(t0);
                 // The delta is the first note.
                 let mut dt = t0;
                 let t1 = notes.iter().map(|n| n.end).max().unwrap();
                 // Iterate through all pulses.
                 for t in t0..t1 {
-                    // Get all note-on events.
-                    for note in notes.iter().filter(|n| n.start == t) {
+                    // Get all note-off events. These are before note-on events so that a note that ends when another starts doesn't cut it off.
+                    for note in notes.iter().filter(|n| n.end == t) {
                         let delta = Self::get_delta_time(&mut dt);
                         track.push(TrackEvent {
                             delta,
                             kind: TrackEventKind::Midi {
                                 channel,
-                                message: MidiMessage::NoteOn {
+                                message: MidiMessage::NoteOff {
                                     key: note.note.into(),
                                     vel: note.velocity.into(),
                                 },
                             },
                         });
                     }
-                    // Get all note-off events.
-                    for note in notes.iter().filter(|n| n.end == t) {
+                    // Get all channel volume events.
+                    for (_, value) in volume_events.iter().filter(|e| e.0 == t) {
                         let delta = Self::get_delta_time(&mut dt);
                         track.push(TrackEvent {
                             delta,
                             kind: TrackEventKind::Midi {
                                 channel,
-                                message: MidiMessage::NoteOff {
+                                message: MidiMessage::Controller {
+                                    controller: CHANNEL_VOLUME_CC.into(),
+                                    value: (*value).into(),
+                                },
+                            },
+                        });
+                    }
+                    // Get all note-on events.
+                    for note in notes.iter().filter(|n| n.start == t) {
+                        let delta = Self::get_delta_time(&mut dt);
+                        track.push(TrackEvent {
+                            delta,
+                            kind: TrackEventKind::Midi {
+                                channel,
+                                message: MidiMessage::NoteOn {
                                     key: note.note.into(),
                                     vel: note.velocity.into(),
                                 },
@@ -308,6 +419,9 @@ impl Exporter {
         if let Err(error) = write_std(&header, tracks.iter(), &mut buffer) {
             panic!("Error writing {:?} {:?}", path, error);
         }
+        if !self.midi_running_status {
+            buffer = remove_running_status(&buffer);
+        }
         Self::write_file(path, &buffer);
     }
 
@@ -317,38 +431,64 @@ impl Exporter {
     /// - `buffer` A buffer of wav data.
     pub(crate) fn wav(&self, path: &Path, buffer: &AudioBuffer) {
         // Get the spec.
+        let bit_depth = self.wav_bit_depth.get();
+        let (bits_per_sample, sample_format) = match bit_depth {
+            WavBitDepth::Int16 => (16, SampleFormat::Int),
+            WavBitDepth::Int24 => (24, SampleFormat::Int),
+            WavBitDepth::Float32 => (32, SampleFormat::Float),
+        };
         let spec = WavSpec {
-            channels: NUM_CHANNELS as u16,
+            channels: self.channels,
             sample_rate: self.framerate.get_u() as u32,
-            bits_per_sample: 16,
-            sample_format: SampleFormat::Int,
+            bits_per_sample,
+            sample_format,
         };
         // Write.
         let mut writer = WavWriter::create(path, spec).unwrap();
-        let mut i16_writer = writer.get_i16_writer(buffer[0].len() as u32 * (NUM_CHANNELS as u32));
-        for (l, r) in buffer[0].iter().zip(buffer[1].iter()) {
-            i16_writer.write_sample(Self::to_i16(l));
-            i16_writer.write_sample(Self::to_i16(r));
+        match bit_depth {
+            WavBitDepth::Int16 => {
+                let mut i16_writer =
+                    writer.get_i16_writer(buffer[0].len() as u32 * self.channels as u32);
+                for sample in self.get_i16_samples(buffer) {
+                    i16_writer.write_sample(sample);
+                }
+                i16_writer.flush().unwrap();
+            }
+            WavBitDepth::Int24 => {
+                for sample in self.get_interleaved_samples(buffer) {
+                    writer.write_sample(Self::to_i24(sample)).unwrap();
+                }
+            }
+            WavBitDepth::Float32 => {
+                for sample in self.get_interleaved_samples(buffer) {
+                    writer.write_sample(*sample).unwrap();
+                }
+            }
         }
-        i16_writer.flush().unwrap();
         writer.finalize().unwrap();
     }
 
     /// Export to a .mp3 file.
     ///
     /// - `path` The output path.
-    /// - `buffer` A buffer of wav data.
-    pub(crate) fn mp3<'a, T: 'a>(&self, path: &Path, buffer: &'a [Vec<T>; NUM_CHANNELS])
-    where
-        mp3lame_encoder::DualPcm<'a, T>: mp3lame_encoder::EncoderInput,
-    {
+    /// - `buffer` A buffer of wav data. If `self.channels` is 1, only the left channel is encoded.
+    pub(crate) fn mp3(&self, path: &Path, buffer: &AudioBuffer) {
+        let input_framerate = self.framerate.get_u() as u32;
+        // The LAME encoder only supports a few framerates.
+        let resampled;
+        let (buffer, framerate) = if MP3_FRAMERATES.contains(&input_framerate) {
+            (buffer, input_framerate)
+        } else {
+            resampled = Self::resample(buffer, input_framerate, MP3_MAX_FRAMERATE);
+            (&resampled, MP3_MAX_FRAMERATE)
+        };
         // Create the encoder.
         let mut mp3_encoder = Builder::new().expect("Create LAME builder");
         mp3_encoder
-            .set_num_channels(NUM_CHANNELS as u8)
+            .set_num_channels(self.channels as u8)
             .expect("Set channels");
         mp3_encoder
-            .set_sample_rate(self.framerate.get_u() as u32)
+            .set_sample_rate(framerate)
             .expect("Set sample rate");
         mp3_encoder
             .set_brate(MP3_BIT_RATES[self.mp3_bit_rate.get()])
@@ -358,17 +498,19 @@ impl Exporter {
             .expect("Set quality");
         // Build the encoder.
         let mut mp3_encoder = mp3_encoder.build().expect("To initialize LAME encoder");
-        // Get the input.
-        let input = DualPcm {
-            left: &buffer[0],
-            right: &buffer[1],
-        };
         // Get the output buffer.
         let mut mp3_out_buffer = Vec::with_capacity(max_required_buffer_size(buffer[0].len()));
         // Get the size.
-        let encoded_size = mp3_encoder
-            .encode(input, mp3_out_buffer.spare_capacity_mut())
-            .expect("To encode");
+        let encoded_size = if self.is_mono() {
+            mp3_encoder.encode(MonoPcm(&buffer[0]), mp3_out_buffer.spare_capacity_mut())
+        } else {
+            let input = DualPcm {
+                left: &buffer[0],
+                right: &buffer[1],
+            };
+            mp3_encoder.encode(input, mp3_out_buffer.spare_capacity_mut())
+        }
+        .expect("To encode");
         unsafe {
             mp3_out_buffer.set_len(mp3_out_buffer.len().wrapping_add(encoded_size));
         }
@@ -395,7 +537,11 @@ impl Exporter {
             tag.set_genre(genre);
         }
         if let Some(comment) = &self.metadata.comment {
-            tag.set_genre(comment);
+            tag.add_frame(Comment {
+                lang: "eng".to_string(),
+                description: String::new(),
+                text: comment.clone(),
+            });
         }
         if let Some(track_number) = &self.metadata.track_number {
             tag.set_track(*track_number);
@@ -410,13 +556,9 @@ impl Exporter {
     /// - `path` The output path.
     /// - `buffer` A buffer of wav data.
     pub(crate) fn ogg(&self, path: &Path, buffer: &AudioBuffer) {
-        let mut samples = vec![];
-        for (l, r) in buffer[0].iter().zip(buffer[1].iter()) {
-            samples.push(Self::to_i16(l));
-            samples.push(Self::to_i16(r));
-        }
+        let samples = self.get_i16_samples(buffer);
         let mut encoder = Encoder::new(
-            NUM_CHANNELS as u32,
+            self.channels as u32,
             self.framerate.get_u(),
             (self.ogg_quality.get() as f32 / 9.0) * 1.2 - 0.2,
         )
@@ -446,7 +588,7 @@ impl Exporter {
         if let Some(track_number) = &self.metadata.track_number {
             comments.add_tag_single("tracknumber", &track_number.to_string());
         }
-        if let Some(comment) = &self.metadata.genre {
+        if let Some(comment) = &self.metadata.comment {
             comments.add_tag_single("description", comment);
         }
         // Write the comments.
@@ -458,17 +600,180 @@ impl Exporter {
         Self::write_file(path, &out);
     }
 
+    /// Export to an .opus file.
+    ///
+    /// - `path` The output path.
+    /// - `buffer` A buffer of wav data.
+    pub(crate) fn opus(&self, path: &Path, buffer: &AudioBuffer) {
+        let input_framerate = self.framerate.get_u() as u32;
+        // The Opus encoder only supports a few framerates.
+        let resampled;
+        let (buffer, framerate) = if OPUS_FRAMERATES.contains(&input_framerate) {
+            (buffer, input_framerate)
+        } else {
+            resampled = Self::resample(buffer, input_framerate, OPUS_MAX_FRAMERATE);
+            (&resampled, OPUS_MAX_FRAMERATE)
+        };
+        let samples = self.get_i16_samples(buffer);
+        let channels = if self.is_mono() {
+            Channels::Mono
+        } else {
+            Channels::Stereo
+        };
+        let mut encoder = OpusEncoder::new(framerate, channels, Application::Audio)
+            .expect("Error creating .opus file encoder.");
+        encoder
+            .set_bitrate(OpusBitrate::Bits(
+                OPUS_BIT_RATES[self.opus_bit_rate.get()] as i32 * 1000,
+            ))
+            .expect("Error setting the .opus bit rate.");
+        // Granule positions are always at 48 kHz.
+        let granule_scale = (OPUS_MAX_FRAMERATE / framerate) as u64;
+        let lookahead = encoder
+            .get_lookahead()
+            .expect("Error getting the .opus encoder lookahead.") as usize;
+        let pre_skip = lookahead as u64 * granule_scale;
+        let num_channels = self.channels as usize;
+        let num_samples = samples.len() / num_channels;
+        // Encode some extra silence so that the last samples aren't lost to the lookahead.
+        let frame_size = (framerate / OPUS_FRAMES_PER_SECOND) as usize;
+        let num_frames = ((num_samples + lookahead) / frame_size + 1) as u64;
+        let end = pre_skip + num_samples as u64 * granule_scale;
+        let mut out = vec![];
+        {
+            let mut writer = PacketWriter::new(&mut out);
+            writer
+                .write_packet(
+                    self.get_opus_head(pre_skip as u16, input_framerate),
+                    0,
+                    PacketWriteEndInfo::EndPage,
+                    0,
+                )
+                .expect("Error writing the .opus header.");
+            writer
+                .write_packet(self.get_opus_tags(), 0, PacketWriteEndInfo::EndPage, 0)
+                .expect("Error writing the .opus comments.");
+            let mut frame = vec![0; frame_size * num_channels];
+            for i in 0..num_frames {
+                let start = (i as usize * frame.len()).min(samples.len());
+                let chunk = &samples[start..(start + frame.len()).min(samples.len())];
+                frame[..chunk.len()].copy_from_slice(chunk);
+                frame[chunk.len()..].fill(0);
+                let packet = encoder
+                    .encode_vec(&frame, OPUS_MAX_PACKET_SIZE)
+                    .expect("Error encoding .opus samples.");
+                let (end_info, granule) = if i == num_frames - 1 {
+                    (PacketWriteEndInfo::EndStream, end)
+                } else {
+                    (
+                        PacketWriteEndInfo::NormalPacket,
+                        (i + 1) * (frame_size as u64 * granule_scale),
+                    )
+                };
+                writer
+                    .write_packet(packet, 0, end_info, granule)
+                    .expect("Error writing .opus samples.");
+            }
+        }
+        // Write the file.
+        Self::write_file(path, &out);
+    }
+
+    /// Returns the Ogg Opus identification header.
+    ///
+    /// - `pre_skip` The number of samples at 48 kHz to discard from the start of the decoded audio.
+    /// - `input_framerate` The framerate of the audio before it was encoded.
+    fn get_opus_head(&self, pre_skip: u16, input_framerate: u32) -> Vec<u8> {
+        let mut head = b"OpusHead".to_vec();
+        // Version.
+        head.push(1);
+        head.push(self.channels as u8);
+        head.extend(pre_skip.to_le_bytes());
+        head.extend(input_framerate.to_le_bytes());
+        // Output gain.
+        head.extend(0i16.to_le_bytes());
+        // Channel mapping family.
+        head.push(0);
+        head
+    }
+
+    /// Returns the Ogg Opus comment header.
+    fn get_opus_tags(&self) -> Vec<u8> {
+        let mut comments = vec![
+            format!("title={}", self.metadata.title),
+            format!("date={}", Local::now().year()),
+        ];
+        if let Some(artist) = &self.metadata.artist {
+            comments.push(format!("artist={}", artist));
+            if self.copyright {
+                comments.push(format!("copyright={}", self.get_copyright(artist)));
+            }
+        }
+        if let Some(album) = &self.metadata.album {
+            comments.push(format!("album={}", album));
+        }
+        if let Some(genre) = &self.metadata.genre {
+            comments.push(format!("genre={}", genre));
+        }
+        if let Some(track_number) = &self.metadata.track_number {
+            comments.push(format!("tracknumber={}", track_number));
+        }
+        if let Some(comment) = &self.metadata.comment {
+            comments.push(format!("description={}", comment));
+        }
+        let vendor = "Cacophony";
+        let mut tags = b"OpusTags".to_vec();
+        tags.extend((vendor.len() as u32).to_le_bytes());
+        tags.extend(vendor.as_bytes());
+        tags.extend((comments.len() as u32).to_le_bytes());
+        for comment in comments {
+            tags.extend((comment.len() as u32).to_le_bytes());
+            tags.extend(comment.as_bytes());
+        }
+        tags
+    }
+
+    /// Resample audio with linear interpolation.
+    ///
+    /// - `buffer` The audio.
+    /// - `framerate` The framerate of the audio.
+    /// - `resampled_framerate` The framerate of the returned audio.
+    fn resample(buffer: &AudioBuffer, framerate: u32, resampled_framerate: u32) -> AudioBuffer {
+        let ratio = framerate as f64 / resampled_framerate as f64;
+        let len = (buffer[0].len() as u64 * resampled_framerate as u64).div_ceil(framerate as u64)
+            as usize;
+        [&buffer[0], &buffer[1]].map(|channel| {
+            (0..len)
+                .map(|i| {
+                    let t = i as f64 * ratio;
+                    let index = t as usize;
+                    let a = channel.get(index).copied().unwrap_or(0.0);
+                    let b = channel.get(index + 1).copied().unwrap_or(a);
+                    a + (b - a) * (t - index as f64) as f32
+                })
+                .collect()
+        })
+    }
+
     /// Encode to flac.
     pub(crate) fn flac(&self, path: &Path, buffer: &AudioBuffer) {
         // Convert to i32.
         let mut samples = vec![];
-        for (left, right) in buffer[0].iter().zip(buffer[1].iter()) {
-            samples.push(Self::to_i32(left));
-            samples.push(Self::to_i32(right));
+        if self.is_mono() {
+            samples.extend(buffer[0].iter().map(Self::to_i32));
+        } else {
+            for (left, right) in buffer[0].iter().zip(buffer[1].iter()) {
+                samples.push(Self::to_i32(left));
+                samples.push(Self::to_i32(right));
+            }
         }
         let config = FlacEncoder::default();
-        let source =
-            MemSource::from_samples(&samples, NUM_CHANNELS, 16, self.framerate.get_u() as usize);
+        let source = MemSource::from_samples(
+            &samples,
+            self.channels as usize,
+            16,
+            self.framerate.get_u() as usize,
+        );
         match encode_with_fixed_block_size(&config, source, config.block_sizes[0]) {
             Ok(flac_stream) => {
                 let mut sink = ByteSink::new();
@@ -492,9 +797,9 @@ impl Exporter {
                     tag.set_vorbis("genre", vec![genre.clone()]);
                 }
                 if let Some(track_number) = &self.metadata.track_number {
-                    tag.set_vorbis("track_number", vec![track_number.to_string()]);
+                    tag.set_vorbis("tracknumber", vec![track_number.to_string()]);
                 }
-                if let Some(comment) = &self.metadata.genre {
+                if let Some(comment) = &self.metadata.comment {
                     tag.set_vorbis("description", vec![comment.clone()]);
                 }
                 // Save the tag.
@@ -531,6 +836,53 @@ impl Exporter {
         (sample * F32_TO_I16).floor() as i16
     }
 
+    /// Converts a buffer of f32 samples to interleaved i16 samples. Applies dither if `self.dither` is true.
+    /// If `self.channels` is 1, only the left channel is converted.
+    fn get_i16_samples(&self, buffer: &AudioBuffer) -> Vec<i16> {
+        let num_channels = self.channels as usize;
+        let mut samples = Vec::with_capacity(buffer[0].len() * num_channels);
+        // Each channel has its own dither.
+        let mut dithers = Dither::per_channel();
+        for i in 0..buffer[0].len() {
+            for (channel, dither) in dithers.iter_mut().enumerate().take(num_channels) {
+                let sample = &buffer[channel][i];
+                samples.push(if self.dither {
+                    Self::to_i16_dithered(sample, dither)
+                } else {
+                    Self::to_i16(sample)
+                });
+            }
+        }
+        samples
+    }
+
+    /// Returns an iterator of interleaved f32 samples.
+    /// If `self.channels` is 1, only the left channel is returned.
+    fn get_interleaved_samples<'a>(
+        &self,
+        buffer: &'a AudioBuffer,
+    ) -> impl Iterator<Item = &'a f32> {
+        let num_channels = self.channels as usize;
+        (0..buffer[0].len())
+            .flat_map(move |i| (0..num_channels).map(move |channel| &buffer[channel][i]))
+    }
+
+    /// Converts an f32 sample to a 24-bit integer sample.
+    fn to_i24(sample: &f32) -> i32 {
+        (sample * F32_TO_I24)
+            .floor()
+            .clamp(I24_RANGE[0], I24_RANGE[1]) as i32
+    }
+
+    /// Converts an f32 sample to an i16 sample with dither. Digital silence isn't dithered, so silent audio stays silent.
+    fn to_i16_dithered(sample: &f32, dither: &mut Dither) -> i16 {
+        if *sample == 0.0 {
+            0
+        } else {
+            (sample * F32_TO_I16 + dither.next()).floor() as i16
+        }
+    }
+
     /// Converts an f32 sample to an i32 sample.
     fn to_i32(sample: &f32) -> i32 {
         (sample * F32_TO_I16).floor() as i32
@@ -542,7 +894,161 @@ impl Exporter {
     }
 }
 
-fn default_flac_settings() -> IndexedValues<ExportSetting, 10> {
+fn default_export_type() -> IndexedValues<ExportType, 6> {
+    IndexedValues::new(
+        0,
+        [
+            ExportType::Wav,
+            ExportType::Mid,
+            ExportType::MP3,
+            ExportType::Ogg,
+            ExportType::Flac,
+            ExportType::Opus,
+        ],
+    )
+}
+
+fn default_opus_bit_rate() -> Index<usize> {
+    Index::new(8, OPUS_BIT_RATES.len())
+}
+
+fn default_mid_settings() -> IndexedValues<ExportSetting, 6> {
+    IndexedValues::new(
+        0,
+        [
+            ExportSetting::Title,
+            ExportSetting::Artist,
+            ExportSetting::Copyright,
+            ExportSetting::MultiFile,
+            ExportSetting::MultiFileSuffix,
+            ExportSetting::MidiRunningStatus,
+        ],
+    )
+}
+
+fn default_midi_running_status() -> bool {
+    true
+}
+
+fn default_channels() -> u16 {
+    NUM_CHANNELS as u16
+}
+
+/// Deserialize the number of channels and clamp it to 1 (mono) or 2 (stereo).
+fn deserialize_channels<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+    Ok(u16::deserialize(deserializer)?.clamp(1, NUM_CHANNELS as u16))
+}
+
+fn default_wav_bit_depth() -> IndexedValues<WavBitDepth, 3> {
+    IndexedValues::new(
+        0,
+        [WavBitDepth::Int16, WavBitDepth::Int24, WavBitDepth::Float32],
+    )
+}
+
+fn default_velocity_floor() -> Index<usize> {
+    Index::new(0, MAX_VOLUME as usize + 1)
+}
+
+fn default_normalization() -> IndexedValues<Normalization, 3> {
+    IndexedValues::new(
+        0,
+        [
+            Normalization::None,
+            Normalization::Loudness,
+            Normalization::Peak,
+        ],
+    )
+}
+
+fn default_loudness_target() -> Index<usize> {
+    // -14 LUFS.
+    Index::new(3, LOUDNESS_TARGETS.len())
+}
+
+fn default_peak_target() -> Index<usize> {
+    // -1 dBFS.
+    Index::new(2, PEAK_TARGETS.len())
+}
+
+fn default_wav_settings() -> IndexedValues<ExportSetting, 13> {
+    IndexedValues::new(
+        0,
+        [
+            ExportSetting::Framerate,
+            ExportSetting::MultiFile,
+            ExportSetting::MultiFileSuffix,
+            ExportSetting::MultiFileTail,
+            ExportSetting::SelectionOnly,
+            ExportSetting::Normalization,
+            ExportSetting::LoudnessTarget,
+            ExportSetting::PeakTarget,
+            ExportSetting::Limiter,
+            ExportSetting::VelocityFloor,
+            ExportSetting::Channels,
+            ExportSetting::WavBitDepth,
+            ExportSetting::Dither,
+        ],
+    )
+}
+
+fn default_mp3_settings() -> IndexedValues<ExportSetting, 20> {
+    IndexedValues::new(
+        0,
+        [
+            ExportSetting::Framerate,
+            ExportSetting::Mp3Quality,
+            ExportSetting::Mp3BitRate,
+            ExportSetting::Title,
+            ExportSetting::Artist,
+            ExportSetting::Copyright,
+            ExportSetting::Album,
+            ExportSetting::TrackNumber,
+            ExportSetting::Genre,
+            ExportSetting::Comment,
+            ExportSetting::MultiFile,
+            ExportSetting::MultiFileSuffix,
+            ExportSetting::MultiFileTail,
+            ExportSetting::SelectionOnly,
+            ExportSetting::Normalization,
+            ExportSetting::LoudnessTarget,
+            ExportSetting::PeakTarget,
+            ExportSetting::Limiter,
+            ExportSetting::VelocityFloor,
+            ExportSetting::Channels,
+        ],
+    )
+}
+
+fn default_ogg_settings() -> IndexedValues<ExportSetting, 20> {
+    IndexedValues::new(
+        0,
+        [
+            ExportSetting::Framerate,
+            ExportSetting::OggQuality,
+            ExportSetting::Title,
+            ExportSetting::Artist,
+            ExportSetting::Copyright,
+            ExportSetting::Album,
+            ExportSetting::TrackNumber,
+            ExportSetting::Genre,
+            ExportSetting::Comment,
+            ExportSetting::MultiFile,
+            ExportSetting::MultiFileSuffix,
+            ExportSetting::MultiFileTail,
+            ExportSetting::SelectionOnly,
+            ExportSetting::Normalization,
+            ExportSetting::LoudnessTarget,
+            ExportSetting::PeakTarget,
+            ExportSetting::Limiter,
+            ExportSetting::VelocityFloor,
+            ExportSetting::Channels,
+            ExportSetting::Dither,
+        ],
+    )
+}
+
+fn default_flac_settings() -> IndexedValues<ExportSetting, 18> {
     IndexedValues::new(
         0,
         [
@@ -556,6 +1062,100 @@ fn default_flac_settings() -> IndexedValues<ExportSetting, 10> {
             ExportSetting::Comment,
             ExportSetting::MultiFile,
             ExportSetting::MultiFileSuffix,
+            ExportSetting::MultiFileTail,
+            ExportSetting::SelectionOnly,
+            ExportSetting::Normalization,
+            ExportSetting::LoudnessTarget,
+            ExportSetting::PeakTarget,
+            ExportSetting::Limiter,
+            ExportSetting::VelocityFloor,
+            ExportSetting::Channels,
         ],
     )
 }
+
+fn default_opus_settings() -> IndexedValues<ExportSetting, 20> {
+    IndexedValues::new(
+        0,
+        [
+            ExportSetting::Framerate,
+            ExportSetting::OpusBitRate,
+            ExportSetting::Title,
+            ExportSetting::Artist,
+            ExportSetting::Copyright,
+            ExportSetting::Album,
+            ExportSetting::TrackNumber,
+            ExportSetting::Genre,
+            ExportSetting::Comment,
+            ExportSetting::MultiFile,
+            ExportSetting::MultiFileSuffix,
+            ExportSetting::MultiFileTail,
+            ExportSetting::SelectionOnly,
+            ExportSetting::Normalization,
+            ExportSetting::LoudnessTarget,
+            ExportSetting::PeakTarget,
+            ExportSetting::Limiter,
+            ExportSetting::VelocityFloor,
+            ExportSetting::Channels,
+            ExportSetting::Dither,
+        ],
+    )
+}
+
+/// The index of a saved list of export settings.
+#[derive(Deserialize)]
+struct SavedSettings {
+    index: Index<usize>,
+}
+
+/// Deserialize a list of export settings. The values are always `settings`, so save files with older lists of settings can still be read.
+/// The saved index is kept if it's in range.
+///
+/// - `deserializer` The deserializer.
+/// - `settings` The default list of settings.
+fn deserialize_settings<'de, D: Deserializer<'de>, const N: usize>(
+    deserializer: D,
+    mut settings: IndexedValues<ExportSetting, N>,
+) -> Result<IndexedValues<ExportSetting, N>, D::Error> {
+    let saved = SavedSettings::deserialize(deserializer)?;
+    if saved.index.get() < N {
+        settings.index.set(saved.index.get());
+    }
+    Ok(settings)
+}
+
+fn deserialize_mid_settings<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<IndexedValues<ExportSetting, 6>, D::Error> {
+    deserialize_settings(deserializer, default_mid_settings())
+}
+
+fn deserialize_wav_settings<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<IndexedValues<ExportSetting, 13>, D::Error> {
+    deserialize_settings(deserializer, default_wav_settings())
+}
+
+fn deserialize_mp3_settings<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<IndexedValues<ExportSetting, 20>, D::Error> {
+    deserialize_settings(deserializer, default_mp3_settings())
+}
+
+fn deserialize_ogg_settings<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<IndexedValues<ExportSetting, 20>, D::Error> {
+    deserialize_settings(deserializer, default_ogg_settings())
+}
+
+fn deserialize_flac_settings<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<IndexedValues<ExportSetting, 18>, D::Error> {
+    deserialize_settings(deserializer, default_flac_settings())
+}
+
+fn deserialize_opus_settings<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<IndexedValues<ExportSetting, 20>, D::Error> {
+    deserialize_settings(deserializer, default_opus_settings())
+}
@@ -0,0 +1,116 @@
+use crate::AudioBuffer;
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Audio that was rendered from frozen tracks. During live playback, this is played instead of the tracks' notes.
+#[derive(Default)]
+pub(crate) struct FrozenAudio {
+    /// The audio buffers of each frozen track. The first sample of each buffer is at time 0.
+    buffers: Vec<Arc<AudioBuffer>>,
+}
+
+impl FrozenAudio {
+    pub(crate) fn new(buffers: Vec<Arc<AudioBuffer>>) -> Self {
+        Self { buffers }
+    }
+
+    /// Returns true if there is frozen audio at time `t` or later.
+    pub(crate) fn is_playing(&self, t: u64) -> bool {
+        self.buffers.iter().any(|b| (t as usize) < b[0].len())
+    }
+
+    /// Returns the sum of the frozen audio samples at time `t`.
+    ///
+    /// - `t` The time in samples.
+    /// - `gain` Multiply the samples by this value. This should be the synthesizer's gain, because the audio was rendered at a gain of 1.
+    pub(crate) fn get_sample(&self, t: u64, gain: f32) -> (f32, f32) {
+        let t = t as usize;
+        self.buffers
+            .iter()
+            .filter(|b| t < b[0].len())
+            .fold((0.0, 0.0), |(left, right), b| {
+                (left + b[0][t] * gain, right + b[1][t] * gain)
+            })
+    }
+
+    /// Remove all frozen audio.
+    pub(crate) fn clear(&mut self) {
+        self.buffers.clear()
+    }
+}
+
+/// Write rendered audio to a lossless 32-bit float .wav file. Returns true if the file was written.
+///
+/// - `path` The output path.
+/// - `framerate` The framerate of the audio.
+/// - `buffer` The audio.
+pub(crate) fn write_frozen_audio(path: &Path, framerate: u32, buffer: &AudioBuffer) -> bool {
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: framerate,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+    let mut writer = match WavWriter::create(path, spec) {
+        Ok(writer) => writer,
+        Err(error) => {
+            println!("Failed to write frozen audio {:?}: {}", path, error);
+            return false;
+        }
+    };
+    for (left, right) in buffer[0].iter().zip(buffer[1].iter()) {
+        if writer.write_sample(*left).is_err() || writer.write_sample(*right).is_err() {
+            return false;
+        }
+    }
+    writer.finalize().is_ok()
+}
+
+/// Read a .wav file that was written by `write_frozen_audio`.
+///
+/// Returns None if the file can't be read, or if its framerate isn't `framerate`. In that case, the track should be synthesized instead.
+///
+/// - `path` The path to the file.
+/// - `framerate` The playback framerate.
+pub(crate) fn read_frozen_audio(path: &Path, framerate: u32) -> Option<AudioBuffer> {
+    let mut reader = WavReader::open(path).ok()?;
+    let spec = reader.spec();
+    if spec.channels != 2
+        || spec.sample_rate != framerate
+        || spec.sample_format != SampleFormat::Float
+    {
+        return None;
+    }
+    let samples = reader
+        .samples::<f32>()
+        .collect::<Result<Vec<f32>, _>>()
+        .ok()?;
+    let left = samples.iter().step_by(2).copied().collect();
+    let right = samples.iter().skip(1).step_by(2).copied().collect();
+    Some([left, right])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frozen_audio() {
+        let path = std::env::temp_dir().join("frozen_audio.wav");
+        let buffer = [vec![0.5, -0.25, 0.125], vec![0.25, 0.5, 0.75]];
+        assert!(write_frozen_audio(&path, 44100, &buffer));
+        // The framerate doesn't match.
+        assert!(read_frozen_audio(&path, 48000).is_none());
+        let read = read_frozen_audio(&path, 44100).unwrap();
+        assert_eq!(read, buffer);
+        std::fs::remove_file(&path).unwrap();
+        // Mix two buffers.
+        let frozen_audio = FrozenAudio::new(vec![Arc::new(read), Arc::new([vec![0.5], vec![0.5]])]);
+        assert_eq!(frozen_audio.get_sample(0, 2.0), (2.0, 1.5));
+        assert_eq!(frozen_audio.get_sample(1, 1.0), (-0.25, 0.5));
+        assert!(frozen_audio.is_playing(2));
+        assert!(!frozen_audio.is_playing(3));
+        assert_eq!(frozen_audio.get_sample(3, 1.0), (0.0, 0.0));
+    }
+}
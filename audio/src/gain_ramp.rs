@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+/// The default duration of a gain ramp in milliseconds.
+pub(crate) const DEFAULT_GAIN_RAMP_MS: u64 = 10;
+
+/// Interpolates the synthesizer gain toward a target gain.
+///
+/// Setting the gain instantly during playback can cause an audible click ("zipper noise").
+/// Instead, the gain is changed by a small amount per frame until it reaches the target.
+pub(crate) struct GainRamp {
+    /// The current gain.
+    current: f32,
+    /// The target gain.
+    target: f32,
+    /// The change in gain per frame.
+    delta: f32,
+    /// The number of frames until we reach the target.
+    remaining: u32,
+    /// The length of a ramp in frames.
+    length: u32,
+}
+
+impl GainRamp {
+    pub(crate) fn new(gain: f32) -> Self {
+        Self {
+            current: gain,
+            target: gain,
+            delta: 0.0,
+            remaining: 0,
+            length: 0,
+        }
+    }
+
+    /// Set the length of a ramp.
+    ///
+    /// - `duration` The duration of a ramp.
+    /// - `framerate` The audio framerate.
+    pub(crate) fn set_length(&mut self, duration: Duration, framerate: f32) {
+        self.length = (duration.as_secs_f32() * framerate) as u32;
+    }
+
+    /// Start ramping toward a new target gain.
+    pub(crate) fn set_target(&mut self, target: f32) {
+        self.target = target;
+        if self.length == 0 {
+            self.finish();
+        } else {
+            self.delta = (target - self.current) / self.length as f32;
+            self.remaining = self.length;
+        }
+    }
+
+    /// Skip the ramp and set the current gain to the target gain. Returns the target gain.
+    pub(crate) fn finish(&mut self) -> f32 {
+        self.current = self.target;
+        self.remaining = 0;
+        self.current
+    }
+
+    /// Advance the ramp by `frames` frames. Returns the new gain, or None if we aren't ramping.
+    pub(crate) fn advance(&mut self, frames: u32) -> Option<f32> {
+        if self.remaining == 0 {
+            None
+        } else if frames >= self.remaining {
+            Some(self.finish())
+        } else {
+            self.current += self.delta * frames as f32;
+            self.remaining -= frames;
+            Some(self.current)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GainRamp;
+    use std::time::Duration;
+
+    #[test]
+    fn gain_ramp() {
+        let mut ramp = GainRamp::new(1.0);
+        assert_eq!(ramp.advance(1), None);
+        // 10 frames.
+        ramp.set_length(Duration::from_millis(10), 1000.0);
+        ramp.set_target(0.0);
+        let g = ramp.advance(1).unwrap();
+        assert!((g - 0.9).abs() < 0.0001, "{}", g);
+        let g = ramp.advance(4).unwrap();
+        assert!((g - 0.5).abs() < 0.0001, "{}", g);
+        assert_eq!(ramp.advance(100), Some(0.0));
+        assert_eq!(ramp.advance(1), None);
+        // Finish immediately.
+        ramp.set_target(1.0);
+        assert_eq!(ramp.finish(), 1.0);
+        assert_eq!(ramp.advance(1), None);
+        // There is no ramp.
+        ramp.set_length(Duration::ZERO, 1000.0);
+        ramp.set_target(0.5);
+        assert_eq!(ramp.advance(1), None);
+    }
+}
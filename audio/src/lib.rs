@@ -6,24 +6,32 @@
 //!
 //! Various data structs are shared in a Arc<Mutex<T>> format. These aren't a unified struct because they need to be locked at different times.
 //!
-//! As far as external crates are concerned, it's only necessary to create a new Conn: `Conn::default()`.
+//! As far as external crates are concerned, it's only necessary to create a new Conn: `Conn::default()` or `Conn::new(settings)`. See `ConnSettings`.
 
 mod command;
 mod conn;
+mod conn_settings;
 mod decayer;
+mod dither;
 pub mod export;
 pub mod exporter;
+mod frozen_audio;
+mod gain_ramp;
+mod limiter;
+mod loudness;
 pub(crate) mod midi_event_queue;
 pub mod play_state;
 mod player;
 mod program;
+mod running_status;
 mod synth_state;
 pub(crate) mod timed_midi_event;
 mod types;
 pub use crate::command::Command;
 pub use crate::conn::Conn;
+pub use crate::conn_settings::ConnSettings;
 use crate::program::Program;
 pub use crate::synth_state::SynthState;
-pub(crate) use crate::types::{AudioBuffer, SharedMidiEventQueue, SharedSynth};
+pub(crate) use crate::types::{AudioBuffer, SharedGainRamp, SharedMidiEventQueue, SharedSynth};
 pub use crate::types::{AudioMessage, CommandsMessage, SharedExportState, SharedPlayState};
 use player::Player;
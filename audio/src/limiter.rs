@@ -0,0 +1,135 @@
+use crate::AudioBuffer;
+use std::collections::VecDeque;
+
+/// The default ceiling in dBFS.
+pub(crate) const DEFAULT_LIMITER_CEILING_DB: f32 = -0.3;
+/// The look-ahead time in seconds.
+const LOOK_AHEAD_SECONDS: f32 = 0.005;
+/// The release time in seconds.
+const RELEASE_SECONDS: f32 = 0.1;
+
+/// A brickwall look-ahead peak limiter.
+///
+/// This processes an entire buffer at once, so it doesn't delay the audio.
+/// Instead, gain reduction starts up to `LOOK_AHEAD_SECONDS` before a peak and ramps down so that the peak never exceeds the ceiling.
+/// After the peak, the gain recovers over `RELEASE_SECONDS`.
+pub(crate) struct Limiter {
+    /// The maximum absolute value of a sample.
+    ceiling: f32,
+    /// The look-ahead length in samples.
+    look_ahead: usize,
+    /// The per-sample release coefficient.
+    release: f32,
+}
+
+impl Limiter {
+    /// - `ceiling_db` The maximum absolute value of a sample in dBFS.
+    /// - `framerate` The framerate of the audio.
+    pub(crate) fn new(ceiling_db: f32, framerate: f32) -> Self {
+        let ceiling = 10f32.powf(ceiling_db.min(0.0) / 20.0);
+        let look_ahead = ((LOOK_AHEAD_SECONDS * framerate) as usize).max(1);
+        let release = 1.0 - (-1.0 / (RELEASE_SECONDS * framerate)).exp();
+        Self {
+            ceiling,
+            look_ahead,
+            release,
+        }
+    }
+
+    /// Limit the peaks of a stereo buffer. Both channels receive the same gain.
+    pub(crate) fn apply(&self, buffer: &mut AudioBuffer) {
+        let len = buffer[0].len();
+        if len == 0 {
+            return;
+        }
+        // The gain required to keep each sample under the ceiling.
+        let required: Vec<f32> = buffer[0]
+            .iter()
+            .zip(buffer[1].iter())
+            .map(|(l, r)| {
+                let peak = l.abs().max(r.abs());
+                if peak > self.ceiling {
+                    self.ceiling / peak
+                } else {
+                    1.0
+                }
+            })
+            .collect();
+        // Nothing to do.
+        if required.iter().all(|g| *g >= 1.0) {
+            return;
+        }
+        // The minimum required gain in the look-ahead window starting at each sample.
+        let mut window_min = vec![1.0; len];
+        let mut deque: VecDeque<usize> = VecDeque::new();
+        for i in (0..len).rev() {
+            while let Some(&j) = deque.back() {
+                if required[j] >= required[i] {
+                    deque.pop_back();
+                } else {
+                    break;
+                }
+            }
+            deque.push_back(i);
+            while let Some(&j) = deque.front() {
+                if j > i + self.look_ahead {
+                    deque.pop_front();
+                } else {
+                    break;
+                }
+            }
+            window_min[i] = required[*deque.front().unwrap()];
+        }
+        // Smooth the attack with a moving average that spans the look-ahead window.
+        // Every value in the average is at most the required gain of the peak, so the peak is still limited.
+        let mut sum = 0.0f64;
+        let mut gain = 1.0f32;
+        for i in 0..len {
+            sum += window_min[i] as f64;
+            let count = if i >= self.look_ahead {
+                sum -= window_min[i - self.look_ahead] as f64;
+                self.look_ahead
+            } else {
+                i + 1
+            };
+            // Pad the start of the buffer with unity gain.
+            let average =
+                ((sum + (self.look_ahead - count) as f64) / self.look_ahead as f64) as f32;
+            let attack = average.min(window_min[i]).min(1.0);
+            // Release toward unity gain, but never above the attack envelope.
+            gain = (gain + (1.0 - gain) * self.release).min(attack);
+            buffer[0][i] = (buffer[0][i] * gain).clamp(-self.ceiling, self.ceiling);
+            buffer[1][i] = (buffer[1][i] * gain).clamp(-self.ceiling, self.ceiling);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Limiter;
+
+    #[test]
+    fn limiter() {
+        let limiter = Limiter::new(-6.0, 44100.0);
+        let ceiling = 10f32.powf(-6.0 / 20.0);
+        // A quiet signal isn't changed.
+        let quiet: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.1).sin() * 0.25).collect();
+        let mut buffer = [quiet.clone(), quiet.clone()];
+        limiter.apply(&mut buffer);
+        assert_eq!(buffer[0], quiet);
+        assert_eq!(buffer[1], quiet);
+        // A loud signal never exceeds the ceiling.
+        let loud: Vec<f32> = (0..10000)
+            .map(|i| (i as f32 * 0.05).sin() * if i > 5000 { 1.5 } else { 0.25 })
+            .collect();
+        let mut buffer = [loud.clone(), loud.iter().map(|s| -s * 0.5).collect()];
+        limiter.apply(&mut buffer);
+        assert!(buffer[0].iter().all(|s| s.abs() <= ceiling));
+        assert!(buffer[1].iter().all(|s| s.abs() <= ceiling));
+        // The gain starts to reduce before the loud part, but not long before.
+        assert_eq!(buffer[0][..4700], loud[..4700]);
+        assert!(buffer[0][4990].abs() < loud[4990].abs());
+        // Both channels get the same gain.
+        assert!((buffer[1][6000] + buffer[0][6000] * 0.5).abs() < 0.0001);
+    }
+}
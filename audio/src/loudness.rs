@@ -0,0 +1,298 @@
+use crate::AudioBuffer;
+use std::f64::consts::PI;
+
+/// Subtract this from the mean-square level in dB, as in ITU-R BS.1770.
+const LOUDNESS_OFFSET: f64 = -0.691;
+/// Without a limiter, normalization won't make peaks louder than this.
+const MAX_PEAK: f32 = 0.999;
+/// The duration of a gating block in seconds.
+const BLOCK_DURATION: f64 = 0.4;
+/// The duration between the starts of two consecutive gating blocks in seconds, i.e. the blocks overlap by 75%.
+const BLOCK_STEP: f64 = 0.1;
+/// Blocks quieter than this, in LUFS, are ignored.
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Blocks that are this many LU quieter than the ungated loudness are ignored.
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Returns the integrated loudness of a buffer in LUFS, as defined in ITU-R BS.1770-4, or None if the buffer is silent.
+///
+/// Each channel is K-weighted and then split into overlapping 400 ms blocks. Blocks that are below the absolute and relative gates are ignored.
+/// If the buffer is shorter than one block, the whole buffer is one block.
+///
+/// - `buffer` The audio channels.
+/// - `framerate` The framerate of the audio.
+pub(crate) fn get_loudness(buffer: &[Vec<f32>], framerate: f32) -> Option<f32> {
+    let len = buffer[0].len();
+    if len == 0 {
+        return None;
+    }
+    let framerate = framerate as f64;
+    // K-weight each channel and square the samples.
+    let squared: Vec<Vec<f64>> = buffer
+        .iter()
+        .map(|channel| {
+            let mut filter = KWeighting::new(framerate);
+            channel
+                .iter()
+                .map(|s| {
+                    let s = filter.process(*s as f64);
+                    s * s
+                })
+                .collect()
+        })
+        .collect();
+    // Get the mean square of each block, summed across the channels.
+    let block_len = ((BLOCK_DURATION * framerate) as usize).clamp(1, len);
+    let step = ((BLOCK_STEP * framerate) as usize).max(1);
+    let blocks: Vec<f64> = (0..=len - block_len)
+        .step_by(step)
+        .map(|start| {
+            squared
+                .iter()
+                .map(|channel| channel[start..start + block_len].iter().sum::<f64>())
+                .sum::<f64>()
+                / block_len as f64
+        })
+        .collect();
+    // Apply the absolute gate.
+    let blocks: Vec<f64> = blocks
+        .into_iter()
+        .filter(|z| to_lufs(*z) > ABSOLUTE_GATE)
+        .collect();
+    if blocks.is_empty() {
+        return None;
+    }
+    // Apply the relative gate.
+    let relative_gate = to_lufs(mean(&blocks)) + RELATIVE_GATE;
+    let blocks: Vec<f64> = blocks
+        .into_iter()
+        .filter(|z| to_lufs(*z) > relative_gate)
+        .collect();
+    if blocks.is_empty() {
+        None
+    } else {
+        Some(to_lufs(mean(&blocks)) as f32)
+    }
+}
+
+/// Scale a buffer so that its integrated loudness is `target`.
+///
+/// - `buffer` The audio buffer.
+/// - `target` The target loudness in LUFS.
+/// - `limit` If true, a limiter will be applied afterwards, so the gain can push the peaks past full scale. If false, the gain is reduced so that the peaks never clip.
+/// - `framerate` The framerate of the audio.
+/// - `mono` If true, both channels are the same mono signal. The loudness is measured on one channel, because a mono file is played back as one channel.
+pub(crate) fn normalize(
+    buffer: &mut AudioBuffer,
+    target: f32,
+    limit: bool,
+    framerate: f32,
+    mono: bool,
+) {
+    let channels = if mono { &buffer[..1] } else { &buffer[..] };
+    let loudness = match get_loudness(channels, framerate) {
+        Some(loudness) => loudness,
+        None => return,
+    };
+    let mut gain = 10f32.powf((target - loudness) / 20.0);
+    if !limit {
+        let peak = get_peak(buffer);
+        if peak * gain > MAX_PEAK {
+            gain = MAX_PEAK / peak;
+        }
+    }
+    buffer
+        .iter_mut()
+        .flat_map(|channel| channel.iter_mut())
+        .for_each(|s| *s *= gain);
+}
+
+/// Scale a buffer so that its loudest sample is at `target`.
+/// Both channels are scaled by the same gain, so the stereo balance doesn't change.
+///
+/// - `buffer` The audio buffer.
+/// - `target` The target peak in dBFS.
+pub(crate) fn normalize_peak(buffer: &mut AudioBuffer, target: f32) {
+    let peak = get_peak(buffer);
+    if peak == 0.0 {
+        return;
+    }
+    let gain = 10f32.powf(target / 20.0) / peak;
+    buffer
+        .iter_mut()
+        .flat_map(|channel| channel.iter_mut())
+        .for_each(|s| *s *= gain);
+}
+
+/// Returns the absolute value of the loudest sample.
+fn get_peak(buffer: &AudioBuffer) -> f32 {
+    buffer
+        .iter()
+        .flat_map(|channel| channel.iter())
+        .fold(0.0f32, |peak, s| peak.max(s.abs()))
+}
+
+/// Converts a mean-square level to LUFS.
+fn to_lufs(mean_square: f64) -> f64 {
+    LOUDNESS_OFFSET + 10.0 * mean_square.log10()
+}
+
+/// Returns the mean of a slice of values.
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// A biquad filter.
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    /// The previous two input samples.
+    x: [f64; 2],
+    /// The previous two output samples.
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// The K-weighting filter of ITU-R BS.1770: A high shelf that models the acoustic effect of the head, followed by a high-pass filter.
+/// The coefficients are derived for any framerate, as in libebur128.
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new(framerate: f64) -> Self {
+        // The high shelf.
+        let f0 = 1681.974450955533;
+        let gain = 3.999843853973347;
+        let q = 0.7071752369554196;
+        let k = (PI * f0 / framerate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+        // The high-pass filter.
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+        let k = (PI * f0 / framerate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+        Self { shelf, high_pass }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAMERATE: f32 = 44100.0;
+
+    #[test]
+    fn loudness() {
+        // A full-scale 997 Hz sine in one channel is -3.01 LUFS.
+        let sine: Vec<f32> = (0..44100)
+            .map(|i| (i as f32 / FRAMERATE * 997.0 * std::f32::consts::TAU).sin())
+            .collect();
+        let loudness = get_loudness(&[sine.clone(), vec![0.0; 44100]], FRAMERATE).unwrap();
+        assert!((loudness + 3.01).abs() < 0.05, "{}", loudness);
+        // Silence is gated. Without gating, this would be -6.02 LUFS.
+        // It's a bit quieter than the sine because some blocks are partially silent.
+        let mut channel = sine;
+        channel.extend([0.0; 44100]);
+        let loudness = get_loudness(&[channel, vec![0.0; 88200]], FRAMERATE).unwrap();
+        assert!((-4.0..-3.0).contains(&loudness), "{}", loudness);
+        // Silence.
+        assert!(get_loudness(&[vec![0.0; 100], vec![0.0; 100]], FRAMERATE).is_none());
+        assert!(get_loudness(&[vec![], vec![]], FRAMERATE).is_none());
+        // A quiet signal is made louder.
+        let mut buffer = get_buffer(0.01);
+        normalize(&mut buffer, -18.0, false, FRAMERATE, false);
+        let loudness = get_loudness(&buffer, FRAMERATE).unwrap();
+        assert!((loudness + 18.0).abs() < 0.01, "{}", loudness);
+        // A loud signal is made quieter.
+        let mut buffer = get_buffer(0.9);
+        normalize(&mut buffer, -23.0, false, FRAMERATE, false);
+        let loudness = get_loudness(&buffer, FRAMERATE).unwrap();
+        assert!((loudness + 23.0).abs() < 0.01, "{}", loudness);
+        // Without a limiter, normalization never clips.
+        let mut buffer = get_buffer(0.5);
+        normalize(&mut buffer, 0.0, false, FRAMERATE, false);
+        assert!(buffer.iter().flatten().all(|s| s.abs() <= MAX_PEAK));
+        // With a limiter, normalization can exceed full scale.
+        let mut buffer = get_buffer(0.5);
+        normalize(&mut buffer, 0.0, true, FRAMERATE);
+        assert!(buffer.iter().flatten().any(|s| s.abs() > 1.0));
+        // A mono signal is measured on one channel, so it isn't 3 dB too loud.
+        let mut buffer = get_buffer(0.1);
+        normalize(&mut buffer, -23.0, false, FRAMERATE, true);
+        let loudness = get_loudness(&buffer[..1], FRAMERATE).unwrap();
+        assert!((loudness + 23.0).abs() < 0.01, "{}", loudness);
+        assert_eq!(buffer[0], buffer[1]);
+        // Measured on both channels, the same signal is 3 dB louder.
+        let loudness = get_loudness(&buffer, FRAMERATE).unwrap();
+        assert!((loudness + 19.99).abs() < 0.01, "{}", loudness);
+    }
+
+    #[test]
+    fn peak() {
+        // Silence.
+        let mut buffer = [vec![0.0; 100], vec![0.0; 100]];
+        normalize_peak(&mut buffer, -1.0);
+        assert!(buffer.iter().flatten().all(|s| *s == 0.0));
+        // A quiet signal is made louder and a loud signal is made quieter.
+        for (amplitude, target) in [(0.1, -1.0), (2.0, -0.3), (0.5, -6.0)] {
+            let mut buffer = get_buffer(amplitude);
+            // The right channel is quieter.
+            buffer[1].iter_mut().for_each(|s| *s *= 0.5);
+            normalize_peak(&mut buffer, target);
+            let peak = buffer.iter().flatten().fold(0.0f32, |p, s| p.max(s.abs()));
+            let peak_db = 20.0 * peak.log10();
+            assert!((peak_db - target).abs() < 0.1, "{}", peak_db);
+            // The stereo balance is preserved.
+            assert!(buffer[0]
+                .iter()
+                .zip(buffer[1].iter())
+                .all(|(l, r)| (l * 0.5 - r).abs() < 0.0001));
+        }
+    }
+
+    fn get_buffer(amplitude: f32) -> AudioBuffer {
+        let channel: Vec<f32> = (0..44100)
+            .map(|i| (i as f32 * 0.05).sin() * amplitude)
+            .collect();
+        [channel.clone(), channel]
+    }
+}
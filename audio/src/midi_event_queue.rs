@@ -31,6 +31,11 @@ impl MidiEventQueue {
         self.events.sort()
     }
 
+    /// Remove all events.
+    pub(crate) fn clear(&mut self) {
+        self.events.clear()
+    }
+
     /// Dequeue any events that start at `time`.
     pub(crate) fn dequeue(&mut self, time: u64) -> Vec<MidiEvent> {
         let mut midi_events = vec![];
@@ -1,6 +1,8 @@
 use crate::decayer::Decayer;
 use crate::play_state::PlayState;
-use crate::types::SharedSample;
+use crate::types::{
+    SharedFrozenAudio, SharedGainRamp, SharedMonitorGain, SharedMonoMonitor, SharedSample,
+};
 use crate::{SharedMidiEventQueue, SharedPlayState, SharedSynth};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::*;
@@ -17,19 +19,27 @@ pub(crate) struct Player {
     _stream: Option<Stream>,
     /// The machine's audio framerate.
     pub framerate: u32,
+    /// The name of the output device.
+    pub device_name: String,
 }
 
 impl Player {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
+        output_device: Option<&str>,
         midi_event_queue: SharedMidiEventQueue,
         synth: SharedSynth,
         sample: SharedSample,
         play_state: SharedPlayState,
+        gain_ramp: SharedGainRamp,
+        monitor_gain: SharedMonitorGain,
+        mono_monitor: SharedMonoMonitor,
+        frozen_audio: SharedFrozenAudio,
     ) -> Option<Self> {
         // Get the host.
         let host = default_host();
         // Try to get an output device.
-        match host.default_output_device() {
+        match Player::get_output_device(&host, output_device) {
             None => {
                 println!("{} Failed to get output device", ERROR_MESSAGE);
                 None
@@ -43,6 +53,7 @@ impl Player {
                 // We have a device and a config!
                 Ok(config) => {
                     let framerate = config.sample_rate().0;
+                    let device_name = device.name().unwrap_or_default();
                     let stream_config: StreamConfig = config.into();
                     let channels = stream_config.channels as usize;
 
@@ -55,18 +66,53 @@ impl Player {
                         synth,
                         sample,
                         play_state,
+                        gain_ramp,
+                        monitor_gain,
+                        mono_monitor,
+                        frozen_audio,
                     );
                     Some(Self {
                         _host: host,
                         _stream: stream,
                         framerate,
+                        device_name,
                     })
                 }
             },
         }
     }
 
+    /// Returns the output device named `output_device`.
+    /// If `output_device` is None or there is no device with that name, returns the default output device.
+    fn get_output_device(host: &Host, output_device: Option<&str>) -> Option<Device> {
+        match output_device {
+            Some(name) => {
+                let device = match host.output_devices() {
+                    Ok(mut devices) => {
+                        devices.find(|d| d.name().map(|n| n == name).unwrap_or(false))
+                    }
+                    Err(err) => {
+                        println!("Failed to enumerate output devices: {}", err);
+                        None
+                    }
+                };
+                match device {
+                    Some(device) => Some(device),
+                    None => {
+                        println!(
+                            "Output device {} not found. Using the default output device.",
+                            name
+                        );
+                        host.default_output_device()
+                    }
+                }
+            }
+            None => host.default_output_device(),
+        }
+    }
+
     /// Start running the stream.
+    #[allow(clippy::too_many_arguments)]
     fn run(
         channels: usize,
         device: Device,
@@ -75,6 +121,10 @@ impl Player {
         synth: SharedSynth,
         sample: SharedSample,
         play_state: SharedPlayState,
+        gain_ramp: SharedGainRamp,
+        monitor_gain: SharedMonitorGain,
+        mono_monitor: SharedMonoMonitor,
+        frozen_audio: SharedFrozenAudio,
     ) -> Option<Stream> {
         // Define the error callback.
         let err_callback = |err| println!("Stream error: {}", err);
@@ -93,6 +143,10 @@ impl Player {
                 // Add decay.
                 PlayState::Decaying => {
                     let len = output.len();
+                    // Ramp the gain once per block.
+                    if let Some(gain) = gain_ramp.lock().advance((len / channels) as u32) {
+                        synth.lock().set_gain(gain);
+                    }
                     // Write the decay block.
                     decayer.decay_shared(&synth, len);
                     // Set the decay block.
@@ -129,46 +183,55 @@ impl Player {
                     // Get the next sample.
                     let mut synth = synth.lock();
                     let mut midi_event_queue = midi_event_queue.lock();
+                    let mut gain_ramp = gain_ramp.lock();
+                    let frozen_audio = frozen_audio.lock();
                     // Iterate through the output buffer's frames.
                     let mut begin_decay = false;
                     let buffer_len = len / channels;
                     let mut t = time;
                     for frame in output.chunks_mut(channels) {
-                        match midi_event_queue.get_next_time() {
-                            Some(next_time) => {
-                                // There are events on this frame.
-                                if t == next_time {
-                                    // Dequeue events.
-                                    let events = midi_event_queue.dequeue(t);
-                                    // Send the MIDI events to the synth.
-                                    if !events.is_empty() {
-                                        for event in events {
-                                            if synth.send_event(event).is_ok() {}
-                                        }
-                                    }
-                                }
-                                // Add the sample.
-                                // This is almost certainly more performant than the code in the `else` block.
-                                if two_channels {
-                                    // Get the sample.
-                                    synth.write(frame);
-                                }
-                                // Add for more than one channel. This is slower.
-                                else {
-                                    synth.write(sample_buffer.as_mut_slice());
-                                    for (id, sample) in frame.iter_mut().enumerate() {
-                                        *sample = sample_buffer[id % 2];
-                                    }
+                        let next_time = midi_event_queue.get_next_time();
+                        // There are no more events and no more frozen audio.
+                        if next_time.is_none() && !frozen_audio.is_playing(t) {
+                            begin_decay = true;
+                            break;
+                        }
+                        // There are events on this frame.
+                        if next_time == Some(t) {
+                            // Dequeue events.
+                            let events = midi_event_queue.dequeue(t);
+                            // Send the MIDI events to the synth.
+                            if !events.is_empty() {
+                                for event in events {
+                                    if synth.send_event(event).is_ok() {}
                                 }
-                                // Advance time.
-                                t += 1;
                             }
-                            // There are no more events.
-                            None => {
-                                begin_decay = true;
-                                break;
+                        }
+                        // Ramp the gain.
+                        if let Some(gain) = gain_ramp.advance(1) {
+                            synth.set_gain(gain);
+                        }
+                        // Get the frozen audio sample.
+                        let (frozen_left, frozen_right) = frozen_audio.get_sample(t, synth.gain());
+                        // Add the sample.
+                        // This is almost certainly more performant than the code in the `else` block.
+                        if two_channels {
+                            // Get the sample.
+                            synth.write(&mut *frame);
+                            frame[0] += frozen_left;
+                            frame[1] += frozen_right;
+                        }
+                        // Add for more than one channel. This is slower.
+                        else {
+                            synth.write(sample_buffer.as_mut_slice());
+                            sample_buffer[0] += frozen_left;
+                            sample_buffer[1] += frozen_right;
+                            for (id, sample) in frame.iter_mut().enumerate() {
+                                *sample = sample_buffer[id % 2];
                             }
                         }
+                        // Advance time.
+                        t += 1;
                     }
                     if begin_decay {
                         *play_state.lock() = PlayState::Decaying;
@@ -185,6 +248,18 @@ impl Player {
                     }
                 }
             }
+            // Apply the monitor gain. This only affects what the user hears.
+            let monitor_gain = *monitor_gain.lock();
+            if monitor_gain != 1.0 {
+                output.iter_mut().for_each(|o| *o *= monitor_gain);
+            }
+            // Sum the left and right channels. This only affects what the user hears.
+            if channels >= 2 && *mono_monitor.lock() {
+                for frame in output.chunks_mut(channels) {
+                    let mono = (frame[0] + frame[1]) * 0.5;
+                    frame.iter_mut().for_each(|o| *o = mono);
+                }
+            }
             // Share the first sample.
             let mut sample = sample.lock();
             sample.0 = output[0];
@@ -0,0 +1,132 @@
+/// Rewrite a Standard MIDI File so that every channel message has an explicit status byte.
+///
+/// midly always writes channel messages with running status: If a message has the same status byte as the previous message, the status byte is omitted.
+/// That's valid MIDI, but some older hardware and software misparse it.
+///
+/// Chunks other than track chunks are copied as-is. If the file is malformed, it is returned unchanged.
+pub(crate) fn remove_running_status(smf: &[u8]) -> Vec<u8> {
+    match try_remove_running_status(smf) {
+        Some(bytes) => bytes,
+        None => smf.to_vec(),
+    }
+}
+
+fn try_remove_running_status(smf: &[u8]) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(smf.len());
+    let mut i = 0;
+    while i < smf.len() {
+        let id = smf.get(i..i + 4)?;
+        let len = u32::from_be_bytes(smf.get(i + 4..i + 8)?.try_into().ok()?) as usize;
+        let chunk = smf.get(i + 8..i + 8 + len)?;
+        let chunk = if id == b"MTrk" {
+            expand_track(chunk)?
+        } else {
+            chunk.to_vec()
+        };
+        bytes.extend_from_slice(id);
+        bytes.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+        bytes.extend(chunk);
+        i += 8 + len;
+    }
+    Some(bytes)
+}
+
+/// Returns the events of a track chunk with explicit status bytes.
+fn expand_track(track: &[u8]) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(track.len());
+    let mut running_status: Option<u8> = None;
+    let mut i = 0;
+    while i < track.len() {
+        // Delta time.
+        let (_, delta_len) = read_vlq(track, i)?;
+        bytes.extend_from_slice(&track[i..i + delta_len]);
+        i += delta_len;
+        let byte = *track.get(i)?;
+        let (status, data_start) = if byte & 0x80 == 0 {
+            (running_status?, i)
+        } else {
+            (byte, i + 1)
+        };
+        let event_end = match status {
+            // Meta event: type, length, data.
+            0xFF => {
+                let (len, len_len) = read_vlq(track, data_start + 1)?;
+                running_status = None;
+                data_start + 1 + len_len + len as usize
+            }
+            // SysEx event: length, data.
+            0xF0 | 0xF7 => {
+                let (len, len_len) = read_vlq(track, data_start)?;
+                running_status = None;
+                data_start + len_len + len as usize
+            }
+            // Channel message.
+            0x80..=0xEF => {
+                running_status = Some(status);
+                match status & 0xF0 {
+                    0xC0 | 0xD0 => data_start + 1,
+                    _ => data_start + 2,
+                }
+            }
+            _ => return None,
+        };
+        bytes.push(status);
+        bytes.extend_from_slice(track.get(data_start..event_end)?);
+        i = event_end;
+    }
+    Some(bytes)
+}
+
+/// Read a variable-length quantity. Returns the value and the number of bytes that were read.
+fn read_vlq(bytes: &[u8], start: usize) -> Option<(u32, usize)> {
+    let mut value = 0u32;
+    for i in 0..4 {
+        let byte = *bytes.get(start + i)?;
+        value = (value << 7) | (byte & 0x7F) as u32;
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::remove_running_status;
+
+    #[test]
+    fn running_status() {
+        let header = [b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 1, 0, 1, 0x01, 0xE0];
+        let track = [
+            // Note-on.
+            0x00, 0x90, 60, 100, //
+            // Note-on with running status.
+            0x00, 64, 100, //
+            // Note-off with running status (velocity 0), with a two-byte delta.
+            0x83, 0x60, 60, 0, //
+            // Program change, then another with running status.
+            0x00, 0xC1, 5, 0x00, 6, //
+            // End of track.
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let mut smf = header.to_vec();
+        smf.extend_from_slice(b"MTrk");
+        smf.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        smf.extend_from_slice(&track);
+        let expected_track = [
+            0x00, 0x90, 60, 100, //
+            0x00, 0x90, 64, 100, //
+            0x83, 0x60, 0x90, 60, 0, //
+            0x00, 0xC1, 5, 0x00, 0xC1, 6, //
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let mut expected = header.to_vec();
+        expected.extend_from_slice(b"MTrk");
+        expected.extend_from_slice(&(expected_track.len() as u32).to_be_bytes());
+        expected.extend_from_slice(&expected_track);
+        assert_eq!(remove_running_status(&smf), expected);
+        // Malformed files are unchanged.
+        let malformed = &smf[..smf.len() - 2];
+        assert_eq!(remove_running_status(malformed), malformed);
+    }
+}
@@ -1,9 +1,12 @@
 use crate::export::ExportState;
+use crate::frozen_audio::FrozenAudio;
+use crate::gain_ramp::GainRamp;
 use crate::midi_event_queue::MidiEventQueue;
 use crate::play_state::PlayState;
 use crate::Command;
 use oxisynth::Synth;
 use parking_lot::Mutex;
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
 /// Type alias for an audio messages.
@@ -14,6 +17,19 @@ pub type CommandsMessage = Vec<Command>;
 pub(crate) type AudioBuffer = [Vec<f32>; 2];
 pub(crate) type SharedSynth = Arc<Mutex<Synth>>;
 pub type SharedExportState = Arc<Mutex<ExportState>>;
+/// The index of the file that is being exported, and the total number of files.
+pub(crate) type SharedExportFile = Arc<Mutex<(usize, usize)>>;
+/// If true, the export thread stops as soon as possible.
+pub(crate) type SharedExportCancelled = Arc<AtomicBool>;
+/// The result of the most recent post-export command, if it hasn't been read yet.
+pub(crate) type SharedPostExportResult = Arc<Mutex<Option<bool>>>;
 pub(crate) type SharedMidiEventQueue = Arc<Mutex<MidiEventQueue>>;
 pub type SharedPlayState = Arc<Mutex<PlayState>>;
 pub(crate) type SharedSample = Arc<Mutex<AudioMessage>>;
+pub(crate) type SharedGainRamp = Arc<Mutex<GainRamp>>;
+/// The linear gain that is applied to the live output but not to exported audio.
+pub(crate) type SharedMonitorGain = Arc<Mutex<f32>>;
+/// If true, the live output is summed to mono. This isn't applied to exported audio.
+pub(crate) type SharedMonoMonitor = Arc<Mutex<bool>>;
+/// Audio that is played instead of the notes of frozen tracks.
+pub(crate) type SharedFrozenAudio = Arc<Mutex<FrozenAudio>>;
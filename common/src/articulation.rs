@@ -0,0 +1,109 @@
+use crate::{Note, MAX_VOLUME};
+use serde::{Deserialize, Serialize};
+
+/// Staccato notes play for this fraction of their duration.
+const STACCATO_FACTOR: f32 = 0.5;
+/// Accented notes are this much louder.
+const ACCENT_VELOCITY: u8 = 20;
+
+/// An articulation mark changes how a note is played without changing the note's stored data.
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, Deserialize, Serialize)]
+pub enum Articulation {
+    /// Play the note for a shorter duration.
+    Staccato,
+    /// Play the note louder.
+    Accent,
+    /// Hold the note until the next note starts.
+    Tenuto,
+}
+
+impl Articulation {
+    /// Returns the next articulation in the cycle: None, Staccato, Accent, Tenuto, None...
+    pub fn cycle(articulation: &Option<Articulation>) -> Option<Articulation> {
+        match articulation {
+            None => Some(Articulation::Staccato),
+            Some(Articulation::Staccato) => Some(Articulation::Accent),
+            Some(Articulation::Accent) => Some(Articulation::Tenuto),
+            Some(Articulation::Tenuto) => None,
+        }
+    }
+
+    /// Returns a copy of `note` with an adjusted velocity and end time.
+    ///
+    /// - `note` The note.
+    /// - `notes` All of the notes in the track. This is used to find the next note for tenuto.
+    pub fn apply(&self, note: &Note, notes: &[Note]) -> Note {
+        let mut n = *note;
+        match self {
+            Articulation::Staccato => {
+                let duration = ((note.get_duration() as f32 * STACCATO_FACTOR) as u64).max(1);
+                n.end = note.start + duration;
+            }
+            Articulation::Accent => {
+                n.velocity = note
+                    .velocity
+                    .saturating_add(ACCENT_VELOCITY)
+                    .min(MAX_VOLUME);
+            }
+            Articulation::Tenuto => {
+                if let Some(next) = notes
+                    .iter()
+                    .filter(|other| other.start > note.start)
+                    .map(|other| other.start)
+                    .min()
+                {
+                    if next > n.end {
+                        n.end = next;
+                    }
+                }
+            }
+        }
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Articulation, Note, MAX_VOLUME, PPQ_U};
+
+    #[test]
+    fn articulation_cycle() {
+        let mut a = None;
+        a = Articulation::cycle(&a);
+        assert_eq!(a, Some(Articulation::Staccato));
+        a = Articulation::cycle(&a);
+        assert_eq!(a, Some(Articulation::Accent));
+        a = Articulation::cycle(&a);
+        assert_eq!(a, Some(Articulation::Tenuto));
+        a = Articulation::cycle(&a);
+        assert_eq!(a, None);
+    }
+
+    #[test]
+    fn articulation_apply() {
+        let n0 = get_note(0, PPQ_U);
+        let n1 = get_note(PPQ_U * 2, PPQ_U * 3);
+        let notes = [n0, n1];
+        let staccato = Articulation::Staccato.apply(&n0, &notes);
+        assert_eq!(staccato.end, PPQ_U / 2, "{:?}", staccato);
+        let accent = Articulation::Accent.apply(&n0, &notes);
+        assert_eq!(accent.velocity, MAX_VOLUME, "{:?}", accent);
+        assert_eq!(accent.end, n0.end, "{:?}", accent);
+        let tenuto = Articulation::Tenuto.apply(&n0, &notes);
+        assert_eq!(tenuto.end, n1.start, "{:?}", tenuto);
+        // There is no next note.
+        let tenuto = Articulation::Tenuto.apply(&n1, &notes);
+        assert_eq!(tenuto.end, n1.end, "{:?}", tenuto);
+    }
+
+    fn get_note(start: u64, end: u64) -> Note {
+        Note {
+            note: 60,
+            velocity: 120,
+            start,
+            end,
+            articulation: None,
+            tied: false,
+        }
+    }
+}
@@ -1,5 +1,10 @@
 use crate::{Index, U64orF32, MAX_VOLUME, PPQ_U};
+use ini::Ini;
 use serde::{Deserialize, Serialize};
+use serde_json::from_str;
+
+/// The number of MIDI channels.
+const NUM_MIDI_CHANNELS: usize = 16;
 
 /// Booleans and numerical values describing the input state.
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -14,6 +19,58 @@ pub struct InputState {
     pub use_volume: bool,
     /// The input beat in PPQ.
     pub beat: U64orF32,
+    /// If an element is true, MIDI note-on and note-off messages on that channel will be accepted.
+    #[serde(default = "default_midi_channels")]
+    pub midi_channels: [bool; NUM_MIDI_CHANNELS],
+}
+
+impl InputState {
+    pub fn new(config: &Ini) -> Self {
+        let section = config.section(Some("MIDI_INPUT")).unwrap();
+        let channels = match section.get("channels") {
+            Some(value) => match from_str::<Vec<u8>>(value) {
+                Ok(channels) => channels,
+                Err(error) => panic!("Error parsing MIDI input channels {}: {}", value, error),
+            },
+            None => panic!("Missing key channels"),
+        };
+        // An empty list means that all channels are accepted.
+        let midi_channels = if channels.is_empty() {
+            default_midi_channels()
+        } else {
+            let mut midi_channels = [false; NUM_MIDI_CHANNELS];
+            for channel in channels {
+                match midi_channels.get_mut(channel as usize) {
+                    Some(c) => *c = true,
+                    None => panic!("Invalid MIDI input channel: {}", channel),
+                }
+            }
+            midi_channels
+        };
+        Self {
+            midi_channels,
+            ..Default::default()
+        }
+    }
+
+    /// Returns true if we accept a MIDI message with this status byte.
+    pub fn accepts_midi_channel(&self, status: u8) -> bool {
+        self.midi_channels[(status & 0x0F) as usize]
+    }
+
+    /// Returns true if we accept MIDI messages on a track's channel.
+    /// Returns None if the channel isn't a MIDI input channel, which is the case for track channels 16 and higher.
+    pub fn get_midi_channel(&self, channel: u8) -> Option<bool> {
+        self.midi_channels.get(channel as usize).copied()
+    }
+
+    /// Toggle whether we accept MIDI messages on a track's channel. Returns the new value.
+    /// Returns None and does nothing if the channel isn't a MIDI input channel.
+    pub fn toggle_midi_channel(&mut self, channel: u8) -> Option<bool> {
+        let accept = self.midi_channels.get_mut(channel as usize)?;
+        *accept = !*accept;
+        Some(*accept)
+    }
 }
 
 impl Default for InputState {
@@ -24,6 +81,33 @@ impl Default for InputState {
             volume: Index::new(MAX_VOLUME, MAX_VOLUME + 1),
             use_volume: true,
             beat: U64orF32::from(PPQ_U),
+            midi_channels: default_midi_channels(),
         }
     }
 }
+
+fn default_midi_channels() -> [bool; NUM_MIDI_CHANNELS] {
+    [true; NUM_MIDI_CHANNELS]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::InputState;
+
+    #[test]
+    fn midi_channels() {
+        let mut input = InputState::default();
+        assert!(input.accepts_midi_channel(0x90));
+        assert!(input.accepts_midi_channel(0x8F));
+        input.midi_channels[15] = false;
+        assert!(input.accepts_midi_channel(0x90));
+        assert!(!input.accepts_midi_channel(0x9F));
+        assert!(!input.accepts_midi_channel(0x8F));
+        // Track channels that aren't MIDI input channels.
+        assert_eq!(input.get_midi_channel(15), Some(false));
+        assert_eq!(input.toggle_midi_channel(15), Some(true));
+        assert_eq!(input.get_midi_channel(16), None);
+        assert_eq!(input.toggle_midi_channel(16), None);
+        assert_eq!(input.toggle_midi_channel(255), None);
+    }
+}
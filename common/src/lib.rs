@@ -13,28 +13,38 @@
 //! `common` is designed such that any Cacophony crate can use it, but itself does not depend on any Cacophony crates.
 
 pub mod args;
+mod articulation;
 pub mod config;
 mod index;
 mod input_state;
 mod midi_track;
 mod music;
 mod note;
+mod note_range;
 mod panel_type;
 pub mod paths;
 mod paths_state;
+mod project_issue;
+mod sample_rounding;
 mod state;
+mod ties;
 pub mod time;
 pub mod view;
+pub use articulation::Articulation;
 pub use index::Index;
 mod indexed_values;
 pub use indexed_values::IndexedValues;
 pub use input_state::InputState;
-pub use midi_track::MidiTrack;
+pub use midi_track::{MidiTrack, NUM_TRACK_COLORS};
 pub use music::*;
 pub use note::{Note, MAX_NOTE, MIN_NOTE, NOTE_NAMES};
+pub use note_range::{NoteRange, NoteRangeMode};
 pub use panel_type::PanelType;
 pub use paths::Paths;
+pub use project_issue::ProjectIssue;
+pub use sample_rounding::SampleRounding;
 pub use state::State;
+pub use ties::Ties;
 use view::View;
 mod edit_mode;
 pub mod music_panel_field;
@@ -60,6 +70,8 @@ pub mod fraction;
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 /// The maximum volume.
 pub const MAX_VOLUME: u8 = 127;
+/// The default volume (CC7) of a MIDI channel.
+pub const DEFAULT_CHANNEL_VOLUME: u8 = 100;
 
 /// Read bytes from a file.
 pub fn get_bytes(path: &Path) -> Vec<u8> {
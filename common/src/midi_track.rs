@@ -1,5 +1,10 @@
-use crate::{Note, MAX_VOLUME};
+use crate::{Note, NoteRange, MAX_VOLUME};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// The number of colors in the renderer's track color palette.
+pub const NUM_TRACK_COLORS: usize = 6;
 
 /// A MIDI track has some notes.
 #[derive(Debug, Deserialize, Serialize)]
@@ -14,6 +19,26 @@ pub struct MidiTrack {
     pub mute: bool,
     /// True if the track is soloed.
     pub solo: bool,
+    /// True if recorded notes can be added to this track even if it isn't selected.
+    #[serde(default)]
+    pub record_armed: bool,
+    /// True if the track is selected for export. If any tracks are selected for export, only those tracks are exported.
+    #[serde(default)]
+    pub export_selected: bool,
+    /// The index of the track's color in the track color palette. If None, the color is derived from the track's position.
+    #[serde(default)]
+    pub color: Option<usize>,
+    /// The path to audio that was rendered from this track, if the track is frozen.
+    /// During live playback, the audio is played instead of the notes. Exports always use the notes.
+    #[serde(default)]
+    pub frozen_audio: Option<PathBuf>,
+    /// The volume envelope: Breakpoints sorted by time. Each breakpoint is a time in PPQ and a channel volume (CC7) value.
+    /// The volume is interpolated between breakpoints. If this is empty, the channel volume doesn't change.
+    #[serde(default)]
+    pub volume_envelope: Vec<(u64, u8)>,
+    /// The range of notes that the track's preset can play. Out-of-range notes can be remapped into the range during playback and audio export.
+    #[serde(default)]
+    pub note_range: NoteRange,
 }
 
 impl MidiTrack {
@@ -24,30 +49,175 @@ impl MidiTrack {
             notes: vec![],
             mute: false,
             solo: false,
+            record_armed: false,
+            export_selected: false,
+            color: None,
+            frozen_audio: None,
+            volume_envelope: vec![],
+            note_range: NoteRange::default(),
         }
     }
 
+    /// Add a note to the track. Returns the track so that calls can be chained.
+    ///
+    /// - `note` The MIDI note value.
+    /// - `velocity` The velocity value.
+    /// - `start` The start time in PPQ.
+    /// - `end` The end time in PPQ.
+    pub fn add_note(&mut self, note: u8, velocity: u8, start: u64, end: u64) -> &mut Self {
+        self.notes.push(Note {
+            note,
+            velocity,
+            start,
+            end,
+            articulation: None,
+            tied: false,
+        });
+        self
+    }
+
+    /// Returns the index of the track's color in the track color palette.
+    ///
+    /// - `index` The index of the track in the music.
+    pub fn get_color_index(&self, index: usize) -> usize {
+        self.color.unwrap_or(index) % NUM_TRACK_COLORS
+    }
+
+    /// Set the track's color to the next color in the track color palette.
+    ///
+    /// - `index` The index of the track in the music.
+    pub fn cycle_color(&mut self, index: usize) {
+        self.color = Some((self.get_color_index(index) + 1) % NUM_TRACK_COLORS);
+    }
+
     /// Returns the end time of the track in PPQ.
     pub fn get_end(&self) -> Option<u64> {
         self.notes.iter().map(|n| n.end).max()
     }
 
+    /// Returns the number of notes in the track.
+    pub fn note_count(&self) -> usize {
+        self.notes.len()
+    }
+
+    /// Returns the lowest and highest note pitches in the track, or None if the track is empty.
+    pub fn pitch_range(&self) -> Option<(u8, u8)> {
+        let min = self.notes.iter().map(|n| n.note).min()?;
+        let max = self.notes.iter().map(|n| n.note).max()?;
+        Some((min, max))
+    }
+
+    /// Returns the index of the note with the highest velocity, or the lowest velocity if `loudest` is false.
+    /// If more than one note has that velocity, this returns the earliest of them. Returns None if the track is empty.
+    pub fn get_velocity_extreme(&self, loudest: bool) -> Option<usize> {
+        self.notes
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, n)| {
+                let velocity = if loudest {
+                    u8::MAX - n.velocity
+                } else {
+                    n.velocity
+                };
+                (velocity, n.start)
+            })
+            .map(|(i, _)| i)
+    }
+
+    /// Returns the value of the volume envelope at `ppq`, or None if the track doesn't have a volume envelope.
+    /// Before the first breakpoint, this is the first breakpoint's value. After the last breakpoint, this is the last breakpoint's value.
+    pub fn get_volume_at(&self, ppq: u64) -> Option<u8> {
+        let first = self.volume_envelope.first()?;
+        if ppq <= first.0 {
+            return Some(first.1);
+        }
+        for points in self.volume_envelope.windows(2) {
+            let (t0, v0) = points[0];
+            let (t1, v1) = points[1];
+            if ppq < t1 {
+                let f = (ppq - t0) as f32 / (t1 - t0) as f32;
+                return Some((v0 as f32 + (v1 as f32 - v0 as f32) * f).round() as u8);
+            }
+        }
+        self.volume_envelope.last().map(|p| p.1)
+    }
+
+    /// Returns the volume envelope as channel volume events starting at `t0`. Each event is a time in PPQ and a value.
+    /// The first event sets the volume at `t0`. Between breakpoints, there is an event each time the interpolated value changes.
+    pub fn get_volume_events(&self, t0: u64) -> Vec<(u64, u8)> {
+        let mut events = match self.get_volume_at(t0) {
+            Some(value) => vec![(t0, value)],
+            None => return vec![],
+        };
+        for points in self.volume_envelope.windows(2) {
+            let (p0, v0) = points[0];
+            let (p1, v1) = points[1];
+            if p1 <= t0 || v0 == v1 {
+                continue;
+            }
+            let range: Vec<u8> = if v1 > v0 {
+                (v0 + 1..=v1).collect()
+            } else {
+                (v1..v0).rev().collect()
+            };
+            for value in range {
+                let f = (value as f32 - v0 as f32) / (v1 as f32 - v0 as f32);
+                let t = p0 + ((p1 - p0) as f32 * f).round() as u64;
+                if t <= t0 {
+                    continue;
+                }
+                // If the volume changes more than once at the same time, only use the last value.
+                match events.last_mut() {
+                    Some(event) if event.0 == t => event.1 = value,
+                    _ => events.push((t, value)),
+                }
+            }
+        }
+        events
+    }
+
+    /// Add a volume envelope breakpoint, or set the value of the breakpoint at `ppq`.
+    pub fn set_volume_point(&mut self, ppq: u64, value: u8) {
+        match self.volume_envelope.iter_mut().find(|p| p.0 == ppq) {
+            Some(point) => point.1 = value,
+            None => {
+                self.volume_envelope.push((ppq, value));
+                self.volume_envelope.sort_by_key(|p| p.0);
+            }
+        }
+    }
+
+    /// Remove the volume envelope breakpoint at `ppq`. Returns true if there was a breakpoint.
+    pub fn remove_volume_point(&mut self, ppq: u64) -> bool {
+        let len = self.volume_envelope.len();
+        self.volume_envelope.retain(|p| p.0 != ppq);
+        self.volume_envelope.len() < len
+    }
+
     /// Returns the track gain as a float between 0 and 1.
     pub fn get_gain_f(&self) -> f32 {
         self.gain as f32 / MAX_VOLUME as f32
     }
 
-    /// Returns all notes in the track that can be played (they are after t0).
-    pub fn get_playback_notes(&self, start: u64) -> Vec<Note> {
-        let gain = self.get_gain_f();
-        let mut notes = vec![];
-        for note in self.notes.iter().filter(|n| n.start >= start) {
-            let mut n1 = *note;
-            n1.velocity = (n1.velocity as f32 * gain) as u8;
-            notes.push(n1);
+    /// Split the notes into voices. Notes that start at the same time are a chord.
+    /// The first voice has the highest note of each chord, the second voice has the second-highest note, and so on.
+    /// Returns a list of notes per voice. The number of voices is the number of notes in the widest chord.
+    pub fn explode_chords(&self) -> Vec<Vec<Note>> {
+        let mut chords: BTreeMap<u64, Vec<Note>> = BTreeMap::new();
+        for note in self.notes.iter() {
+            chords.entry(note.start).or_default().push(*note);
         }
-        notes.sort();
-        notes
+        let mut voices: Vec<Vec<Note>> = vec![];
+        for chord in chords.values_mut() {
+            chord.sort_by(|a, b| b.note.cmp(&a.note));
+            for (i, note) in chord.iter().enumerate() {
+                if i >= voices.len() {
+                    voices.push(vec![]);
+                }
+                voices[i].push(*note);
+            }
+        }
+        voices
     }
 }
 
@@ -59,6 +229,117 @@ impl Clone for MidiTrack {
             notes: self.notes.clone(),
             mute: self.mute,
             solo: self.solo,
+            record_armed: self.record_armed,
+            export_selected: self.export_selected,
+            color: self.color,
+            frozen_audio: self.frozen_audio.clone(),
+            volume_envelope: self.volume_envelope.clone(),
+            note_range: self.note_range,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{MidiTrack, Note, NUM_TRACK_COLORS, PPQ_U};
+
+    #[test]
+    fn track_stats() {
+        let mut track = MidiTrack::new(0);
+        assert_eq!(track.note_count(), 0);
+        assert_eq!(track.pitch_range(), None);
+        for (note, start) in [(64, 0), (60, PPQ_U), (72, PPQ_U * 2)] {
+            track.notes.push(Note {
+                note,
+                velocity: 120,
+                start,
+                end: start + PPQ_U,
+                articulation: None,
+                tied: false,
+            });
+        }
+        assert_eq!(track.note_count(), 3);
+        assert_eq!(track.pitch_range(), Some((60, 72)));
+    }
+
+    #[test]
+    fn track_velocity_extreme() {
+        let mut track = MidiTrack::new(0);
+        assert_eq!(track.get_velocity_extreme(true), None);
+        track
+            .add_note(60, 80, PPQ_U * 2, PPQ_U * 3)
+            .add_note(62, 20, PPQ_U, PPQ_U * 2)
+            .add_note(64, 120, PPQ_U * 3, PPQ_U * 4)
+            .add_note(65, 120, PPQ_U * 2, PPQ_U * 3)
+            .add_note(67, 20, 0, PPQ_U);
+        // Ties go to the earliest note.
+        assert_eq!(track.get_velocity_extreme(true), Some(3));
+        assert_eq!(track.get_velocity_extreme(false), Some(4));
+    }
+
+    #[test]
+    fn track_volume_envelope() {
+        let mut track = MidiTrack::new(0);
+        assert_eq!(track.get_volume_at(0), None);
+        assert!(track.get_volume_events(0).is_empty());
+        track.set_volume_point(PPQ_U * 2, 80);
+        track.set_volume_point(PPQ_U, 100);
+        track.set_volume_point(PPQ_U * 2, 96);
+        assert_eq!(track.volume_envelope, vec![(PPQ_U, 100), (PPQ_U * 2, 96)]);
+        assert_eq!(track.get_volume_at(0), Some(100));
+        assert_eq!(track.get_volume_at(PPQ_U + PPQ_U / 2), Some(98));
+        assert_eq!(track.get_volume_at(PPQ_U * 3), Some(96));
+        assert_eq!(
+            track.get_volume_events(0),
+            vec![
+                (0, 100),
+                (PPQ_U + 48, 99),
+                (PPQ_U + 96, 98),
+                (PPQ_U + 144, 97),
+                (PPQ_U * 2, 96)
+            ]
+        );
+        // Start in the middle of the envelope.
+        assert_eq!(
+            track.get_volume_events(PPQ_U + 100),
+            vec![(PPQ_U + 100, 98), (PPQ_U + 144, 97), (PPQ_U * 2, 96)]
+        );
+        assert!(!track.remove_volume_point(0));
+        assert!(track.remove_volume_point(PPQ_U));
+        assert_eq!(track.get_volume_at(0), Some(96));
+    }
+
+    #[test]
+    fn track_explode_chords() {
+        let mut track = MidiTrack::new(0);
+        assert!(track.explode_chords().is_empty());
+        track
+            .add_note(60, 120, 0, PPQ_U)
+            .add_note(67, 120, 0, PPQ_U)
+            .add_note(64, 120, 0, PPQ_U)
+            .add_note(62, 120, PPQ_U, PPQ_U * 2)
+            .add_note(65, 120, PPQ_U * 2, PPQ_U * 3)
+            .add_note(59, 120, PPQ_U * 2, PPQ_U * 3);
+        let voices = track.explode_chords();
+        assert_eq!(voices.len(), 3);
+        let pitches: Vec<Vec<u8>> = voices
+            .iter()
+            .map(|v| v.iter().map(|n| n.note).collect())
+            .collect();
+        assert_eq!(pitches, vec![vec![67, 62, 65], vec![64, 59], vec![60]]);
+        assert_eq!(voices[1][1].start, PPQ_U * 2);
+    }
+
+    #[test]
+    fn track_color() {
+        let mut track = MidiTrack::new(0);
+        assert_eq!(track.get_color_index(1), 1);
+        assert_eq!(track.get_color_index(NUM_TRACK_COLORS + 1), 1);
+        track.cycle_color(1);
+        assert_eq!(track.color, Some(2));
+        assert_eq!(track.get_color_index(0), 2);
+        track.color = Some(NUM_TRACK_COLORS - 1);
+        track.cycle_color(0);
+        assert_eq!(track.get_color_index(0), 0);
+    }
+}
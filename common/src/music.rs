@@ -1,4 +1,5 @@
 use super::midi_track::MidiTrack;
+use crate::{Note, ProjectIssue};
 use serde::{Deserialize, Serialize};
 
 /// Tracks, notes, and metadata.
@@ -27,6 +28,32 @@ impl Music {
         }
     }
 
+    /// Add a new track and return a mutable reference to it.
+    ///
+    /// - `channel` The track's channel.
+    ///
+    /// ```
+    /// use common::{Music, PPQ_U};
+    ///
+    /// let mut music = Music::default();
+    /// music
+    ///     .add_track(0)
+    ///     .add_note(60, 127, 0, PPQ_U)
+    ///     .add_note(64, 127, PPQ_U, PPQ_U * 2);
+    /// assert_eq!(music.midi_tracks.len(), 1);
+    /// assert_eq!(music.midi_tracks[0].notes.len(), 2);
+    /// assert_eq!(music.midi_tracks[0].get_end(), Some(PPQ_U * 2));
+    /// ```
+    pub fn add_track(&mut self, channel: u8) -> &mut MidiTrack {
+        self.midi_tracks.push(MidiTrack::new(channel));
+        self.midi_tracks.last_mut().unwrap()
+    }
+
+    /// Returns true if there aren't any tracks, or if none of the tracks have notes. Muted tracks still count.
+    pub fn is_empty(&self) -> bool {
+        self.midi_tracks.iter().all(|t| t.notes.is_empty())
+    }
+
     /// Returns all tracks that can be played.
     pub fn get_playable_tracks(&self) -> Vec<&MidiTrack> {
         // Get all tracks that can play music.
@@ -38,4 +65,169 @@ impl Music {
         };
         tracks
     }
+
+    /// Returns all tracks that can be exported.
+    /// If any tracks are selected for export, only those tracks are returned, regardless of whether they are muted.
+    /// Otherwise, this returns the playable tracks.
+    pub fn get_export_tracks(&self) -> Vec<&MidiTrack> {
+        let selected: Vec<&MidiTrack> = self
+            .midi_tracks
+            .iter()
+            .filter(|t| t.export_selected)
+            .collect();
+        if selected.is_empty() {
+            self.get_playable_tracks()
+        } else {
+            selected
+        }
+    }
+
+    /// Returns an iterator over the notes of all playable tracks, sorted by start time.
+    /// Notes that start at the same time are in track order.
+    pub fn iter_notes_sorted(&self) -> impl Iterator<Item = (&MidiTrack, &Note)> {
+        let mut notes: Vec<(&MidiTrack, &Note)> = self
+            .get_playable_tracks()
+            .into_iter()
+            .flat_map(|track| track.notes.iter().map(move |note| (track, note)))
+            .collect();
+        notes.sort_by(|a, b| a.1.start.cmp(&b.1.start));
+        notes.into_iter()
+    }
+
+    /// Returns a list of problems that would make an export empty or incorrect.
+    ///
+    /// - `has_program` Returns true if a channel has a program.
+    pub fn validate<F>(&self, has_program: F) -> Vec<ProjectIssue>
+    where
+        F: Fn(u8) -> bool,
+    {
+        let tracks = self.get_export_tracks();
+        if tracks.is_empty() {
+            return vec![ProjectIssue::NoPlayableTracks];
+        }
+        let mut issues = vec![];
+        for track in tracks {
+            if !track.notes.is_empty() && !has_program(track.channel) {
+                issues.push(ProjectIssue::NoProgram {
+                    channel: track.channel,
+                });
+            }
+            for (index, _) in track
+                .notes
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| n.end <= n.start)
+            {
+                issues.push(ProjectIssue::InvalidNote {
+                    channel: track.channel,
+                    index,
+                });
+            }
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MidiTrack, Music, Note, ProjectIssue, PPQ_U};
+
+    #[test]
+    fn music_iter_notes_sorted() {
+        let mut music = Music::default();
+        let mut track_0 = MidiTrack::new(0);
+        track_0.notes = vec![get_note(60, PPQ_U * 2), get_note(62, 0)];
+        let mut track_1 = MidiTrack::new(1);
+        track_1.notes = vec![get_note(64, PPQ_U), get_note(65, 0)];
+        let mut track_2 = MidiTrack::new(2);
+        track_2.notes = vec![get_note(67, 0)];
+        track_2.mute = true;
+        music.midi_tracks = vec![track_0, track_1, track_2];
+        let notes: Vec<(u8, u8)> = music
+            .iter_notes_sorted()
+            .map(|(track, note)| (track.channel, note.note))
+            .collect();
+        assert_eq!(notes, vec![(0, 62), (1, 65), (1, 64), (0, 60)]);
+        // Only the soloed track is included.
+        music.midi_tracks[1].solo = true;
+        let notes: Vec<u8> = music.iter_notes_sorted().map(|(_, n)| n.note).collect();
+        assert_eq!(notes, vec![65, 64]);
+    }
+
+    #[test]
+    fn music_is_empty() {
+        let mut music = Music::default();
+        assert!(music.is_empty());
+        music.add_track(0);
+        assert!(music.is_empty());
+        music.add_track(1).add_note(60, 127, 0, PPQ_U);
+        assert!(!music.is_empty());
+        // Muted notes are still notes.
+        music.midi_tracks[1].mute = true;
+        assert!(!music.is_empty());
+    }
+
+    #[test]
+    fn music_get_export_tracks() {
+        let mut music = Music::default();
+        music.midi_tracks = vec![MidiTrack::new(0), MidiTrack::new(1), MidiTrack::new(2)];
+        music.midi_tracks[1].mute = true;
+        let channels = |music: &Music| -> Vec<u8> {
+            music
+                .get_export_tracks()
+                .iter()
+                .map(|t| t.channel)
+                .collect()
+        };
+        assert_eq!(channels(&music), vec![0, 2]);
+        // Only selected tracks are exported, even if they're muted.
+        music.midi_tracks[1].export_selected = true;
+        music.midi_tracks[2].export_selected = true;
+        assert_eq!(channels(&music), vec![1, 2]);
+    }
+
+    #[test]
+    fn music_validate() {
+        let mut music = Music::default();
+        assert_eq!(
+            music.validate(|_| true),
+            vec![ProjectIssue::NoPlayableTracks]
+        );
+        let mut track_0 = MidiTrack::new(0);
+        track_0.notes = vec![get_note(60, 0)];
+        let mut track_1 = MidiTrack::new(1);
+        track_1.notes = vec![get_note(62, 0), get_note(64, PPQ_U)];
+        track_1.notes[1].end = PPQ_U;
+        music.midi_tracks = vec![track_0, track_1];
+        assert_eq!(music.validate(|_| true).len(), 1);
+        assert_eq!(
+            music.validate(|c| c == 1),
+            vec![
+                ProjectIssue::NoProgram { channel: 0 },
+                ProjectIssue::InvalidNote {
+                    channel: 1,
+                    index: 1
+                }
+            ]
+        );
+        // Muted tracks are ignored.
+        music.midi_tracks[1].mute = true;
+        assert!(music.validate(|_| true).is_empty());
+        music.midi_tracks[0].mute = true;
+        assert_eq!(
+            music.validate(|_| true),
+            vec![ProjectIssue::NoPlayableTracks]
+        );
+    }
+
+    fn get_note(note: u8, start: u64) -> Note {
+        Note {
+            note,
+            velocity: 120,
+            start,
+            end: start + PPQ_U,
+            articulation: None,
+            tied: false,
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use crate::{Articulation, Ties};
 use serde::ser::SerializeSeq;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
@@ -34,6 +35,13 @@ pub struct Note {
     pub start: u64,
     /// The end time in PPQ.
     pub end: u64,
+    /// An optional articulation mark. This changes how the note is played, not the note's data.
+    #[serde(default)]
+    pub articulation: Option<Articulation>,
+    /// If true, this note is tied to the next note of the same pitch that starts when this note ends.
+    /// Tied notes are played as one sustained note, but they are still separate notes.
+    #[serde(default)]
+    pub tied: bool,
 }
 
 impl Note {
@@ -57,6 +65,40 @@ impl Note {
     pub fn get_name(&self) -> &str {
         NOTE_NAMES[127 - self.note as usize]
     }
+
+    /// Returns a copy of this note with its articulation (if any) applied.
+    ///
+    /// - `notes` All of the notes in the track.
+    pub fn get_articulated(&self, notes: &[Note]) -> Note {
+        match &self.articulation {
+            Some(articulation) => articulation.apply(self, notes),
+            None => *self,
+        }
+    }
+
+    /// Returns a copy of this note that lasts until the end of the last note that it is tied to.
+    /// Returns None if this note continues a tie, in which case it is played by the note that it is tied from.
+    ///
+    /// - `ties` All of the notes in the track, indexed for looking up ties.
+    /// - `t0` Playback starts at this time. If a note is tied from a note that starts before `t0`, it is played anyway.
+    pub fn get_tied(&self, ties: &Ties, t0: u64) -> Option<Note> {
+        if ties.is_tied_from(self, t0) {
+            return None;
+        }
+        let mut tied = *self;
+        let mut note = *self;
+        // Each tied note starts when the previous note ends, and zero-length notes can't be tied, so this always ends.
+        while let Some(next) = ties.get_next(&note) {
+            tied.end = next.end;
+            note = *next;
+        }
+        Some(tied)
+    }
+
+    /// Returns true if this note is tied to `other`. A zero-length note isn't tied to anything, because it would be tied to itself.
+    pub(crate) fn is_tied_to(&self, other: &Note) -> bool {
+        self.tied && self.start < self.end && self.note == other.note && self.end == other.start
+    }
 }
 
 impl Ord for Note {
@@ -86,11 +128,25 @@ impl Serialize for Note {
     where
         S: serde::Serializer,
     {
-        let mut seq = serializer.serialize_seq(Some(4)).unwrap();
+        // Only serialize the articulation and the tie if there are any, so that older versions can read the save file.
+        let len = if self.tied {
+            6
+        } else if self.articulation.is_some() {
+            5
+        } else {
+            4
+        };
+        let mut seq = serializer.serialize_seq(Some(len)).unwrap();
         seq.serialize_element(&self.note).unwrap();
         seq.serialize_element(&self.velocity).unwrap();
         seq.serialize_element(&self.start).unwrap();
         seq.serialize_element(&self.end).unwrap();
+        if self.tied {
+            seq.serialize_element(&self.articulation).unwrap();
+            seq.serialize_element(&self.tied).unwrap();
+        } else if let Some(articulation) = &self.articulation {
+            seq.serialize_element(articulation).unwrap();
+        }
         seq.end()
     }
 }
@@ -98,7 +154,7 @@ impl Serialize for Note {
 #[cfg(test)]
 mod tests {
     use crate::note::MIDDLE_C;
-    use crate::{Note, MAX_VOLUME, PPQ_U};
+    use crate::{Articulation, Note, Ties, MAX_VOLUME, PPQ_U};
     use serde_json::{from_str, to_string};
 
     #[test]
@@ -121,6 +177,75 @@ mod tests {
         assert_eq!(note.velocity, MAX_VOLUME, "{:?}", note);
         assert_eq!(note.start, 0, "{:?}", note);
         assert_eq!(note.end, PPQ_U, "{:?}", note);
+        assert!(note.articulation.is_none(), "{:?}", note);
+    }
+
+    #[test]
+    fn note_articulation_serialization() {
+        let mut note = get_note();
+        note.articulation = Some(Articulation::Staccato);
+        let s = to_string(&note).unwrap();
+        assert_eq!(&s, "[60,127,0,192,\"Staccato\"]", "{}", s);
+        let note: Note = from_str(&s).unwrap();
+        assert_eq!(
+            note.articulation,
+            Some(Articulation::Staccato),
+            "{:?}",
+            note
+        );
+    }
+
+    #[test]
+    fn note_tie() {
+        let mut note = get_note();
+        note.tied = true;
+        let s = to_string(&note).unwrap();
+        assert_eq!(&s, "[60,127,0,192,null,true]", "{}", s);
+        let note: Note = from_str(&s).unwrap();
+        assert!(note.tied, "{:?}", note);
+        assert!(note.articulation.is_none(), "{:?}", note);
+        // Three tied notes and an untied note.
+        let mut notes = vec![note];
+        for i in 1..4 {
+            let mut n = get_note();
+            n.set_t0_by(PPQ_U * i, true);
+            n.tied = i < 3;
+            notes.push(n);
+        }
+        let ties = Ties::new(&notes);
+        let tied = notes[0].get_tied(&ties, 0).unwrap();
+        assert_eq!(tied.start, 0);
+        assert_eq!(tied.end, PPQ_U * 4);
+        for note in notes[1..].iter() {
+            assert!(note.get_tied(&ties, 0).is_none());
+        }
+        // Playback starts partway through the tie.
+        let tied = notes[2].get_tied(&ties, PPQ_U * 2).unwrap();
+        assert_eq!(tied.start, PPQ_U * 2);
+        assert_eq!(tied.end, PPQ_U * 4);
+        assert!(notes[3].get_tied(&ties, PPQ_U * 2).is_none());
+        // A note isn't tied to a note with a different pitch.
+        notes[1].note += 1;
+        let ties = Ties::new(&notes);
+        assert_eq!(notes[0].get_tied(&ties, 0).unwrap().end, PPQ_U);
+        assert_eq!(notes[1].get_tied(&ties, 0).unwrap().end, PPQ_U * 2);
+        // A zero-length note isn't tied to itself.
+        let mut zero = get_note();
+        zero.end = zero.start;
+        zero.tied = true;
+        let notes = vec![zero, get_note()];
+        let ties = Ties::new(&notes);
+        assert_eq!(notes[0].get_tied(&ties, 0), Some(zero));
+        // A note can be tied to a zero-length note, but the tie ends there.
+        let mut note = get_note();
+        note.tied = true;
+        let mut zero = zero;
+        zero.start = note.end;
+        zero.end = note.end;
+        let notes = vec![note, zero];
+        let ties = Ties::new(&notes);
+        assert_eq!(notes[0].get_tied(&ties, 0).unwrap().end, PPQ_U);
+        assert!(notes[1].get_tied(&ties, 0).is_none());
     }
 
     fn get_note() -> Note {
@@ -129,6 +254,8 @@ mod tests {
             velocity: MAX_VOLUME,
             start: 0,
             end: PPQ_U,
+            articulation: None,
+            tied: false,
         }
     }
 }
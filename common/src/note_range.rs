@@ -0,0 +1,136 @@
+use crate::{MAX_NOTE, MIN_NOTE};
+use serde::{Deserialize, Serialize};
+
+/// The number of half-steps in an octave.
+const OCTAVE: u8 = 12;
+
+/// How a track plays notes that are outside of its note range.
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, Deserialize, Serialize)]
+pub enum NoteRangeMode {
+    /// Play the nearest note in the range.
+    Clamp,
+    /// Shift the note by octaves until it is in the range.
+    Fold,
+}
+
+/// The range of notes that a track's preset can play, and how out-of-range notes are remapped.
+/// This is applied when notes are scheduled. It doesn't change the stored notes.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Deserialize, Serialize)]
+pub struct NoteRange {
+    /// How out-of-range notes are played. If None, they are played as-is.
+    pub mode: Option<NoteRangeMode>,
+    /// The lowest note in the range.
+    pub min: u8,
+    /// The highest note in the range.
+    pub max: u8,
+}
+
+impl Default for NoteRange {
+    fn default() -> Self {
+        Self {
+            mode: None,
+            min: MIN_NOTE,
+            max: MAX_NOTE,
+        }
+    }
+}
+
+impl NoteRange {
+    /// Set the next mode in the cycle: None, Clamp, Fold, None...
+    pub fn cycle_mode(&mut self) {
+        self.mode = match self.mode {
+            None => Some(NoteRangeMode::Clamp),
+            Some(NoteRangeMode::Clamp) => Some(NoteRangeMode::Fold),
+            Some(NoteRangeMode::Fold) => None,
+        }
+    }
+
+    /// Raise or lower the lowest note. It can't be higher than the highest note.
+    pub fn set_min(&mut self, up: bool) {
+        self.min = if up {
+            (self.min + 1).min(self.max)
+        } else {
+            self.min.saturating_sub(1).max(MIN_NOTE)
+        };
+    }
+
+    /// Raise or lower the highest note. It can't be lower than the lowest note.
+    pub fn set_max(&mut self, up: bool) {
+        self.max = if up {
+            (self.max + 1).min(MAX_NOTE)
+        } else {
+            self.max.saturating_sub(1).max(self.min)
+        };
+    }
+
+    /// Returns the note that should be played instead of `note`.
+    ///
+    /// If the range is narrower than an octave, a folded note might still be out of range; in that case, it is clamped.
+    pub fn apply(&self, note: u8) -> u8 {
+        if (self.min..=self.max).contains(&note) {
+            return note;
+        }
+        match self.mode {
+            None => note,
+            Some(NoteRangeMode::Clamp) => note.clamp(self.min, self.max),
+            Some(NoteRangeMode::Fold) => {
+                let mut n = note;
+                while n < self.min && n + OCTAVE <= self.max {
+                    n += OCTAVE;
+                }
+                while n > self.max && n >= self.min + OCTAVE {
+                    n -= OCTAVE;
+                }
+                n.clamp(self.min, self.max)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{NoteRange, NoteRangeMode, MAX_NOTE, MIN_NOTE};
+
+    #[test]
+    fn note_range() {
+        let mut range = NoteRange::default();
+        assert_eq!(range.apply(MIN_NOTE), MIN_NOTE);
+        assert_eq!(range.apply(MAX_NOTE), MAX_NOTE);
+        range.min = 48;
+        range.max = 72;
+        // By default, notes are played as-is.
+        assert_eq!(range.apply(30), 30);
+        range.cycle_mode();
+        assert_eq!(range.mode, Some(NoteRangeMode::Clamp));
+        assert_eq!(range.apply(30), 48);
+        assert_eq!(range.apply(80), 72);
+        assert_eq!(range.apply(60), 60);
+        range.cycle_mode();
+        assert_eq!(range.mode, Some(NoteRangeMode::Fold));
+        assert_eq!(range.apply(30), 54);
+        assert_eq!(range.apply(97), 61);
+        assert_eq!(range.apply(60), 60);
+        // The range is narrower than an octave.
+        range.max = 52;
+        assert_eq!(range.apply(40), 52);
+        assert_eq!(range.apply(30), 48);
+        assert_eq!(range.apply(58), 52);
+        range.cycle_mode();
+        assert_eq!(range.mode, None);
+    }
+
+    #[test]
+    fn note_range_bounds() {
+        let mut range = NoteRange::default();
+        range.set_min(false);
+        assert_eq!(range.min, MIN_NOTE);
+        range.set_max(true);
+        assert_eq!(range.max, MAX_NOTE);
+        range.min = 60;
+        range.max = 60;
+        range.set_min(true);
+        assert_eq!(range.min, 60);
+        range.set_max(false);
+        assert_eq!(range.max, 60);
+    }
+}
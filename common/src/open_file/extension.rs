@@ -10,6 +10,7 @@ pub enum Extension {
     MP3,
     Ogg,
     Flac,
+    Opus,
 }
 
 impl Extension {
@@ -67,6 +68,13 @@ impl Extension {
                     "flac"
                 }
             }
+            Self::Opus => {
+                if period {
+                    ".opus"
+                } else {
+                    "opus"
+                }
+            }
         }
     }
 }
@@ -26,16 +26,22 @@ pub struct Paths {
     pub saves_directory: PathBuf,
     /// The path to the exported audio files.
     pub export_directory: PathBuf,
+    /// The path to the audio files of frozen tracks.
+    pub frozen_directory: PathBuf,
     /// The path to the splash image.
     pub splash_path: PathBuf,
     /// The path to the default soundfont in data/
     pub default_soundfont_path: PathBuf,
     /// The path to the data/ directory itself.
     pub data_directory: PathBuf,
+    /// The path to the file where copied notes are stored between sessions.
+    pub copied_notes_path: PathBuf,
+    /// The path to the file where the most recently used export directory is stored between sessions.
+    pub last_export_directory_path: PathBuf,
 }
 
 impl Paths {
-    /// Setup the paths, needs to be be called at least once.
+    /// Setup the paths, needs to be be called at least once. Later calls don't change the paths.
     pub fn init(data_directory_from_cli: &Path) {
         let data_directory = get_data_directory(data_directory_from_cli);
         let user_directory = match UserDirs::new() {
@@ -58,22 +64,27 @@ impl Paths {
         let soundfonts_directory = get_directory("soundfonts", &user_directory);
         let saves_directory = get_directory("saves", &user_directory);
         let export_directory = get_directory("exports", &user_directory);
+        let frozen_directory = get_directory("frozen", &user_directory);
         let splash_path = data_directory.join("splash.png");
         let default_soundfont_path = data_directory.join("CT1MBGMRSV1.06.sf2");
-        PATHS
-            .set(Self {
-                default_ini_path,
-                user_directory,
-                user_ini_path,
-                text_path,
-                soundfonts_directory,
-                saves_directory,
-                export_directory,
-                splash_path,
-                default_soundfont_path,
-                data_directory,
-            })
-            .unwrap();
+        let copied_notes_path = user_directory.join("copied_notes.json");
+        let last_export_directory_path = user_directory.join("last_export_directory.txt");
+        // If the paths were already set, they aren't replaced.
+        let _ = PATHS.set(Self {
+            default_ini_path,
+            user_directory,
+            user_ini_path,
+            text_path,
+            soundfonts_directory,
+            saves_directory,
+            export_directory,
+            frozen_directory,
+            splash_path,
+            default_soundfont_path,
+            data_directory,
+            copied_notes_path,
+            last_export_directory_path,
+        });
     }
 
     /// Get a reference to the paths, panics when not initialized.
@@ -0,0 +1,10 @@
+/// A problem with the music that would make an export empty or incorrect.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ProjectIssue {
+    /// Every track is muted, or there are no tracks.
+    NoPlayableTracks,
+    /// A playable track has notes but doesn't have a program.
+    NoProgram { channel: u8 },
+    /// A note in a playable track ends at or before its start time.
+    InvalidNote { channel: u8, index: usize },
+}
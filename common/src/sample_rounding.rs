@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumString;
+
+/// How a time in PPQ is rounded when it is converted to samples. See `Time::ppq_to_samples`.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, EnumString, Deserialize, Serialize)]
+#[strum(serialize_all = "lowercase")]
+pub enum SampleRounding {
+    /// Round down to the previous sample.
+    #[default]
+    Truncate,
+    /// Round to the nearest sample.
+    Nearest,
+}
@@ -44,6 +44,22 @@ impl SelectMode {
         }
     }
 
+    /// Returns the time span of a many-note selection in PPQ: The earliest start and the latest end of the selected notes.
+    /// Returns None if this is a single-note selection or if no notes are selected.
+    ///
+    /// - `music` The music.
+    pub fn get_time_range(&self, music: &Music) -> Option<(u64, u64)> {
+        match self {
+            SelectMode::Single(_) => None,
+            SelectMode::Many(_) => {
+                let notes = self.get_notes(music)?;
+                let start = notes.iter().map(|n| n.start).min()?;
+                let end = notes.iter().map(|n| n.end).max()?;
+                Some((start, end))
+            }
+        }
+    }
+
     /// Converts and returns the selection as a list of cloned notes from the selected track's `notes`.
     ///
     /// - `music` The music.
@@ -79,6 +79,14 @@ pub fn get_line_width(config: &Ini) -> f32 {
     parse(config.section(Some("RENDER")).unwrap(), "line_width")
 }
 
+/// Returns the minimum pixel width of a note in the piano roll.
+pub fn get_min_note_width(config: &Ini) -> f32 {
+    parse(
+        config.section(Some("PIANO_ROLL")).unwrap(),
+        "min_note_width",
+    )
+}
+
 /// Returns the size of the piano roll viewport.
 pub fn get_viewport_size(config: &Ini) -> [u32; 2] {
     let piano_roll_panel_size = get_piano_roll_panel_size(config);
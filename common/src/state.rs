@@ -1,3 +1,4 @@
+use crate::config::parse;
 use crate::music_panel_field::MusicPanelField;
 use crate::{
     EditMode, Index, IndexedEditModes, IndexedValues, InputState, Music, PanelType, PianoRollMode,
@@ -30,6 +31,12 @@ pub struct State {
     pub edit_mode: IndexedEditModes,
     /// The current selection.
     pub select_mode: SelectMode,
+    /// The punch-in time in PPQ. If not None, recorded notes that start before this time are rejected.
+    #[serde(default)]
+    pub punch_in: Option<u64>,
+    /// The punch-out time in PPQ. If not None, recorded notes that start at or after this time are rejected.
+    #[serde(default)]
+    pub punch_out: Option<u64>,
     /// If true, there are unsaved changes.
     #[serde(skip_serializing, skip_deserializing)]
     pub unsaved_changes: bool,
@@ -39,8 +46,9 @@ impl State {
     pub fn new(config: &Ini) -> State {
         let music = Music::default();
         let view = View::new(config);
-        let time = Time::default();
-        let input = InputState::default();
+        let mut time = Time::default();
+        time.sample_rounding = parse(config.section(Some("AUDIO")).unwrap(), "sample_rounding");
+        let input = InputState::new(config);
         let panels = vec![PanelType::Music, PanelType::Tracks, PanelType::PianoRoll];
         let focus = Index::new(0, panels.len());
         let music_panel_field = IndexedValues::new(
@@ -65,7 +73,25 @@ impl State {
             piano_roll_mode,
             edit_mode,
             select_mode,
+            punch_in: None,
+            punch_out: None,
             unsaved_changes: false,
         }
     }
+
+    /// Returns true if the piano roll is armed or if any track is record-armed.
+    pub fn is_armed(&self) -> bool {
+        self.input.armed || self.music.midi_tracks.iter().any(|t| t.record_armed)
+    }
+
+    /// Returns the start and end time of the punch region in PPQ, or None if there is no punch region.
+    pub fn get_punch(&self) -> Option<[u64; 2]> {
+        if self.punch_in.is_none() && self.punch_out.is_none() {
+            None
+        } else {
+            let t0 = self.punch_in.unwrap_or(0);
+            let t1 = self.punch_out.unwrap_or(u64::MAX);
+            Some([t0.min(t1), t0.max(t1)])
+        }
+    }
 }
@@ -0,0 +1,43 @@
+use crate::Note;
+use hashbrown::HashMap;
+
+/// The notes of a track, indexed by pitch and time so that tied notes can be found quickly. See `Note::get_tied`.
+pub struct Ties<'a> {
+    /// Key = The pitch and the start time. Value = The notes.
+    starts: HashMap<(u8, u64), Vec<&'a Note>>,
+    /// Key = The pitch and the end time. Value = The notes.
+    ends: HashMap<(u8, u64), Vec<&'a Note>>,
+}
+
+impl<'a> Ties<'a> {
+    /// - `notes` All of the notes in the track.
+    pub fn new(notes: &'a [Note]) -> Self {
+        let mut starts: HashMap<(u8, u64), Vec<&'a Note>> = HashMap::new();
+        let mut ends: HashMap<(u8, u64), Vec<&'a Note>> = HashMap::new();
+        for note in notes.iter() {
+            starts
+                .entry((note.note, note.start))
+                .or_default()
+                .push(note);
+            ends.entry((note.note, note.end)).or_default().push(note);
+        }
+        Self { starts, ends }
+    }
+
+    /// Returns the note that `note` is tied to, if any.
+    pub(crate) fn get_next(&self, note: &Note) -> Option<&'a Note> {
+        self.starts
+            .get(&(note.note, note.end))?
+            .iter()
+            .find(|n| note.is_tied_to(n))
+            .copied()
+    }
+
+    /// Returns true if a note that starts at or after `t0` is tied to `note`.
+    pub(crate) fn is_tied_from(&self, note: &Note, t0: u64) -> bool {
+        match self.ends.get(&(note.note, note.start)) {
+            Some(notes) => notes.iter().any(|n| n.start >= t0 && n.is_tied_to(note)),
+            None => false,
+        }
+    }
+}
@@ -1,10 +1,14 @@
 use crate::edit_mode::*;
-use crate::U64orF32;
+use crate::{SampleRounding, U64orF32};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 /// The default BPM.
 pub const DEFAULT_BPM: u64 = 120;
+/// The minimum BPM that can be set automatically.
+pub const MIN_BPM: u64 = 20;
+/// The maximum BPM that can be set automatically.
+pub const MAX_BPM: u64 = 999;
 /// Converts BPM to seconds.
 const BPM_TO_SECONDS: f32 = 60.0;
 /// Pulses per quarter note as a u64.
@@ -25,6 +29,22 @@ pub struct Time {
     pub bpm: U64orF32,
     /// The current edit mode.
     pub mode: IndexedEditModes,
+    /// The start time of the loop region in PPQ.
+    #[serde(default)]
+    pub loop_start: u64,
+    /// The end time of the loop region in PPQ.
+    #[serde(default)]
+    pub loop_end: u64,
+    /// If true, playback returns to `loop_start` when it reaches `loop_end`.
+    #[serde(default)]
+    pub looping: bool,
+    /// The time in PPQ of the first downbeat of the song. Notes before this time are a pickup.
+    /// Beat numbers are relative to this time. This doesn't change when notes are played or exported.
+    #[serde(default)]
+    pub song_start: u64,
+    /// How times are rounded when they are converted to samples.
+    #[serde(default)]
+    pub sample_rounding: SampleRounding,
 }
 
 impl Time {
@@ -33,9 +53,22 @@ impl Time {
         ppq as f32 * (BPM_TO_SECONDS / (self.bpm.get_f() * PPQ_F))
     }
 
+    /// Converts seconds into pulses per quarter note.
+    pub fn seconds_to_ppq(&self, seconds: f32) -> u64 {
+        (seconds * self.bpm.get_f() * PPQ_F / BPM_TO_SECONDS) as u64
+    }
+
     /// Converts pulses per quarter note into a quantity of samples.
+    ///
+    /// This is calculated with f64s so that a time late in a long piece of music is still accurate to within a sample.
+    /// Always convert absolute times rather than summing converted durations, so that rounding errors can't accumulate.
     pub fn ppq_to_samples(&self, ppq: u64, framerate: f32) -> u64 {
-        (self.ppq_to_seconds(ppq) * framerate) as u64
+        let samples = (ppq as f64 * BPM_TO_SECONDS as f64 * framerate as f64)
+            / (self.bpm.get_f() as f64 * PPQ_F as f64);
+        match self.sample_rounding {
+            SampleRounding::Truncate => samples as u64,
+            SampleRounding::Nearest => samples.round() as u64,
+        }
     }
 
     /// Converts pulses per quarter note into a duration
@@ -45,7 +78,8 @@ impl Time {
 
     /// Converts a quantity of samples into pulses per quarter note.
     pub fn samples_to_ppq(&self, samples: u64, framerate: f32) -> u64 {
-        ((self.bpm.get_f() * samples as f32) / (BPM_TO_SECONDS * framerate) * PPQ_F) as u64
+        ((self.bpm.get_f() as f64 * samples as f64) / (BPM_TO_SECONDS as f64 * framerate as f64)
+            * PPQ_F as f64) as u64
     }
 }
 
@@ -56,6 +90,11 @@ impl Default for Time {
             playback: 0,
             bpm: U64orF32::from(DEFAULT_BPM),
             mode: EditMode::indexed(),
+            loop_start: 0,
+            loop_end: 0,
+            looping: false,
+            song_start: 0,
+            sample_rounding: SampleRounding::default(),
         }
     }
 }
@@ -80,7 +119,10 @@ mod tests {
         ppq_seconds(PPQ_U, 1.0, &time);
         ppq_seconds(288, 1.5, &time);
 
+        // Seconds to PPQ.
+        assert_eq!(time.seconds_to_ppq(1.5), 288);
         time.bpm = U64orF32::from(DEFAULT_BPM);
+        assert_eq!(time.seconds_to_ppq(0.5), PPQ_U);
 
         let framerate: f32 = 44100.0;
 
@@ -105,6 +147,38 @@ mod tests {
         samples_ppq(44100, PPQ_U * 2, framerate, &time);
     }
 
+    #[test]
+    fn ppq_to_samples_drift() {
+        let mut time = Time {
+            bpm: U64orF32::from(97),
+            ..Default::default()
+        };
+        let framerate = 44100u128;
+        // The last of 100,000 triplet eighth notes. This is more than five hours.
+        let ppq = 100_000 * (PPQ_U / 3);
+        // The exact position, as a fraction of samples.
+        let numerator = ppq as u128 * 60 * framerate;
+        let denominator = 97 * PPQ_U as u128;
+        let exact = numerator as f64 / denominator as f64;
+        for (sample_rounding, expected) in [
+            (SampleRounding::Truncate, numerator / denominator),
+            (
+                SampleRounding::Nearest,
+                (numerator + denominator / 2) / denominator,
+            ),
+        ] {
+            time.sample_rounding = sample_rounding;
+            let samples = time.ppq_to_samples(ppq, framerate as f32);
+            assert!(
+                (samples as f64 - exact).abs() <= 1.0,
+                "{} {}",
+                samples,
+                exact
+            );
+            assert_eq!(samples as u128, expected);
+        }
+    }
+
     fn ppq_seconds(ppq: u64, f: f32, time: &Time) {
         let t = time.ppq_to_seconds(ppq);
         assert_eq!(t, f, "{} {}", t, f);
@@ -21,6 +21,9 @@ pub struct View {
     pub mode: IndexedEditModes,
     /// If true, we're viewing a single track. If false, we're viewing multiple tracks.
     pub single_track: bool,
+    /// The distance between gridlines in PPQ. This is purely visual and is independent of the input beat.
+    #[serde(default = "default_grid_subdivision")]
+    pub grid_subdivision: u64,
     /// The zoom time deltas.
     zoom_levels: Vec<u64>,
     /// The index of the current zoom level.
@@ -86,11 +89,13 @@ impl View {
         zoom_increments.insert(EditMode::Normal, normal_zoom);
         zoom_increments.insert(EditMode::Quick, quick_zoom);
         zoom_increments.insert(EditMode::Precise, precise_zoom);
+        let grid_subdivision = parse_fraction(section, "grid_subdivision") * PPQ_U;
         Self {
             dt,
             dn,
             mode,
             single_track: true,
+            grid_subdivision,
             zoom_levels,
             zoom_index,
             zoom_increments,
@@ -119,6 +124,18 @@ impl View {
         };
     }
 
+    /// If the time range `t0..t1` isn't fully visible, scroll the viewport so that it starts at `t0`.
+    ///
+    /// - `t0` The start time in PPQ.
+    /// - `t1` The end time in PPQ.
+    pub fn show_time(&mut self, t0: u64, t1: u64) {
+        if t0 >= self.dt[0] && t1 <= self.dt[1] {
+            return;
+        }
+        let delta = self.get_dt();
+        self.dt = [t0, t0 + delta];
+    }
+
     /// Move `self.dn` up or down.
     ///
     /// - `dn` Move `self.dn` by this value.
@@ -181,6 +198,11 @@ impl View {
     }
 }
 
+/// Older save files don't have a grid subdivision, so default to one beat.
+fn default_grid_subdivision() -> u64 {
+    PPQ_U
+}
+
 #[cfg(test)]
 mod tests {
     use crate::time::PPQ_U;
@@ -197,6 +219,7 @@ mod tests {
         assert_eq!(view.dt, [0, VIEW_T1], "{:?}", view.dt);
         assert_eq!(view.mode.index.get(), 0, "{}", view.mode.index.get());
         assert_eq!(view.single_track, true, "{}", view.single_track);
+        assert_eq!(view.grid_subdivision, PPQ_U, "{}", view.grid_subdivision);
     }
 
     #[test]
@@ -214,6 +237,20 @@ mod tests {
         view.dt = [0, VIEW_T1];
     }
 
+    #[test]
+    fn view_show_time() {
+        let mut view = get_new_view();
+        // The range is already visible.
+        view.show_time(PPQ_U, PPQ_U * 2);
+        assert_eq!(view.dt, [0, VIEW_T1], "{:?}", view.dt);
+        // Scroll forward.
+        view.show_time(VIEW_T1, VIEW_T1 + PPQ_U);
+        assert_eq!(view.dt, [VIEW_T1, VIEW_T1 * 2], "{:?}", view.dt);
+        // Scroll back.
+        view.show_time(PPQ_U, PPQ_U * 2);
+        assert_eq!(view.dt, [PPQ_U, VIEW_T1 + PPQ_U], "{:?}", view.dt);
+    }
+
     #[test]
     fn view_dz() {
         let mut view = get_new_view();
@@ -14,11 +14,19 @@ pub enum InputEvent {
     AppTTS,
     FileTTS,
     StopTTS,
+    HistoryStatusTTS,
+    // Monitor gain.
+    DecreaseMonitorGain,
+    IncreaseMonitorGain,
+    ToggleMonoMonitor,
     // Enable links panel.
     EnableLinksPanel,
     // Undo-redo.
     Undo,
     Redo,
+    HistoryBack,
+    HistoryForward,
+    RepeatLastAction,
     // Files.
     OpenFile,
     NewFile,
@@ -37,6 +45,14 @@ pub enum InputEvent {
     // Tracks panel.
     AddTrack,
     RemoveTrack,
+    AddTemplateTrack,
+    ExplodeChords,
+    FreezeTrack,
+    CycleNoteRangeMode,
+    NoteRangeMinDown,
+    NoteRangeMinUp,
+    NoteRangeMaxDown,
+    NoteRangeMaxUp,
     NextTrack,
     PreviousTrack,
     EnableSoundFontPanel,
@@ -48,6 +64,11 @@ pub enum InputEvent {
     DecreaseTrackGain,
     Mute,
     Solo,
+    RecordArm,
+    ToggleMidiInputChannel,
+    ExportSelect,
+    CycleTrackColor,
+    ClearTrack,
     // Open file panel.
     UpDirectory,
     DownDirectory,
@@ -75,7 +96,10 @@ pub enum InputEvent {
     IncreaseInputVolume,
     DecreaseInputVolume,
     ToggleInputVolume,
+    ToggleRhythmPattern,
     PlayStop,
+    PlayFromCursor,
+    AuditionTrack,
     PianoRollPreviousTrack,
     PianoRollNextTrack,
     // Piano roll - view mode.
@@ -88,6 +112,8 @@ pub enum InputEvent {
     ViewZoomIn,
     ViewZoomOut,
     ViewZoomDefault,
+    ViewGridSubdivisionLeft,
+    ViewGridSubdivisionRight,
     // Piano roll - time mode.
     TimeCursorLeft,
     TimeCursorRight,
@@ -101,6 +127,13 @@ pub enum InputEvent {
     TimePlaybackCursor,
     TimeCursorBeat,
     TimePlaybackBeat,
+    TimePunchIn,
+    TimePunchOut,
+    TimeClearPunch,
+    TimeSetSongStart,
+    TimeGoTo,
+    TimeFitTempo,
+    TimeToggleScrub,
     // Piano roll - edit mode.
     EditStartLeft,
     EditStartRight,
@@ -110,6 +143,9 @@ pub enum InputEvent {
     EditPitchDown,
     EditVolumeUp,
     EditVolumeDown,
+    EditArticulation,
+    EditTie,
+    SetVelocity,
     // Piano roll - select mode.
     SelectStartLeft,
     SelectStartRight,
@@ -117,11 +153,21 @@ pub enum InputEvent {
     SelectEndRight,
     SelectAll,
     SelectNone,
-    // Copy, cut, paste, delete.
+    InvertSelection,
+    SelectColumn,
+    // Copy, cut, paste, delete, repeat.
     CopyNotes,
     CutNotes,
     PasteNotes,
     DeleteNotes,
+    RepeatNotes,
+    AlignToCursor,
+    GoToLoudestNote,
+    GoToQuietestNote,
+    VolumeEnvelopeUp,
+    VolumeEnvelopeDown,
+    RemoveVolumePoint,
+    LoopSelection,
     // Quit Panel.
     QuitPanelYes,
     QuitPanelNo,
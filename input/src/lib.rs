@@ -9,9 +9,12 @@ mod midi_binding;
 mod midi_conn;
 mod note_on;
 mod qwerty_binding;
+mod qwerty_layout;
+mod velocity_curve;
 use common::args::Args;
+use common::config::{parse, parse_bool};
 use common::{State, MAX_NOTE, MIN_NOTE};
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use ini::Ini;
 pub use input_event::InputEvent;
 pub use keys::KEYS;
@@ -21,10 +24,12 @@ use midi_binding::MidiBinding;
 use midi_conn::MidiConn;
 use note_on::NoteOn;
 pub use qwerty_binding::QwertyBinding;
+use qwerty_layout::QwertyLayout;
 use serde_json::from_str;
 use std::fs::File;
 use std::io::Read;
 use std::str::FromStr;
+use velocity_curve::VelocityCurve;
 
 const MAX_OCTAVE: u8 = 9;
 /// Only these events are allowed during alphanumeric input.
@@ -42,21 +47,6 @@ const ALLOWED_DURING_ALPHANUMERIC_INPUT: [InputEvent; 12] = [
     InputEvent::PreviousPath,
     InputEvent::CloseOpenFile,
 ];
-/// Note-on events generated by a qwerty keyboard, and the index of each key on a C scale.
-const QWERTY_NOTE_EVENTS: [(InputEvent, u8); 12] = [
-    (InputEvent::G, 7),
-    (InputEvent::FSharp, 6),
-    (InputEvent::F, 5),
-    (InputEvent::E, 4),
-    (InputEvent::DSharp, 3),
-    (InputEvent::D, 2),
-    (InputEvent::CSharp, 1),
-    (InputEvent::C, 0),
-    (InputEvent::B, 11),
-    (InputEvent::ASharp, 10),
-    (InputEvent::A, 9),
-    (InputEvent::GSharp, 8),
-];
 /// Don't allow these when typing a filename.
 const ILLEGAL_FILENAME_CHARACTERS: [char; 23] = [
     '!', '@', '#', '$', '%', '^', '&', '*', '=', '+', '{', '}', '\\', '|', ':', '"', '\'', '<',
@@ -68,6 +58,8 @@ const ILLEGAL_FILENAME_CHARACTERS: [char; 23] = [
 pub struct Input {
     /// Events that began on this frame (usually due to a key press or MIDI controller message).
     events: Vec<InputEvent>,
+    /// Events whose qwerty bindings are being held down on this frame.
+    held: Vec<InputEvent>,
     /// The MIDI connection.
     midi_conn: Option<MidiConn>,
     // Note-on MIDI messages. These will be sent immediately to the synthesizer to be played.
@@ -78,12 +70,21 @@ pub struct Input {
     note_on_events: Vec<NoteOn>,
     /// Notes that were added after all note-off events are done.
     pub new_notes: Vec<[u8; 3]>,
+    /// For each note in `new_notes`, true if the note is from a MIDI device, or false if it's from qwerty input.
+    /// Qwerty notes always have a status byte of 144 (channel 0), so their channel is meaningless.
+    pub new_notes_midi: Vec<bool>,
     /// Input events generated by MIDI input.
     midi_events: HashMap<InputEvent, MidiBinding>,
     /// Input events generated by qwerty input.
     qwerty_events: HashMap<InputEvent, QwertyBinding>,
     /// The octave for qwerty input.
     qwerty_octave: u8,
+    /// How qwerty note events are mapped to pitches.
+    qwerty_layout: QwertyLayout,
+    /// The response curve applied to the velocity of MIDI note-on messages.
+    velocity_curve: VelocityCurve,
+    /// If true, note-ons with the same pitch on the same frame are collapsed into one note-on.
+    dedup_note_ons: bool,
     /// Was backspace pressed on this frame?
     backspace: bool,
     /// Characters pressed on this frame.
@@ -117,6 +118,14 @@ impl Input {
             midi_events.insert(k_input.0, k_input.1);
         }
 
+        // Get the qwerty note layout.
+        let qwerty_layout = parse(config.section(Some("QWERTY_NOTES")).unwrap(), "layout");
+
+        // Get the MIDI velocity curve.
+        let midi_input_section = config.section(Some("MIDI_INPUT")).unwrap();
+        let velocity_curve = parse(midi_input_section, "velocity_curve");
+        let dedup_note_ons = parse_bool(midi_input_section, "dedup_note_ons");
+
         let mut debug_inputs = vec![];
         if let Some(events) = &args.events {
             match File::open(events) {
@@ -140,6 +149,9 @@ impl Input {
             qwerty_events,
             midi_events,
             qwerty_octave: 4,
+            qwerty_layout,
+            velocity_curve,
+            dedup_note_ons,
             debug_inputs,
             ..Default::default()
         }
@@ -159,9 +171,12 @@ impl Input {
     /// 2. Add note messages to the list for playing notes.
     /// 3. Store new note-on events.
     /// 4. If all note-ons have had a corresponding note-off, add them to the new notes lists.
+    ///
+    /// Finally, store the new events. An event that is bound to both a qwerty key and a MIDI control happens at most once per frame.
     pub fn update(&mut self, state: &State) {
         // Clear the old new notes.
         self.new_notes.clear();
+        self.new_notes_midi.clear();
         self.note_on_messages.clear();
         self.note_off_keys.clear();
 
@@ -191,6 +206,14 @@ impl Input {
             .map(|q| *q.0)
             .collect();
 
+        // Get the held keys.
+        let mut held: Vec<InputEvent> = self
+            .qwerty_events
+            .iter()
+            .filter(|q| q.1.down)
+            .map(|q| *q.0)
+            .collect();
+
         // DEBUG.
         if cfg!(debug_assertions) && !&self.debug_inputs.is_empty() {
             let e = self.debug_inputs.remove(0);
@@ -198,7 +221,8 @@ impl Input {
         }
 
         // Qwerty note input.
-        for (_, note_index) in QWERTY_NOTE_EVENTS
+        let qwerty_note_events = self.qwerty_layout.get_note_events();
+        for (_, note_index) in qwerty_note_events
             .iter()
             .filter(|(e, _)| events.contains(e))
         {
@@ -213,7 +237,7 @@ impl Input {
             self.qwerty_octave -= 1;
         }
         // Qwerty note-off.
-        for (_, qwerty_note_off) in QWERTY_NOTE_EVENTS.iter().filter(|(e, _)| {
+        for (_, qwerty_note_off) in qwerty_note_events.iter().filter(|(e, _)| {
             self.qwerty_events[e]
                 .keys
                 .iter()
@@ -232,17 +256,18 @@ impl Input {
         // Remove events during alphanumeric input.
         if state.input.alphanumeric_input {
             events.retain(|e| ALLOWED_DURING_ALPHANUMERIC_INPUT.contains(e));
+            held.retain(|e| ALLOWED_DURING_ALPHANUMERIC_INPUT.contains(e));
         }
-        self.events = events;
+        self.held = held;
 
         // MIDI INPUT.
         if let Some(midi_conn) = &mut self.midi_conn {
-            // Poll for MIDI events.
-            let mut midi = midi_conn.buffer.lock();
+            // Poll for MIDI events and clear the MIDI buffer.
+            let midi: Vec<[u8; 3]> = midi_conn.buffer.lock().drain(..).collect();
             // Append MIDI events.
             for mde in self.midi_events.iter_mut() {
                 if mde.1.update(&midi, self.time_counter) {
-                    self.events.push(*mde.0);
+                    events.push(*mde.0);
                 }
             }
             // Increment the time counter.
@@ -251,27 +276,18 @@ impl Input {
                 self.time_counter = 0;
             }
             // Get note-on and note-off events.
-            let volume = state.input.volume.get();
-            for midi in midi.iter() {
+            for midi in midi
+                .iter()
+                .filter(|m| state.input.accepts_midi_channel(m[0]))
+            {
                 // Note-on.
                 if midi[0] >= 144 && midi[0] <= 159 && midi[1] > MIN_NOTE && midi[2] <= MAX_NOTE {
-                    // Set the volume.
-                    let midi = if state.input.use_volume {
-                        [midi[0], midi[1], volume]
-                    } else {
-                        *midi
-                    };
-                    // Remember the note-on for piano roll input.
-                    if state.input.armed {
-                        self.note_on_events.push(NoteOn::new(&midi));
-                    }
-                    // Copy this note to the immediate note-on array.
-                    self.note_on_messages.push(midi);
+                    self.midi_note_on(midi, state);
                 }
                 // Note-off.
                 if midi[0] >= 128 && midi[0] <= 143 {
                     self.note_off_keys.push(midi[1]);
-                    if state.input.armed {
+                    if state.is_armed() {
                         // Find the corresponding note.
                         for note_on in self.note_on_events.iter_mut() {
                             // Same key. Note-off.
@@ -286,12 +302,36 @@ impl Input {
             if !self.note_on_events.is_empty() && self.note_on_events.iter().all(|n| n.off) {
                 for note_on in self.note_on_events.iter() {
                     self.new_notes.push(note_on.note);
+                    self.new_notes_midi.push(true);
                 }
                 self.note_on_events.clear();
             }
-            // Clear the MIDI buffer.
-            midi.clear();
         }
+
+        // Store the events.
+        self.events = Self::dedup_events(events);
+    }
+
+    /// Remove repeated events, e.g. if an event is bound to both a qwerty key and a MIDI control and both were pressed. The order of the events is preserved.
+    fn dedup_events(mut events: Vec<InputEvent>) -> Vec<InputEvent> {
+        let mut unique = HashSet::new();
+        events.retain(|e| unique.insert(*e));
+        events
+    }
+
+    /// Replace the events of this frame with `event`, as if it had just happened.
+    pub fn set_event(&mut self, event: InputEvent) {
+        self.set_events(&[event]);
+    }
+
+    /// Replace the events of this frame with `events`, as if they had just happened, e.g. because they share a key.
+    pub fn set_events(&mut self, events: &[InputEvent]) {
+        self.events = events.to_vec();
+    }
+
+    /// Returns true if any event happened on this frame.
+    pub fn has_events(&self) -> bool {
+        !self.events.is_empty()
     }
 
     /// Returns true if the event happened.
@@ -299,6 +339,12 @@ impl Input {
         self.events.contains(event)
     }
 
+    /// Returns true if the qwerty binding of the event is being held down.
+    /// Unlike `happened`, this is true on every frame until the keys are released.
+    pub fn is_held(&self, event: &InputEvent) -> bool {
+        self.held.contains(event)
+    }
+
     /// Reads the qwerty and MIDI bindings for an event.
     pub fn get_bindings(
         &self,
@@ -409,12 +455,40 @@ impl Input {
     /// Push a new note from qwerty input.
     fn qwerty_note(&mut self, note: u8, state: &State) {
         let note: [u8; 3] = [144, self.get_pitch(note), state.input.volume.get()];
-        if state.input.armed {
+        if self.is_duplicate_note_on(&note) {
+            return;
+        }
+        if state.is_armed() {
             self.new_notes.push(note);
+            self.new_notes_midi.push(false);
         }
         self.note_on_messages.push(note);
     }
 
+    /// Push a new note from a MIDI note-on message.
+    fn midi_note_on(&mut self, midi: &[u8; 3], state: &State) {
+        // Set the volume.
+        let midi = if state.input.use_volume {
+            [midi[0], midi[1], state.input.volume.get()]
+        } else {
+            [midi[0], midi[1], self.velocity_curve.apply(midi[2])]
+        };
+        if self.is_duplicate_note_on(&midi) {
+            return;
+        }
+        // Remember the note-on for piano roll input.
+        if state.is_armed() {
+            self.note_on_events.push(NoteOn::new(&midi));
+        }
+        // Copy this note to the immediate note-on array.
+        self.note_on_messages.push(midi);
+    }
+
+    /// Returns true if we should ignore a note-on because there was already a note-on with the same pitch on this frame.
+    fn is_duplicate_note_on(&self, note: &[u8; 3]) -> bool {
+        self.dedup_note_ons && self.note_on_messages.iter().any(|n| n[1] == note[1])
+    }
+
     /// Converts the note index to a MIDI note value.
     fn get_pitch(&self, note: u8) -> u8 {
         (9 - self.qwerty_octave) * 12 + note
@@ -428,3 +502,47 @@ impl Input {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Input, InputEvent};
+    use common::State;
+    use ini::Ini;
+
+    #[test]
+    fn dedup_note_ons() {
+        let mut state = State::new(&Ini::load_from_file("../data/config.ini").unwrap());
+        state.input.armed = true;
+        for dedup_note_ons in [true, false] {
+            let mut input = Input {
+                qwerty_octave: 4,
+                dedup_note_ons,
+                ..Default::default()
+            };
+            // Play the same pitch on the qwerty keyboard and twice on a MIDI controller.
+            input.qwerty_note(0, &state);
+            let pitch = input.get_pitch(0);
+            input.midi_note_on(&[144, pitch, 100], &state);
+            input.midi_note_on(&[145, pitch, 100], &state);
+            let note_ons = if dedup_note_ons { 1 } else { 3 };
+            assert_eq!(input.note_on_messages.len(), note_ons);
+            assert_eq!(input.new_notes.len(), 1);
+            assert_eq!(input.new_notes_midi, vec![false]);
+            assert_eq!(input.note_on_events.len(), note_ons - 1);
+        }
+    }
+
+    #[test]
+    fn dedup_events() {
+        // The same event from the qwerty keyboard and from a MIDI controller.
+        let events = vec![
+            InputEvent::NextPanel,
+            InputEvent::OctaveUp,
+            InputEvent::NextPanel,
+        ];
+        assert_eq!(
+            Input::dedup_events(events),
+            vec![InputEvent::NextPanel, InputEvent::OctaveUp]
+        );
+    }
+}
@@ -21,6 +21,8 @@ pub struct QwertyBinding {
     repeatable: bool,
     /// If true, this event is pressed.
     pub(crate) pressed: bool,
+    /// If true, all of the keys and mods are held down.
+    pub(crate) down: bool,
 }
 
 impl QwertyBinding {
@@ -59,6 +61,7 @@ impl QwertyBinding {
                     sensitivity,
                     frame: 0,
                     pressed: false,
+                    down: false,
                 }
             }
             Err(error) => panic!(
@@ -82,6 +85,8 @@ impl QwertyBinding {
     /// - All of the above is true.
     /// - A sufficient number of frames have elapsed.
     ///
+    /// The keys are held if all of the `mods` and `keys` are down, regardless of the number of elapsed frames.
+    ///
     /// Parameters:
     ///
     /// - `pressed` The keys that were pressed on this frame.
@@ -94,10 +99,15 @@ impl QwertyBinding {
         alphanumeric: bool,
     ) {
         self.pressed = false;
+        self.down = false;
         // Mods.
         if self.mods.iter().all(|m| down.contains(m))
             && !self.non_mods.iter().any(|m| down.contains(m))
         {
+            // Held.
+            self.down = self.keys.iter().all(|k| {
+                (!alphanumeric || !ALPHANUMERIC_INPUT_MODS.contains(k)) && down.contains(k)
+            });
             // Pressed.
             if self.keys.iter().all(|k| {
                 (!alphanumeric || !ALPHANUMERIC_INPUT_MODS.contains(k)) && pressed.contains(k)
@@ -0,0 +1,77 @@
+use crate::InputEvent;
+use strum_macros::EnumString;
+
+/// Note-on events generated by a qwerty keyboard in the piano layout, and the index of each key on a C scale.
+const PIANO: [(InputEvent, u8); 12] = [
+    (InputEvent::G, 7),
+    (InputEvent::FSharp, 6),
+    (InputEvent::F, 5),
+    (InputEvent::E, 4),
+    (InputEvent::DSharp, 3),
+    (InputEvent::D, 2),
+    (InputEvent::CSharp, 1),
+    (InputEvent::C, 0),
+    (InputEvent::B, 11),
+    (InputEvent::ASharp, 10),
+    (InputEvent::A, 9),
+    (InputEvent::GSharp, 8),
+];
+/// Note-on events generated by a qwerty keyboard in the chromatic layout, and the index of each key on a C scale.
+const CHROMATIC: [(InputEvent, u8); 12] = [
+    (InputEvent::C, 0),
+    (InputEvent::D, 1),
+    (InputEvent::E, 2),
+    (InputEvent::F, 3),
+    (InputEvent::G, 4),
+    (InputEvent::A, 5),
+    (InputEvent::B, 6),
+    (InputEvent::CSharp, 7),
+    (InputEvent::DSharp, 8),
+    (InputEvent::FSharp, 9),
+    (InputEvent::GSharp, 10),
+    (InputEvent::ASharp, 11),
+];
+
+/// How qwerty note events are mapped to pitches.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub(crate) enum QwertyLayout {
+    /// Each note event plays the note it is named after.
+    /// With the default bindings, the home row plays the white keys and the row above it plays the black keys.
+    #[default]
+    Piano,
+    /// Each note event is one half-step above the previous one.
+    /// With the default bindings, the home row plays C to F# and the row above it plays G to B.
+    Chromatic,
+}
+
+impl QwertyLayout {
+    /// Returns the note-on events and the index of each key on a C scale.
+    pub(crate) fn get_note_events(&self) -> &'static [(InputEvent, u8); 12] {
+        match self {
+            Self::Piano => &PIANO,
+            Self::Chromatic => &CHROMATIC,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QwertyLayout;
+    use std::str::FromStr;
+
+    #[test]
+    fn qwerty_layout() {
+        assert_eq!(QwertyLayout::from_str("piano"), Ok(QwertyLayout::Piano));
+        assert_eq!(
+            QwertyLayout::from_str("chromatic"),
+            Ok(QwertyLayout::Chromatic)
+        );
+        // Each layout covers every semitone exactly once.
+        for layout in [QwertyLayout::Piano, QwertyLayout::Chromatic] {
+            let mut notes: Vec<u8> = layout.get_note_events().iter().map(|(_, n)| *n).collect();
+            notes.sort();
+            assert_eq!(notes, (0..12).collect::<Vec<u8>>());
+        }
+    }
+}
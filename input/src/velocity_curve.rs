@@ -0,0 +1,53 @@
+use strum_macros::EnumString;
+
+/// The maximum MIDI velocity.
+const MAX_VELOCITY: f32 = 127.0;
+
+/// A response curve that reshapes the velocity of incoming MIDI note-on messages.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub(crate) enum VelocityCurve {
+    /// Don't change the velocity.
+    #[default]
+    Linear,
+    /// Quieter notes are louder. It's easier to play loud notes.
+    Soft,
+    /// Quieter notes are quieter. It's harder to play loud notes.
+    Hard,
+}
+
+impl VelocityCurve {
+    /// Returns the reshaped velocity (0-127).
+    pub(crate) fn apply(&self, velocity: u8) -> u8 {
+        let exponent = match self {
+            Self::Linear => return velocity,
+            Self::Soft => 0.5,
+            Self::Hard => 2.0,
+        };
+        let v = (velocity as f32 / MAX_VELOCITY).clamp(0.0, 1.0);
+        (v.powf(exponent) * MAX_VELOCITY).round() as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VelocityCurve;
+    use std::str::FromStr;
+
+    #[test]
+    fn velocity_curve() {
+        for curve in [
+            VelocityCurve::Linear,
+            VelocityCurve::Soft,
+            VelocityCurve::Hard,
+        ] {
+            // The endpoints map to themselves.
+            assert_eq!(curve.apply(0), 0, "{:?}", curve);
+            assert_eq!(curve.apply(127), 127, "{:?}", curve);
+        }
+        assert_eq!(VelocityCurve::Linear.apply(64), 64);
+        assert!(VelocityCurve::Soft.apply(64) > 64);
+        assert!(VelocityCurve::Hard.apply(64) < 64);
+        assert_eq!(VelocityCurve::from_str("soft"), Ok(VelocityCurve::Soft));
+    }
+}
@@ -0,0 +1,61 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A source of time for time-dependent features.
+///
+/// `IO` and its panels read the time from a `Clock` instead of calling `std::time` directly so that time-dependent features can be tested with `MockClock`.
+pub trait Clock {
+    /// Returns the current time as a duration since the Unix epoch.
+    fn now(&self) -> Duration;
+}
+
+/// A clock that reads the system time.
+#[derive(Default)]
+pub struct RealClock;
+
+impl Clock for RealClock {
+    fn now(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+}
+
+/// A deterministic clock for tests. It starts at the Unix epoch and time only passes when `advance` is called.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct MockClock {
+    /// The current time.
+    now: std::cell::Cell<Duration>,
+}
+
+#[cfg(test)]
+impl MockClock {
+    /// Move the clock forward by `duration`.
+    pub(crate) fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, MockClock, RealClock};
+    use std::time::Duration;
+
+    #[test]
+    fn clock() {
+        let clock = MockClock::default();
+        assert_eq!(clock.now(), Duration::ZERO);
+        clock.advance(Duration::from_millis(500));
+        clock.advance(Duration::from_millis(250));
+        assert_eq!(clock.now(), Duration::from_millis(750));
+        // The real clock is after the epoch.
+        assert!(RealClock.now() > Duration::ZERO);
+    }
+}
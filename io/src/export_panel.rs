@@ -1,5 +1,4 @@
 use crate::panel::*;
-use audio::export::ExportState;
 use common::PanelType;
 
 /// Are we done yet?
@@ -9,6 +8,12 @@ pub(crate) struct ExportPanel {
     panels: Vec<PanelType>,
     /// The previous focus.
     focus: usize,
+    /// If true, we said how long the export will be.
+    estimated: bool,
+    /// If true, the user cancelled the export.
+    cancelled: bool,
+    /// The tooltips handler.
+    tooltips: Tooltips,
 }
 
 impl ExportPanel {
@@ -16,6 +21,8 @@ impl ExportPanel {
     pub fn enable(&mut self, state: &mut State, panels: &[PanelType], focus: usize) {
         self.panels = panels.to_vec();
         self.focus = focus;
+        self.estimated = false;
+        self.cancelled = false;
         state.panels = vec![PanelType::ExportState];
         state.focus.set(0);
     }
@@ -26,21 +33,85 @@ impl Panel for ExportPanel {
         &mut self,
         state: &mut State,
         conn: &mut Conn,
-        _: &Input,
-        _: &mut TTS,
-        _: &Text,
+        input: &Input,
+        tts: &mut TTS,
+        text: &Text,
         _: &mut PathsState,
     ) -> Option<Snapshot> {
+        // Say how long the export will be.
+        if !self.estimated {
+            self.estimated = true;
+            let samples = conn.estimate_export_samples(state);
+            let ppq = state
+                .time
+                .samples_to_ppq(samples, conn.exporter.framerate.get_f());
+            let estimate = text.get_time(ppq, &state.time);
+            // Say which part of the music is being exported.
+            let s = match conn.get_export_range(state) {
+                Some((t0, t1)) => text.get_with_values(
+                    "EXPORT_PANEL_TTS_ESTIMATE_RANGE",
+                    &[
+                        &estimate,
+                        &text.get_time(t0, &state.time),
+                        &text.get_time(t1, &state.time),
+                    ],
+                ),
+                None => text.get_with_values("EXPORT_PANEL_TTS_ESTIMATE", &[&estimate]),
+            };
+            tts.enqueue(s);
+            tts.enqueue(self.tooltips.get_tooltip(
+                "EXPORT_PANEL_TTS_CANCEL",
+                &[InputEvent::CloseOpenFile],
+                input,
+                text,
+            ));
+        }
+        // Cancel the export. We'll say that it was cancelled when it actually stops.
+        if conn.exporting() && input.happened(&InputEvent::CloseOpenFile) {
+            conn.do_commands(&[Command::StopExport]);
+            self.cancelled = true;
+        }
+        // Say how much has been exported.
+        else if input.happened(&InputEvent::StatusTTS) {
+            let progress = conn.get_export_progress();
+            if let Some(progress) = progress {
+                let mut s = vec![];
+                // Say which file is being exported.
+                if let Some((index, num_files)) = conn.get_export_file() {
+                    s.push(text.get_with_values(
+                        "EXPORT_PANEL_STATUS_TTS_FILE",
+                        &[&(index + 1).to_string(), &num_files.to_string()],
+                    ));
+                }
+                s.push(text.get_with_values(
+                    "EXPORT_PANEL_STATUS_TTS",
+                    &[&((progress * 100.0) as u8).to_string()],
+                ));
+                tts.enqueue(s.join(" "));
+            }
+        }
         // We're done.
-        let export_state = conn.export_state.lock();
-        if *export_state == ExportState::NotExporting {
+        if !conn.exporting() {
+            // Say how many files were written before the export was cancelled.
+            if self.cancelled {
+                let (exported_files, num_files) = conn.get_exported_files();
+                let s = if num_files > 1 {
+                    text.get_with_values(
+                        "EXPORT_PANEL_TTS_CANCELLED_FILES",
+                        &[&exported_files.to_string(), &num_files.to_string()],
+                    )
+                } else {
+                    text.get("EXPORT_PANEL_TTS_CANCELLED")
+                };
+                tts.enqueue(s);
+            }
             state.panels = self.panels.clone();
             state.focus.set(self.focus);
         }
         None
     }
 
-    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn) {}
+    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn, _: &mut TTS, _: &Text) {}
 
     fn update_abc123(
         &mut self,
@@ -1,22 +1,47 @@
 use crate::abc123::{on_disable_exporter, update_exporter};
 use crate::panel::*;
-use audio::export::{ExportSetting, ExportType, MultiFileSuffix};
-use audio::exporter::{Exporter, MP3_BIT_RATES};
+use audio::export::{ExportSetting, ExportType, MultiFileSuffix, Normalization, WavBitDepth};
+use audio::exporter::{Exporter, MP3_BIT_RATES, OPUS_BIT_RATES};
 use audio::Conn;
 use common::{IndexedValues, U64orF32};
+use ini::Ini;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use serde_json::from_str;
 
-/// All possible audio framerates.
-const FRAMERATES: [u64; 3] = [22050, 44100, 48000];
+/// The default audio framerates.
+const DEFAULT_FRAMERATES: [u64; 3] = [22050, 44100, 48000];
 
 /// Set the values of export settings.
-#[derive(Default)]
 pub(crate) struct ExportSettingsPanel {
+    /// All possible audio framerates.
+    framerates: Vec<u64>,
     tooltips: Tooltips,
 }
 
 impl ExportSettingsPanel {
+    pub fn new(config: &Ini) -> Self {
+        let framerates = match config
+            .section(Some("EXPORT"))
+            .and_then(|section| section.get("framerates"))
+        {
+            Some(value) => match from_str::<Vec<u64>>(value) {
+                Ok(framerates) => {
+                    if framerates.is_empty() || framerates.contains(&0) {
+                        panic!("Invalid export framerates: {}", value);
+                    }
+                    framerates
+                }
+                Err(error) => panic!("Error parsing export framerates {}: {}", value, error),
+            },
+            None => DEFAULT_FRAMERATES.to_vec(),
+        };
+        Self {
+            framerates,
+            tooltips: Tooltips::default(),
+        }
+    }
+
     /// Returns the text-to-speech status string for an alphanumeric field.
     ///
     /// - `tooltips` The tooltips handler.
@@ -158,15 +183,20 @@ impl ExportSettingsPanel {
     /// Set the export framerate.
     ///
     /// - `exporter` The exporter. This will have its framerate set.
-    /// - `up` Increment or decrement along the `FRAMERATES` array.
-    fn set_framerate(exporter: &mut Exporter, up: bool) {
-        let i = FRAMERATES
+    /// - `framerates` All possible framerates.
+    /// - `up` Increment or decrement along the `framerates` array. If the current framerate isn't in the array, this sets the framerate to the first element.
+    fn set_framerate(exporter: &mut Exporter, framerates: &[u64], up: bool) {
+        match framerates
             .iter()
             .position(|f| *f == exporter.framerate.get_u())
-            .unwrap();
-        let mut index = Index::new(i, FRAMERATES.len());
-        index.increment(up);
-        exporter.framerate = U64orF32::from(FRAMERATES[index.get()]);
+        {
+            Some(i) => {
+                let mut index = Index::new(i, framerates.len());
+                index.increment(up);
+                exporter.framerate = U64orF32::from(framerates[index.get()]);
+            }
+            None => exporter.framerate = U64orF32::from(framerates[0]),
+        }
     }
 
     /// Set the track number.
@@ -216,6 +246,7 @@ impl ExportSettingsPanel {
         mut f: F,
         state: &mut State,
         tooltips: &mut Tooltips,
+        framerates: &[u64],
         input: &Input,
         tts: &mut TTS,
         text: &Text,
@@ -301,6 +332,10 @@ impl ExportSettingsPanel {
                     "EXPORT_SETTINGS_PANEL_STATUS_TTS_QUALITY",
                     &[&exporter.ogg_quality.get().to_string()],
                 )),
+                ExportSetting::OpusBitRate => TtsString::from(text.get_with_values(
+                    "EXPORT_SETTINGS_PANEL_STATUS_TTS_BIT_RATE",
+                    &[&OPUS_BIT_RATES[exporter.opus_bit_rate.get()].to_string()],
+                )),
                 ExportSetting::TrackNumber => TtsString::from(text.get_with_values(
                     "EXPORT_SETTINGS_PANEL_STATUS_TTS_TRACK_NUMBER",
                     &[&match exporter.metadata.track_number {
@@ -316,6 +351,84 @@ impl ExportSettingsPanel {
                     input,
                     text,
                 ),
+                ExportSetting::MultiFileTail => Self::get_status_bool_tts(
+                    tooltips,
+                    "EXPORT_SETTINGS_PANEL_STATUS_TTS_MULTI_FILE_TAIL_ENABLED",
+                    "EXPORT_SETTINGS_PANEL_STATUS_TTS_MULTI_FILE_TAIL_DISABLED",
+                    exporter.multi_file_tail,
+                    input,
+                    text,
+                ),
+                ExportSetting::SelectionOnly => Self::get_status_bool_tts(
+                    tooltips,
+                    "EXPORT_SETTINGS_PANEL_STATUS_TTS_SELECTION_ONLY_ENABLED",
+                    "EXPORT_SETTINGS_PANEL_STATUS_TTS_SELECTION_ONLY_DISABLED",
+                    exporter.selection_only,
+                    input,
+                    text,
+                ),
+                ExportSetting::Normalization => match exporter.normalization.get() {
+                    Normalization::None => TtsString::from(
+                        text.get("EXPORT_SETTINGS_PANEL_STATUS_TTS_NO_NORMALIZATION"),
+                    ),
+                    Normalization::Loudness => TtsString::from(text.get_with_values(
+                        "EXPORT_SETTINGS_PANEL_STATUS_TTS_LOUDNESS_NORMALIZATION",
+                        &[&exporter.get_loudness_target().to_string()],
+                    )),
+                    Normalization::Peak => TtsString::from(text.get_with_values(
+                        "EXPORT_SETTINGS_PANEL_STATUS_TTS_PEAK_NORMALIZATION",
+                        &[&exporter.get_peak_target().to_string()],
+                    )),
+                },
+                ExportSetting::LoudnessTarget => TtsString::from(text.get_with_values(
+                    "EXPORT_SETTINGS_PANEL_STATUS_TTS_LOUDNESS_TARGET",
+                    &[&exporter.get_loudness_target().to_string()],
+                )),
+                ExportSetting::PeakTarget => TtsString::from(text.get_with_values(
+                    "EXPORT_SETTINGS_PANEL_STATUS_TTS_PEAK_TARGET",
+                    &[&exporter.get_peak_target().to_string()],
+                )),
+                ExportSetting::Limiter => Self::get_status_bool_tts(
+                    tooltips,
+                    "EXPORT_SETTINGS_PANEL_STATUS_TTS_LIMITER_ENABLED",
+                    "EXPORT_SETTINGS_PANEL_STATUS_TTS_LIMITER_DISABLED",
+                    exporter.limiter,
+                    input,
+                    text,
+                ),
+                ExportSetting::MidiRunningStatus => Self::get_status_bool_tts(
+                    tooltips,
+                    "EXPORT_SETTINGS_PANEL_STATUS_TTS_MIDI_RUNNING_STATUS_ENABLED",
+                    "EXPORT_SETTINGS_PANEL_STATUS_TTS_MIDI_RUNNING_STATUS_DISABLED",
+                    exporter.midi_running_status,
+                    input,
+                    text,
+                ),
+                ExportSetting::VelocityFloor => match exporter.velocity_floor.get() {
+                    0 => TtsString::from(
+                        text.get("EXPORT_SETTINGS_PANEL_STATUS_TTS_NO_VELOCITY_FLOOR"),
+                    ),
+                    velocity_floor => TtsString::from(text.get_with_values(
+                        "EXPORT_SETTINGS_PANEL_STATUS_TTS_VELOCITY_FLOOR",
+                        &[&velocity_floor.to_string()],
+                    )),
+                },
+                ExportSetting::Channels => Self::get_status_bool_tts(
+                    tooltips,
+                    "EXPORT_SETTINGS_PANEL_STATUS_TTS_MONO",
+                    "EXPORT_SETTINGS_PANEL_STATUS_TTS_STEREO",
+                    exporter.is_mono(),
+                    input,
+                    text,
+                ),
+                ExportSetting::Dither => Self::get_status_bool_tts(
+                    tooltips,
+                    "EXPORT_SETTINGS_PANEL_STATUS_TTS_DITHER_ENABLED",
+                    "EXPORT_SETTINGS_PANEL_STATUS_TTS_DITHER_DISABLED",
+                    exporter.dither,
+                    input,
+                    text,
+                ),
                 ExportSetting::MultiFileSuffix => {
                     let key = match &exporter.multi_file_suffix.get() {
                         MultiFileSuffix::Preset => {
@@ -330,6 +443,17 @@ impl ExportSettingsPanel {
                     };
                     TtsString::from(text.get_ref(key))
                 }
+                ExportSetting::WavBitDepth => {
+                    let key = match &exporter.wav_bit_depth.get() {
+                        WavBitDepth::Int16 => "EXPORT_SETTINGS_PANEL_WAV_BIT_DEPTH_16",
+                        WavBitDepth::Int24 => "EXPORT_SETTINGS_PANEL_WAV_BIT_DEPTH_24",
+                        WavBitDepth::Float32 => "EXPORT_SETTINGS_PANEL_WAV_BIT_DEPTH_32F",
+                    };
+                    TtsString::from(text.get_with_values(
+                        "EXPORT_SETTINGS_PANEL_STATUS_TTS_WAV_BIT_DEPTH",
+                        &[text.get_ref(key)],
+                    ))
+                }
             };
             tts.enqueue(s);
         }
@@ -399,7 +523,7 @@ impl ExportSettingsPanel {
                     input,
                     text,
                 ),
-                ExportSetting::Mp3BitRate => Self::get_input_lr_tts(
+                ExportSetting::Mp3BitRate | ExportSetting::OpusBitRate => Self::get_input_lr_tts(
                     tooltips,
                     "EXPORT_SETTINGS_PANEL_INPUT_TTS_MP3_BIT_RATE",
                     input,
@@ -422,12 +546,108 @@ impl ExportSettingsPanel {
                         .clone(),
                     Self::get_input_scroll_tts(tooltips, input, text),
                 ],
+                ExportSetting::MultiFileTail => vec![
+                    tooltips
+                        .get_tooltip(
+                            "EXPORT_SETTINGS_PANEL_INPUT_TTS_MULTI_FILE_TAIL",
+                            &[InputEvent::ToggleExportSettingBoolean],
+                            input,
+                            text,
+                        )
+                        .clone(),
+                    Self::get_input_scroll_tts(tooltips, input, text),
+                ],
+                ExportSetting::SelectionOnly => vec![
+                    tooltips
+                        .get_tooltip(
+                            "EXPORT_SETTINGS_PANEL_INPUT_TTS_SELECTION_ONLY",
+                            &[InputEvent::ToggleExportSettingBoolean],
+                            input,
+                            text,
+                        )
+                        .clone(),
+                    Self::get_input_scroll_tts(tooltips, input, text),
+                ],
+                ExportSetting::Normalization => Self::get_input_lr_tts(
+                    tooltips,
+                    "EXPORT_SETTINGS_PANEL_INPUT_TTS_NORMALIZATION",
+                    input,
+                    text,
+                ),
+                ExportSetting::LoudnessTarget => Self::get_input_lr_tts(
+                    tooltips,
+                    "EXPORT_SETTINGS_PANEL_INPUT_TTS_LOUDNESS_TARGET",
+                    input,
+                    text,
+                ),
+                ExportSetting::PeakTarget => Self::get_input_lr_tts(
+                    tooltips,
+                    "EXPORT_SETTINGS_PANEL_INPUT_TTS_PEAK_TARGET",
+                    input,
+                    text,
+                ),
+                ExportSetting::Limiter => vec![
+                    tooltips
+                        .get_tooltip(
+                            "EXPORT_SETTINGS_PANEL_INPUT_TTS_LIMITER",
+                            &[InputEvent::ToggleExportSettingBoolean],
+                            input,
+                            text,
+                        )
+                        .clone(),
+                    Self::get_input_scroll_tts(tooltips, input, text),
+                ],
+                ExportSetting::MidiRunningStatus => vec![
+                    tooltips
+                        .get_tooltip(
+                            "EXPORT_SETTINGS_PANEL_INPUT_TTS_MIDI_RUNNING_STATUS",
+                            &[InputEvent::ToggleExportSettingBoolean],
+                            input,
+                            text,
+                        )
+                        .clone(),
+                    Self::get_input_scroll_tts(tooltips, input, text),
+                ],
+                ExportSetting::VelocityFloor => Self::get_input_lr_tts(
+                    tooltips,
+                    "EXPORT_SETTINGS_PANEL_INPUT_TTS_VELOCITY_FLOOR",
+                    input,
+                    text,
+                ),
+                ExportSetting::Channels => vec![
+                    tooltips
+                        .get_tooltip(
+                            "EXPORT_SETTINGS_PANEL_INPUT_TTS_MONO",
+                            &[InputEvent::ToggleExportSettingBoolean],
+                            input,
+                            text,
+                        )
+                        .clone(),
+                    Self::get_input_scroll_tts(tooltips, input, text),
+                ],
+                ExportSetting::Dither => vec![
+                    tooltips
+                        .get_tooltip(
+                            "EXPORT_SETTINGS_PANEL_INPUT_TTS_DITHER",
+                            &[InputEvent::ToggleExportSettingBoolean],
+                            input,
+                            text,
+                        )
+                        .clone(),
+                    Self::get_input_scroll_tts(tooltips, input, text),
+                ],
                 ExportSetting::MultiFileSuffix => Self::get_input_lr_tts(
                     tooltips,
                     "EXPORT_SETTINGS_PANEL_INPUT_TTS_MULTI_FILE_SUFFIX",
                     input,
                     text,
                 ),
+                ExportSetting::WavBitDepth => Self::get_input_lr_tts(
+                    tooltips,
+                    "EXPORT_SETTINGS_PANEL_INPUT_TTS_WAV_BIT_DEPTH",
+                    input,
+                    text,
+                ),
             };
             tts.enqueue(s);
         }
@@ -445,9 +665,9 @@ impl ExportSettingsPanel {
                 // Framerate.
                 ExportSetting::Framerate => {
                     if input.happened(&InputEvent::PreviousExportSettingValue) {
-                        Self::set_framerate(exporter, false);
+                        Self::set_framerate(exporter, framerates, false);
                     } else if input.happened(&InputEvent::NextExportSettingValue) {
-                        Self::set_framerate(exporter, true);
+                        Self::set_framerate(exporter, framerates, true);
                     }
                 }
                 ExportSetting::Copyright => {
@@ -471,11 +691,60 @@ impl ExportSettingsPanel {
                 ExportSetting::OggQuality => {
                     Self::set_index(|e| &mut e.ogg_quality, input, exporter);
                 }
+                ExportSetting::OpusBitRate => {
+                    Self::set_index(|e| &mut e.opus_bit_rate, input, exporter);
+                }
                 ExportSetting::MultiFile => {
                     if input.happened(&InputEvent::ToggleExportSettingBoolean) {
                         exporter.multi_file = !exporter.multi_file;
                     }
                 }
+                ExportSetting::MultiFileTail => {
+                    if input.happened(&InputEvent::ToggleExportSettingBoolean) {
+                        exporter.multi_file_tail = !exporter.multi_file_tail;
+                    }
+                }
+                ExportSetting::SelectionOnly => {
+                    if input.happened(&InputEvent::ToggleExportSettingBoolean) {
+                        exporter.selection_only = !exporter.selection_only;
+                    }
+                }
+                ExportSetting::Normalization => {
+                    Self::set_index(
+                        |e: &mut Exporter| &mut e.normalization.index,
+                        input,
+                        exporter,
+                    );
+                }
+                ExportSetting::LoudnessTarget => {
+                    Self::set_index(|e| &mut e.loudness_target, input, exporter);
+                }
+                ExportSetting::PeakTarget => {
+                    Self::set_index(|e| &mut e.peak_target, input, exporter);
+                }
+                ExportSetting::Limiter => {
+                    if input.happened(&InputEvent::ToggleExportSettingBoolean) {
+                        exporter.limiter = !exporter.limiter;
+                    }
+                }
+                ExportSetting::MidiRunningStatus => {
+                    if input.happened(&InputEvent::ToggleExportSettingBoolean) {
+                        exporter.midi_running_status = !exporter.midi_running_status;
+                    }
+                }
+                ExportSetting::VelocityFloor => {
+                    Self::set_index(|e| &mut e.velocity_floor, input, exporter);
+                }
+                ExportSetting::Channels => {
+                    if input.happened(&InputEvent::ToggleExportSettingBoolean) {
+                        exporter.channels = if exporter.is_mono() { 2 } else { 1 };
+                    }
+                }
+                ExportSetting::Dither => {
+                    if input.happened(&InputEvent::ToggleExportSettingBoolean) {
+                        exporter.dither = !exporter.dither;
+                    }
+                }
                 ExportSetting::MultiFileSuffix => {
                     Self::set_index(
                         |e: &mut Exporter| &mut e.multi_file_suffix.index,
@@ -483,6 +752,13 @@ impl ExportSettingsPanel {
                         exporter,
                     );
                 }
+                ExportSetting::WavBitDepth => {
+                    Self::set_index(
+                        |e: &mut Exporter| &mut e.wav_bit_depth.index,
+                        input,
+                        exporter,
+                    );
+                }
                 _ => (),
             }
         }
@@ -570,6 +846,7 @@ impl Panel for ExportSettingsPanel {
     ) -> Option<Snapshot> {
         // Close this.
         if input.happened(&InputEvent::CloseOpenFile) {
+            tts.enqueue(text.get_ref("OPEN_FILE_PANEL_TTS_CLOSED"));
             return Some(Snapshot::from_io_commands(vec![IOCommand::CloseOpenFile]));
         }
         let export_type = conn.exporter.export_type.get();
@@ -578,6 +855,7 @@ impl Panel for ExportSettingsPanel {
                 |e| &mut e.mid_settings,
                 state,
                 &mut self.tooltips,
+                &self.framerates,
                 input,
                 tts,
                 text,
@@ -587,6 +865,7 @@ impl Panel for ExportSettingsPanel {
                 |e| &mut e.mp3_settings,
                 state,
                 &mut self.tooltips,
+                &self.framerates,
                 input,
                 tts,
                 text,
@@ -596,6 +875,7 @@ impl Panel for ExportSettingsPanel {
                 |e| &mut e.ogg_settings,
                 state,
                 &mut self.tooltips,
+                &self.framerates,
                 input,
                 tts,
                 text,
@@ -605,6 +885,7 @@ impl Panel for ExportSettingsPanel {
                 |e| &mut e.flac_settings,
                 state,
                 &mut self.tooltips,
+                &self.framerates,
                 input,
                 tts,
                 text,
@@ -614,6 +895,17 @@ impl Panel for ExportSettingsPanel {
                 |e| &mut e.wav_settings,
                 state,
                 &mut self.tooltips,
+                &self.framerates,
+                input,
+                tts,
+                text,
+                &mut conn.exporter,
+            ),
+            ExportType::Opus => Self::update_settings(
+                |e| &mut e.opus_settings,
+                state,
+                &mut self.tooltips,
+                &self.framerates,
                 input,
                 tts,
                 text,
@@ -644,17 +936,21 @@ impl Panel for ExportSettingsPanel {
             ExportType::Wav => {
                 Self::update_settings_abc123(|e| &mut e.wav_settings, input, &mut conn.exporter)
             }
+            ExportType::Opus => {
+                Self::update_settings_abc123(|e| &mut e.opus_settings, input, &mut conn.exporter)
+            }
         };
         (None, updated)
     }
 
-    fn on_disable_abc123(&mut self, _: &mut State, conn: &mut Conn) {
+    fn on_disable_abc123(&mut self, _: &mut State, conn: &mut Conn, _: &mut TTS, _: &Text) {
         match conn.exporter.export_type.get() {
             ExportType::Mid => Self::disable_abc123(|e| &mut e.mid_settings, &mut conn.exporter),
             ExportType::MP3 => Self::disable_abc123(|e| &mut e.mp3_settings, &mut conn.exporter),
             ExportType::Ogg => Self::disable_abc123(|e| &mut e.ogg_settings, &mut conn.exporter),
             ExportType::Wav => Self::disable_abc123(|e| &mut e.wav_settings, &mut conn.exporter),
             ExportType::Flac => Self::disable_abc123(|e| &mut e.flac_settings, &mut conn.exporter),
+            ExportType::Opus => Self::disable_abc123(|e| &mut e.opus_settings, &mut conn.exporter),
         };
     }
 
@@ -665,6 +961,7 @@ impl Panel for ExportSettingsPanel {
             ExportType::Ogg => Self::allow_abc123(|e| &e.ogg_settings, &conn.exporter),
             ExportType::Wav => Self::allow_abc123(|e| &e.wav_settings, &conn.exporter),
             ExportType::Flac => Self::allow_abc123(|e| &e.flac_settings, &conn.exporter),
+            ExportType::Opus => Self::allow_abc123(|e| &e.opus_settings, &conn.exporter),
         }
     }
 
@@ -0,0 +1,153 @@
+use crate::snapshot::Snapshot;
+
+/// The maximum number of snapshots that can be undone.
+const MAX_UNDOS: usize = 100;
+
+/// The undo-redo history: A single list of snapshots and an index into it.
+#[derive(Default)]
+pub(crate) struct History {
+    /// The snapshots.
+    snapshots: Vec<Snapshot>,
+    /// The snapshots before this index can be undone. The snapshots at and after this index can be redone.
+    index: usize,
+}
+
+impl History {
+    /// Returns the number of snapshots that can be undone.
+    pub(crate) fn get_num_undos(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the number of snapshots that can be redone.
+    pub(crate) fn get_num_redos(&self) -> usize {
+        self.snapshots.len() - self.index
+    }
+
+    /// Returns the total number of snapshots.
+    pub(crate) fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Step back and return the most recent snapshot that hasn't been undone, or None if there is nothing to undo.
+    pub(crate) fn undo(&mut self) -> Option<&Snapshot> {
+        if self.index == 0 {
+            return None;
+        }
+        self.index -= 1;
+        Some(&self.snapshots[self.index])
+    }
+
+    /// Step forward and return the earliest snapshot that has been undone, or None if there is nothing to redo.
+    pub(crate) fn redo(&mut self) -> Option<&Snapshot> {
+        if self.index == self.snapshots.len() {
+            return None;
+        }
+        self.index += 1;
+        Some(&self.snapshots[self.index - 1])
+    }
+
+    /// Remove every snapshot that could be redone.
+    pub(crate) fn clear_redos(&mut self) {
+        self.snapshots.truncate(self.index);
+    }
+
+    /// Insert this snapshot at the current index, without removing any snapshots that could be redone.
+    /// If there are too many snapshots, the oldest snapshot is removed.
+    pub(crate) fn insert(&mut self, snapshot: Snapshot) {
+        self.snapshots.insert(self.index, snapshot);
+        self.index += 1;
+        if self.index > MAX_UNDOS {
+            self.snapshots.remove(0);
+            self.index -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{History, MAX_UNDOS};
+    use crate::snapshot::Snapshot;
+    use common::State;
+    use ini::Ini;
+
+    #[test]
+    fn history_undo_redo() {
+        let mut history = History::default();
+        // There's nothing to undo or redo.
+        assert!(history.undo().is_none());
+        assert!(history.redo().is_none());
+        assert_eq!(history.get_num_undos(), 0);
+        assert_eq!(history.get_num_redos(), 0);
+        let mut state = State::new(&Ini::load_from_file("../data/config.ini").unwrap());
+        for cursor in 1..4 {
+            history.insert(get_snapshot(cursor, &mut state));
+        }
+        assert_eq!(history.get_num_undos(), 3);
+        assert_eq!(history.get_num_redos(), 0);
+        // The index can't go past the end.
+        assert!(history.redo().is_none());
+        // Undo the most recent snapshot.
+        assert_eq!(get_cursor(history.undo()), 3);
+        assert_eq!(get_cursor(history.undo()), 2);
+        assert_eq!(history.get_num_undos(), 1);
+        assert_eq!(history.get_num_redos(), 2);
+        // Redo, then undo the same snapshot again.
+        assert_eq!(get_cursor(history.redo()), 2);
+        assert_eq!(get_cursor(history.undo()), 2);
+        // The index can't go past the start.
+        assert_eq!(get_cursor(history.undo()), 1);
+        assert!(history.undo().is_none());
+        assert_eq!(history.get_num_undos(), 0);
+        assert_eq!(history.get_num_redos(), 3);
+        // Redo everything.
+        for cursor in 1..4 {
+            assert_eq!(get_cursor(history.redo()), cursor);
+        }
+        assert!(history.redo().is_none());
+        assert_eq!(history.get_num_redos(), 0);
+    }
+
+    #[test]
+    fn history_insert() {
+        let mut history = History::default();
+        let mut state = State::new(&Ini::load_from_file("../data/config.ini").unwrap());
+        for cursor in 1..4 {
+            history.insert(get_snapshot(cursor, &mut state));
+        }
+        history.undo();
+        history.undo();
+        // Insert a snapshot in the middle of the history without removing the redos.
+        history.insert(get_snapshot(4, &mut state));
+        assert_eq!(history.len(), 4);
+        assert_eq!(history.get_num_undos(), 2);
+        assert_eq!(get_cursor(history.redo()), 2);
+        history.undo();
+        // Remove the redos, then insert a snapshot.
+        history.clear_redos();
+        assert_eq!(history.get_num_redos(), 0);
+        history.insert(get_snapshot(5, &mut state));
+        assert_eq!(history.len(), 3);
+        assert_eq!(get_cursor(history.undo()), 5);
+        assert_eq!(get_cursor(history.undo()), 4);
+        assert_eq!(get_cursor(history.undo()), 1);
+        // The oldest snapshots are removed.
+        let mut history = History::default();
+        for cursor in 0..MAX_UNDOS as u64 + 2 {
+            history.insert(get_snapshot(cursor, &mut state));
+        }
+        assert_eq!(history.len(), MAX_UNDOS);
+        assert_eq!(history.get_num_undos(), MAX_UNDOS);
+        while history.get_num_undos() > 1 {
+            history.undo();
+        }
+        assert_eq!(get_cursor(history.undo()), 2);
+    }
+
+    fn get_snapshot(cursor: u64, state: &mut State) -> Snapshot {
+        Snapshot::from_state(|s| s.time.cursor = cursor, state)
+    }
+
+    fn get_cursor(snapshot: Option<&Snapshot>) -> u64 {
+        snapshot.unwrap().to_state.as_ref().unwrap().time.cursor
+    }
+}
@@ -1,5 +1,5 @@
 use audio::{Command, Conn};
-use common::{MidiTrack, Music, Note, Paths, State, U64orF32};
+use common::{MidiTrack, Music, Paths, State, U64orF32};
 use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
 use std::fs::read;
 use std::path::Path;
@@ -69,12 +69,12 @@ pub(crate) fn import(path: &Path, state: &mut State, conn: &mut Conn) {
                                 .find(|(_, n)| n.0 == key)
                                 .unwrap();
                             // Add a note.
-                            track.notes.push(Note {
-                                note: note_on.0.as_int(),
-                                velocity: u8::max(vel.as_int(), note_on.1.as_int()),
-                                start: note_on.2,
-                                end: time,
-                            });
+                            track.add_note(
+                                note_on.0.as_int(),
+                                u8::max(vel.as_int(), note_on.1.as_int()),
+                                note_on.2,
+                                time,
+                            );
                             // Remove the note-on event.
                             note_ons.remove(index);
                         }
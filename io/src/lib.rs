@@ -1,6 +1,6 @@
 //! This crate handles essentially all of Cacophony's functionality except the rendering (see the `render` crate).
 //!
-//! The only public struct is `IO`.
+//! The only public struct is `IO`, apart from `RealClock`, the default implementation of the `Clock` trait.
 //!
 //! Per frame, `IO` listens for user input via an `Input` (see the `input` crate), and then does any of the following:
 //!
@@ -9,22 +9,32 @@
 //! - Send an internal `IOCommand` to itself.
 //! - Play text-to-speech audio (see the `text` crate).
 //!
-//! Certain operations will create a copy of the current `State` which will be added to an undo stack.
-//! Undoing an action reverts the app to that state, pops it from the undo stack, and pushes it to the redo stack.
+//! Certain operations will create a copy of the current `State` which will be added to the history.
+//! The history is a single list of snapshots and an index into it. Undoing an action reverts the app to the state before the snapshot at the index and moves the index back; redoing moves it forward again.
+//! A new action removes every snapshot after the index.
 //!
 //! `IO` divides input listening into discrete panels, e.g. the music panel and the tracks panel.
 //! Each panel implements the `Panel` trait.
+//!
+//! Time-dependent logic should read the time from `IO`'s `Clock`, which is shared with the panels that need it, rather than from `std::time` so that it can be tested with a deterministic clock.
 
 use audio::export::ExportState;
+use audio::exporter::Exporter;
+use audio::play_state::PlayState;
 use audio::Conn;
+use common::config::{parse, parse_bool};
 use common::{InputState, Music, PanelType, Paths, PathsState, SelectMode, State};
 use edit::edit_file;
 use hashbrown::HashMap;
 use ini::Ini;
 use input::{Input, InputEvent};
 use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
 use text::{Enqueable, Text, Tooltips, TtsString, TTS};
+mod clock;
 mod export_panel;
+mod history;
 mod import_midi;
 mod io_command;
 mod music_panel;
@@ -32,19 +42,23 @@ mod panel;
 mod piano_roll;
 mod save;
 mod snapshot;
+mod stop_position;
 mod tracks_panel;
 use io_command::IOCommand;
 use io_command::IOCommands;
 use music_panel::MusicPanel;
 mod open_file_panel;
+pub use clock::{Clock, RealClock};
 use common::open_file::{FileAndDirectory, OpenFileType};
 use export_panel::ExportPanel;
 use export_settings_panel::ExportSettingsPanel;
+use history::History;
 use open_file_panel::OpenFilePanel;
 use panel::Panel;
 use piano_roll::PianoRollPanel;
 use save::Save;
-use snapshot::Snapshot;
+use snapshot::{get_state_signature, Snapshot};
+use stop_position::StopPosition;
 use tracks_panel::TracksPanel;
 mod abc123;
 mod export_settings_panel;
@@ -54,8 +68,14 @@ mod links_panel;
 mod popup;
 use links_panel::LinksPanel;
 
-/// The maximum size of the undo stack.
-const MAX_UNDOS: usize = 100;
+/// These events request text-to-speech.
+const MANUAL_TTS_EVENTS: [InputEvent; 5] = [
+    InputEvent::StatusTTS,
+    InputEvent::InputTTS,
+    InputEvent::AppTTS,
+    InputEvent::FileTTS,
+    InputEvent::HistoryStatusTTS,
+];
 
 /// Parse user input and apply it to the application's various states as needed:
 ///
@@ -64,12 +84,12 @@ const MAX_UNDOS: usize = 100;
 /// - Modify the `PathsState`.
 /// - Modify the `Conn`.
 pub struct IO {
-    /// A stack of snapshots that can be popped to undo an action.
-    undo: Vec<Snapshot>,
-    /// A stack of snapshots that can be popped to redo an action.
-    redo: Vec<Snapshot>,
+    /// The undo-redo history.
+    history: History,
     /// Top-level text-to-speech lookups.
     tts: HashMap<InputEvent, Vec<TtsString>>,
+    /// The tooltips handler.
+    tooltips: Tooltips,
     /// The music panel.
     music_panel: MusicPanel,
     /// The tracks panel.
@@ -90,6 +110,46 @@ pub struct IO {
     pre_export_panels: Vec<PanelType>,
     /// The index of the focused panel prior to exporting audio.
     pre_export_focus: usize,
+    /// If true, arm the track when the piano roll panel gains focus.
+    auto_arm: bool,
+    /// If true, disarm the track when the piano roll panel loses focus.
+    auto_disarm: bool,
+    /// The app state and exporter when alphanumeric input was enabled.
+    abc123_start: Option<(State, Exporter)>,
+    /// If true, closing a panel during alphanumeric input keeps the typed text. If false, it restores `abc123_start`.
+    close_keeps_text: bool,
+    /// If true, save the project before exporting if there are unsaved changes.
+    save_before_export: bool,
+    /// If true, cycling past the last panel focuses the first panel, and vice versa.
+    wrap_panels: bool,
+    /// Where the playback time goes after the user stops the music.
+    stop_position: StopPosition,
+    /// The time in PPQ at which playback began.
+    playback_begin: u64,
+    /// If true, an action that doesn't change the state that was just redone won't clear the redo stack.
+    keep_redo_on_repeat: bool,
+    /// The signature of the state after the most recent redo, if nothing has happened since then.
+    redone: Option<u64>,
+    /// If true, stepping through the history plays the music from the cursor.
+    audition_history: bool,
+    /// Time-dependent logic reads the time from this clock.
+    clock: Rc<dyn Clock>,
+    /// Increase or decrease the monitor gain by this many dB.
+    monitor_gain_step: i8,
+    /// The number of backups of a save file to keep when it is overwritten.
+    backups: usize,
+    /// If true, read the panel status whenever the focused panel or the selected track changes.
+    status_on_focus: bool,
+    /// If true, say that the project is empty instead of playing or exporting music without any notes.
+    empty_project_tts: bool,
+    /// The focused panel and the selected track on the previous frame.
+    focus_context: Option<(PanelType, Option<usize>)>,
+    /// If true, the focus changed and the status hasn't been read yet.
+    focus_status_pending: bool,
+    /// If true, the most recent status readout was automatic and can be interrupted by the next one.
+    focus_status_spoken: bool,
+    /// The most recent edit that modified the state, if any. See `Snapshot.action`.
+    last_action: Option<InputEvent>,
 }
 
 impl IO {
@@ -125,7 +185,12 @@ impl IO {
             tooltips
                 .get_tooltip(
                     "APP_TTS_4",
-                    &[InputEvent::Undo, InputEvent::Redo],
+                    &[
+                        InputEvent::Undo,
+                        InputEvent::Redo,
+                        InputEvent::HistoryBack,
+                        InputEvent::HistoryForward,
+                    ],
                     input,
                     text,
                 )
@@ -136,6 +201,24 @@ impl IO {
             tooltips
                 .get_tooltip("APP_TTS_6", &[InputEvent::EnableLinksPanel], input, text)
                 .clone(),
+            tooltips
+                .get_tooltip("APP_TTS_7", &[InputEvent::HistoryStatusTTS], input, text)
+                .clone(),
+            tooltips
+                .get_tooltip(
+                    "APP_TTS_8",
+                    &[
+                        InputEvent::DecreaseMonitorGain,
+                        InputEvent::IncreaseMonitorGain,
+                        InputEvent::ToggleMonoMonitor,
+                    ],
+                    input,
+                    text,
+                )
+                .clone(),
+            tooltips
+                .get_tooltip("APP_TTS_9", &[InputEvent::RepeatLastAction], input, text)
+                .clone(),
         ];
         tts.insert(InputEvent::AppTTS, app_tts);
         // File TTS.
@@ -166,15 +249,40 @@ impl IO {
         ];
         tts.insert(InputEvent::FileTTS, file_tts);
         let music_panel = MusicPanel::default();
-        let tracks_panel = TracksPanel::default();
-        let open_file_panel = OpenFilePanel::default();
+        let clock: Rc<dyn Clock> = Rc::new(RealClock);
+        let tracks_panel = TracksPanel::new(config, Rc::clone(&clock));
+        let open_file_panel = OpenFilePanel::new(config);
         let piano_roll_panel = PianoRollPanel::new(&input_state.beat.get_u(), config);
         let export_panel = ExportPanel::default();
-        let export_settings_panel = ExportSettingsPanel::default();
+        let export_settings_panel = ExportSettingsPanel::new(config);
         let quit_panel = QuitPanel::default();
         let links_panel = LinksPanel::default();
+        let section = config.section(Some("PIANO_ROLL")).unwrap();
+        let auto_arm = parse_bool(section, "auto_arm");
+        let auto_disarm = parse_bool(section, "auto_disarm");
+        let wrap_panels = parse_bool(section, "wrap_panels");
+        let keep_redo_on_repeat = parse_bool(section, "keep_redo_on_repeat");
+        let audition_history = parse_bool(section, "audition_history");
+        let stop_position = parse(section, "stop_position");
+        let close_keeps_text = parse_bool(
+            config.section(Some("ALPHANUMERIC_INPUT")).unwrap(),
+            "close_keeps_text",
+        );
+        let monitor_gain_step = parse(config.section(Some("AUDIO")).unwrap(), "monitor_gain_step");
+        let save_before_export =
+            parse_bool(config.section(Some("SAVE")).unwrap(), "save_before_export");
+        let backups = parse(config.section(Some("SAVE")).unwrap(), "backups");
+        let status_on_focus = parse_bool(
+            config.section(Some("TEXT_TO_SPEECH")).unwrap(),
+            "status_on_focus",
+        );
+        let empty_project_tts = parse_bool(
+            config.section(Some("TEXT_TO_SPEECH")).unwrap(),
+            "empty_project_tts",
+        );
         Self {
             tts,
+            tooltips,
             music_panel,
             tracks_panel,
             open_file_panel,
@@ -183,10 +291,29 @@ impl IO {
             export_settings_panel,
             quit_panel,
             links_panel,
-            redo: vec![],
-            undo: vec![],
+            history: History::default(),
             pre_export_panels: vec![],
             pre_export_focus: 0,
+            auto_arm,
+            auto_disarm,
+            abc123_start: None,
+            close_keeps_text,
+            save_before_export,
+            wrap_panels,
+            stop_position,
+            keep_redo_on_repeat,
+            redone: None,
+            audition_history,
+            clock,
+            monitor_gain_step,
+            backups,
+            status_on_focus,
+            empty_project_tts,
+            focus_context: None,
+            focus_status_pending: false,
+            focus_status_spoken: false,
+            playback_begin: 0,
+            last_action: None,
         }
     }
 
@@ -204,7 +331,7 @@ impl IO {
         &mut self,
         state: &mut State,
         conn: &mut Conn,
-        input: &Input,
+        input: &mut Input,
         tts: &mut TTS,
         text: &mut Text,
         paths_state: &mut PathsState,
@@ -216,31 +343,87 @@ impl IO {
             }
             // Quit.
             else {
+                self.piano_roll_panel.save_copied_notes();
                 return true;
             }
         }
 
-        // Don't do anything while exporting.
+        // Release any previewed note.
+        conn.update_preview(self.clock.now());
+        // Loop playback.
+        conn.update_loop(state);
+        // Hear only the selected track while the audition key is held.
+        conn.set_audition(state, input.is_held(&InputEvent::AuditionTrack));
+        // Say whether the post-export command succeeded.
+        if let Some(success) = conn.take_post_export_result() {
+            tts.enqueue(text.get_ref(if success {
+                "POST_EXPORT_TTS_SUCCESS"
+            } else {
+                "POST_EXPORT_TTS_FAILURE"
+            }));
+        }
+
+        // A track can only be cleared while the tracks panel has focus.
+        if state.panels[state.focus.get()] != PanelType::Tracks {
+            self.tracks_panel.cancel_clear_track();
+        }
+
+        // Don't do anything while exporting except update the export panel, which can cancel the export.
         if conn.exporting() {
+            self.export_panel
+                .update(state, conn, input, tts, text, paths_state);
             return false;
         }
 
         // Alphanumeric input.
         if state.input.alphanumeric_input {
+            // Remember the values from before the user started typing.
+            if self.abc123_start.is_none() {
+                self.abc123_start = Some((state.clone(), conn.exporter.clone()));
+            }
+            let close_keeps_text = self.close_keeps_text;
+
             // Get the focused panel.
             let panel = self.get_panel(&state.panels[state.focus.get()]);
 
             // Toggle off alphanumeric input.
             if panel.allow_alphanumeric_input(state, conn) {
-                if input.happened(&InputEvent::ToggleAlphanumericInput) {
+                // Stop typing without closing the panel.
+                if input.happened(&InputEvent::CloseOpenFile) {
+                    let s0 = state.clone();
+                    let key = if close_keeps_text {
+                        state.input.alphanumeric_input = false;
+                        panel.on_disable_abc123(state, conn, tts, text);
+                        "ABC123_TTS_CLOSE_KEEP"
+                    } else {
+                        // Restore the values from before the user started typing.
+                        if let Some((s, exporter)) = self.abc123_start.take() {
+                            *state = s;
+                            conn.exporter = exporter;
+                        }
+                        state.input.alphanumeric_input = false;
+                        "ABC123_TTS_CLOSE_DISCARD"
+                    };
+                    self.abc123_start = None;
+                    tts.enqueue(self.tooltips.get_tooltip(
+                        key,
+                        &[InputEvent::CloseOpenFile],
+                        input,
+                        text,
+                    ));
+                    let snapshot = Some(Snapshot::from_states(s0, state));
+                    self.apply_snapshot(snapshot, state, conn, tts, text, paths_state);
+                    return false;
+                } else if input.happened(&InputEvent::ToggleAlphanumericInput) {
                     let s0 = state.clone();
                     state.input.alphanumeric_input = false;
                     // Do something on disable.
-                    panel.on_disable_abc123(state, conn);
+                    panel.on_disable_abc123(state, conn, tts, text);
+                    self.abc123_start = None;
                     // There is always a snapshot (because we toggled off alphanumeric input).
                     let snapshot = Some(Snapshot::from_states(s0, state));
                     // Apply the snapshot.
-                    self.apply_snapshot(snapshot, state, conn, paths_state);
+                    self.apply_snapshot(snapshot, state, conn, tts, text, paths_state);
                     return false;
                 }
                 // Try to do alphanumeric input.
@@ -248,7 +431,7 @@ impl IO {
                     let (snapshot, updated) = panel.update_abc123(state, input, conn);
                     // We applied alphanumeric input.
                     if updated {
-                        self.apply_snapshot(snapshot, state, conn, paths_state);
+                        self.apply_snapshot(snapshot, state, conn, tts, text, paths_state);
                         return false;
                     }
                 }
@@ -256,6 +439,7 @@ impl IO {
         }
         // Apply alphanumeric input.
         else {
+            self.abc123_start = None;
             let panel = self.get_panel(&state.panels[state.focus.get()]);
             if panel.allow_alphanumeric_input(state, conn)
                 && input.happened(&InputEvent::ToggleAlphanumericInput)
@@ -265,7 +449,7 @@ impl IO {
                     true,
                     state,
                 ));
-                self.apply_snapshot(snapshot, state, conn, paths_state);
+                self.apply_snapshot(snapshot, state, conn, tts, text, paths_state);
                 return false;
             } else if let Some(track) = state.music.get_selected_track() {
                 // Play notes.
@@ -284,6 +468,8 @@ impl IO {
         if input.happened(&InputEvent::NewFile) {
             paths_state.saves.filename = None;
             state.music = Music::default();
+            conn.set_monitor_gain(0);
+            conn.set_mono_monitor(false);
         }
         // Open file.
         else if input.happened(&InputEvent::OpenFile) {
@@ -294,8 +480,7 @@ impl IO {
             match &paths_state.saves.try_get_path() {
                 // Save to the existing path,
                 Some(path) => {
-                    Save::write(&path.with_extension("cac"), state, conn, paths_state);
-                    state.unsaved_changes = false;
+                    IO::save(path, state, conn, tts, text, paths_state, self.backups);
                 }
                 // Set a new path.
                 None => self.open_file_panel.write_save(state, paths_state),
@@ -308,8 +493,27 @@ impl IO {
         // Export.
         else if input.happened(&InputEvent::ExportFile) {
             let export_state = *conn.export_state.lock();
-            // We aren't exporting already.
-            if export_state == ExportState::NotExporting {
+            // We aren't exporting already, and there is something to export.
+            if export_state == ExportState::NotExporting
+                && (conn.exports_silence() || !self.is_empty_project(state, tts, text))
+            {
+                // Save first.
+                if self.save_before_export && state.unsaved_changes {
+                    match &paths_state.saves.try_get_path() {
+                        Some(path) => {
+                            if !IO::save(path, state, conn, tts, text, paths_state, self.backups) {
+                                return false;
+                            }
+                            tts.enqueue(text.get_ref("SAVE_BEFORE_EXPORT_TTS"));
+                        }
+                        // Ask for a save path instead of exporting.
+                        None => {
+                            self.open_file_panel.write_save(state, paths_state);
+                            tts.enqueue(text.get_ref("SAVE_BEFORE_EXPORT_NO_PATH_TTS"));
+                            return false;
+                        }
+                    }
+                }
                 self.pre_export_focus = state.focus.get();
                 self.pre_export_panels = state.panels.clone();
                 self.open_file_panel.export(state, paths_state, conn)
@@ -329,49 +533,24 @@ impl IO {
         }
         // Undo.
         else if input.happened(&InputEvent::Undo) {
-            if let Some(undo) = self.undo.pop() {
-                // Get the redo state.
-                let redo = Snapshot::from_snapshot(&undo);
-                // Assign the undo state to the previous state.
-                if let Some(s1) = undo.from_state {
-                    *state = s1;
-                }
-                // Send the commands.
-                if let Some(commands) = undo.from_commands {
-                    conn.do_commands(&commands);
-                }
-                // Push to the redo stack.
-                self.redo.push(redo);
-                state.unsaved_changes = true;
-            }
+            self.undo(state, conn);
         // Redo.
         } else if input.happened(&InputEvent::Redo) {
-            if let Some(redo) = self.redo.pop() {
-                let undo = Snapshot::from_snapshot(&redo);
-                // Assign the redo state to the current state.
-                if let Some(s1) = redo.from_state {
-                    *state = s1;
-                }
-                // Send the commands.
-                if let Some(commands) = redo.from_commands {
-                    conn.do_commands(&commands);
-                }
-                // Push to the undo stack.
-                self.undo.push(undo);
-                state.unsaved_changes = true;
-            }
+            self.redo(state, conn);
+        }
+        // Step through the history.
+        else if input.happened(&InputEvent::HistoryBack) {
+            let stepped = self.undo(state, conn);
+            self.step_history(stepped, state, conn, tts, text);
+        } else if input.happened(&InputEvent::HistoryForward) {
+            let stepped = self.redo(state, conn);
+            self.step_history(stepped, state, conn, tts, text);
         }
         // Cycle panels.
         else if input.happened(&InputEvent::NextPanel) {
-            let s0 = state.clone();
-            state.focus.increment(true);
-            state.unsaved_changes = true;
-            self.undo.push(Snapshot::from_states(s0, state));
+            self.cycle_panels(state, true, tts, text);
         } else if input.happened(&InputEvent::PreviousPanel) {
-            let s0 = state.clone();
-            state.focus.increment(false);
-            state.unsaved_changes = true;
-            self.undo.push(Snapshot::from_states(s0, state));
+            self.cycle_panels(state, false, tts, text);
         }
 
         // App-level TTS.
@@ -385,21 +564,68 @@ impl IO {
         if input.happened(&InputEvent::StopTTS)
             || input.happened(&InputEvent::StatusTTS)
             || input.happened(&InputEvent::InputTTS)
+            || input.happened(&InputEvent::HistoryStatusTTS)
         {
             tts.stop();
         }
+        // Say how much history there is.
+        if input.happened(&InputEvent::HistoryStatusTTS) {
+            tts.enqueue(self.get_history_tts(text));
+        }
+        // Set the monitor gain.
+        if input.happened(&InputEvent::DecreaseMonitorGain) {
+            self.set_monitor_gain(false, conn, tts, text);
+        } else if input.happened(&InputEvent::IncreaseMonitorGain) {
+            self.set_monitor_gain(true, conn, tts, text);
+        }
+        // Toggle mono monitoring.
+        if input.happened(&InputEvent::ToggleMonoMonitor) {
+            conn.set_mono_monitor(!conn.get_mono_monitor());
+            tts.stop();
+            tts.enqueue(text.get_ref(if conn.get_mono_monitor() {
+                "MONO_MONITOR_TTS_ON"
+            } else {
+                "MONO_MONITOR_TTS_OFF"
+            }));
+        }
         // Links.
         if input.happened(&InputEvent::EnableLinksPanel) {
             self.links_panel.enable(state);
             return false;
         }
+        // Read the status of a newly focused panel or track.
+        if self.status_on_focus {
+            self.update_status_on_focus(state, input, tts);
+        }
+        // Repeat the last action.
+        if input.happened(&InputEvent::RepeatLastAction) {
+            match self.last_action {
+                Some(event) => {
+                    tts.enqueue(self.tooltips.get_tooltip(
+                        "REPEAT_LAST_ACTION_TTS",
+                        &[event],
+                        input,
+                        text,
+                    ));
+                    input.set_event(event);
+                }
+                None => tts.enqueue(text.get_ref("REPEAT_LAST_ACTION_TTS_NONE")),
+            }
+        }
         // Get the focused panel.
         let panel = self.get_panel(&state.panels[state.focus.get()]);
         // Update the focuses panel and potentially get a screenshot.
         let snapshot = panel.update(state, conn, input, tts, text, paths_state);
-        let (applied, need_to_quit) = self.apply_snapshot(snapshot, state, conn, paths_state);
+        let action = snapshot.as_ref().and_then(|s| s.action);
+        let (applied, need_to_quit) =
+            self.apply_snapshot(snapshot, state, conn, tts, text, paths_state);
+        // Remember the edit so that it can be repeated.
+        if applied && action.is_some() {
+            self.last_action = action;
+        }
         // Quit while we're ahead.
         if need_to_quit {
+            self.piano_roll_panel.save_copied_notes();
             return true;
         }
         // Stop doing stuff here but don't quit.
@@ -409,13 +635,93 @@ impl IO {
         // Get the focused panel.
         let panel = self.get_panel(&state.panels[state.focus.get()]);
         // Play music.
-        if panel.allow_play_music() && input.happened(&InputEvent::PlayStop) {
-            conn.set_music(state);
+        if panel.allow_play_music() {
+            // There is nothing to play.
+            if (input.happened(&InputEvent::PlayStop)
+                || input.happened(&InputEvent::PlayFromCursor))
+                && *conn.play_state.lock() == PlayState::NotPlaying
+                && self.is_empty_project(state, tts, text)
+            {
+                return false;
+            }
+            if input.happened(&InputEvent::PlayStop) {
+                let stop = conn.playback_position_ppq(&state.time);
+                conn.set_music(state);
+                match stop {
+                    Some(stop) => self.set_stop_position(state, stop, tts, text),
+                    None => self.playback_begin = state.time.playback,
+                }
+            }
+            // Play music from the cursor.
+            else if input.happened(&InputEvent::PlayFromCursor) {
+                let stop = conn.playback_position_ppq(&state.time);
+                if *conn.play_state.lock() == PlayState::NotPlaying {
+                    tts.enqueue(text.get_with_values(
+                        "PLAY_FROM_CURSOR_TTS",
+                        &[&text.get_position_tts(state.time.cursor, &state.time)],
+                    ));
+                }
+                conn.set_music_from(state, state.time.cursor);
+                match stop {
+                    Some(stop) => self.set_stop_position(state, stop, tts, text),
+                    None => self.playback_begin = state.time.cursor,
+                }
+            }
         }
         // We're not done yet.
         false
     }
 
+    /// If the focused panel or the selected track changed, read the status of the focused panel on the next frame without any other input.
+    /// A manual text-to-speech request cancels the readout. Each automatic readout interrupts the previous automatic readout, but not other speech.
+    fn update_status_on_focus(&mut self, state: &State, input: &mut Input, tts: &mut TTS) {
+        let context = (state.panels[state.focus.get()], state.music.selected);
+        if let Some(focus_context) = self.focus_context {
+            if focus_context != context {
+                self.focus_status_pending = true;
+            }
+        }
+        self.focus_context = Some(context);
+        if MANUAL_TTS_EVENTS.iter().any(|e| input.happened(e)) {
+            self.focus_status_pending = false;
+            self.focus_status_spoken = false;
+        } else if self.focus_status_pending && !input.has_events() {
+            if self.focus_status_spoken {
+                tts.stop();
+            }
+            // The focused panel will read its status.
+            input.set_event(InputEvent::StatusTTS);
+            self.focus_status_pending = false;
+            self.focus_status_spoken = true;
+        }
+    }
+
+    /// Returns true if `self.empty_project_tts` is true and there aren't any notes, in which case say so.
+    /// Tracks that have notes but are muted don't count as empty.
+    fn is_empty_project(&self, state: &State, tts: &mut TTS, text: &Text) -> bool {
+        if self.empty_project_tts && state.music.is_empty() {
+            tts.stop();
+            tts.enqueue(text.get_ref("EMPTY_PROJECT_TTS"));
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Set the playback time after the music stops and say where it is.
+    ///
+    /// - `state` The state of the app.
+    /// - `stop` The time in PPQ at which the music stopped.
+    /// - `tts` Text-to-speech.
+    /// - `text` The text.
+    fn set_stop_position(&self, state: &mut State, stop: u64, tts: &mut TTS, text: &Text) {
+        state.time.playback = self.stop_position.get_playback(self.playback_begin, stop);
+        tts.enqueue(text.get_with_values(
+            "STOP_TTS",
+            &[&text.get_position_tts(state.time.playback, &state.time)],
+        ));
+    }
+
     /// Open a save file from a path.
     pub fn load_save(
         &self,
@@ -429,6 +735,189 @@ impl IO {
         paths_state.saves = FileAndDirectory::new_path(save_path.to_path_buf());
     }
 
+    /// Save to an existing save path.
+    ///
+    /// - `backups` The number of backups of the save file to keep.
+    ///
+    /// Returns true if the file was saved.
+    fn save(
+        path: &Path,
+        state: &mut State,
+        conn: &Conn,
+        tts: &mut TTS,
+        text: &Text,
+        paths_state: &PathsState,
+        backups: usize,
+    ) -> bool {
+        match Save::validate(state, conn, paths_state) {
+            Ok(()) => {
+                Save::write(
+                    &path.with_extension("cac"),
+                    state,
+                    conn,
+                    paths_state,
+                    backups,
+                );
+                state.unsaved_changes = false;
+                true
+            }
+            Err(error) => {
+                tts.enqueue(text.get_with_values("SAVE_ERROR_TTS", &[&error.to_string()]));
+                false
+            }
+        }
+    }
+
+    /// Replace the clock that time-dependent logic reads from. By default, this is a `RealClock`.
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.tracks_panel.set_clock(Rc::clone(&clock));
+        self.clock = clock;
+    }
+
+    /// Returns the current time according to the clock.
+    pub fn get_time(&self) -> Duration {
+        self.clock.now()
+    }
+
+    /// Returns the number of snapshots that can be undone.
+    pub fn get_num_undos(&self) -> usize {
+        self.history.get_num_undos()
+    }
+
+    /// Returns the number of snapshots that can be redone.
+    pub fn get_num_redos(&self) -> usize {
+        self.history.get_num_redos()
+    }
+
+    /// Revert the most recent snapshot that hasn't been undone. Returns true if there was something to undo.
+    fn undo(&mut self, state: &mut State, conn: &mut Conn) -> bool {
+        let snapshot = match self.history.undo() {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+        // Assign the undo state to the previous state.
+        if let Some(s1) = &snapshot.from_state {
+            *state = s1.clone();
+        }
+        // Send the commands.
+        if let Some(commands) = &snapshot.from_commands {
+            conn.do_commands(commands);
+        }
+        state.unsaved_changes = true;
+        self.redone = None;
+        true
+    }
+
+    /// Re-apply the earliest snapshot that has been undone. Returns true if there was something to redo.
+    fn redo(&mut self, state: &mut State, conn: &mut Conn) -> bool {
+        let snapshot = match self.history.redo() {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+        // Assign the redo state to the current state.
+        if let Some(s1) = &snapshot.to_state {
+            *state = s1.clone();
+        }
+        // Send the commands.
+        if let Some(commands) = &snapshot.to_commands {
+            conn.do_commands(commands);
+        }
+        state.unsaved_changes = true;
+        if self.keep_redo_on_repeat {
+            self.redone = Some(get_state_signature(state));
+        }
+        true
+    }
+
+    /// Say where we are in the history after stepping back or forward. If `self.audition_history` is true, play the music from the cursor.
+    ///
+    /// - `stepped` If false, we're already at the start or end of the history.
+    fn step_history(
+        &self,
+        stepped: bool,
+        state: &State,
+        conn: &mut Conn,
+        tts: &mut TTS,
+        text: &Text,
+    ) {
+        tts.stop();
+        if !stepped {
+            tts.enqueue(text.get_ref("HISTORY_STEP_TTS_NONE"));
+            return;
+        }
+        tts.enqueue(text.get_with_values(
+            "HISTORY_STEP_TTS",
+            &[
+                &self.history.get_num_undos().to_string(),
+                &self.history.len().to_string(),
+            ],
+        ));
+        if self.audition_history {
+            conn.restart_music_from(state, state.time.cursor);
+        }
+    }
+
+    /// Returns a description of the size of the undo and redo stacks.
+    fn get_history_tts(&self, text: &Text) -> String {
+        text.get_with_values(
+            "HISTORY_STATUS_TTS",
+            &[
+                &self.get_num_undos().to_string(),
+                &self.get_num_redos().to_string(),
+            ],
+        )
+    }
+
+    /// Increment or decrement the monitor gain by `self.monitor_gain_step` and say the new value.
+    fn set_monitor_gain(&self, up: bool, conn: &mut Conn, tts: &mut TTS, text: &Text) {
+        let step = if up {
+            self.monitor_gain_step
+        } else {
+            -self.monitor_gain_step
+        };
+        conn.set_monitor_gain(conn.get_monitor_gain().saturating_add(step));
+        tts.stop();
+        tts.enqueue(
+            text.get_with_values("MONITOR_GAIN_TTS", &[&conn.get_monitor_gain().to_string()]),
+        );
+    }
+
+    /// Focus the next or previous panel.
+    /// If `self.wrap_panels` is false, stop at the first and last panels.
+    fn cycle_panels(&mut self, state: &mut State, up: bool, tts: &mut TTS, text: &Text) {
+        let s0 = state.clone();
+        if self.wrap_panels {
+            state.focus.increment(up);
+            if up && state.focus.get() < s0.focus.get() {
+                tts.enqueue(text.get_ref("WRAP_FIRST_PANEL_TTS"));
+            } else if !up && state.focus.get() > s0.focus.get() {
+                tts.enqueue(text.get_ref("WRAP_LAST_PANEL_TTS"));
+            }
+        } else if !state.focus.increment_no_loop(up) {
+            return;
+        }
+        self.set_auto_arm(&s0, state, tts, text);
+        state.unsaved_changes = true;
+        self.insert_history(Snapshot::from_states(s0, state));
+    }
+
+    /// Arm the track if the piano roll panel just gained focus, or disarm it if the panel just lost focus.
+    /// This only happens when the focus changes, so the user can still arm or disarm the track manually.
+    ///
+    /// - `s0` The state prior to the focus change.
+    /// - `state` The state after the focus change.
+    fn set_auto_arm(&self, s0: &State, state: &mut State, tts: &mut TTS, text: &Text) {
+        let was_focused = s0.panels[s0.focus.get()] == PanelType::PianoRoll;
+        let is_focused = state.panels[state.focus.get()] == PanelType::PianoRoll;
+        if self.auto_arm && !was_focused && is_focused && !state.input.armed {
+            state.input.armed = true;
+            tts.enqueue(text.get_ref("AUTO_ARM_TTS"));
+        } else if self.auto_disarm && was_focused && !is_focused && state.input.armed {
+            state.input.armed = false;
+            tts.enqueue(text.get_ref("AUTO_DISARM_TTS"));
+        }
+    }
+
     fn get_panel(&mut self, panel_type: &PanelType) -> &mut dyn Panel {
         match panel_type {
             PanelType::ExportSettings => &mut self.export_settings_panel,
@@ -453,6 +942,8 @@ impl IO {
         snapshot: Option<Snapshot>,
         state: &mut State,
         conn: &mut Conn,
+        tts: &mut TTS,
+        text: &Text,
         paths_state: &mut PathsState,
     ) -> (bool, bool) {
         // Push an undo state generated by the focused panel.
@@ -479,12 +970,15 @@ impl IO {
                         },
                         // Export.
                         IOCommand::Export => {
-                            self.export_panel.enable(
-                                state,
-                                &self.pre_export_panels,
-                                self.pre_export_focus,
-                            );
-                            conn.start_export(state, paths_state);
+                            if conn.start_export(state, paths_state) {
+                                self.export_panel.enable(
+                                    state,
+                                    &self.pre_export_panels,
+                                    self.pre_export_focus,
+                                );
+                            } else {
+                                tts.enqueue(text.get_ref("EXPORT_PANEL_TTS_NOTHING_TO_EXPORT"));
+                            }
                         }
                         // Close the open-file panel.
                         IOCommand::CloseOpenFile => self.open_file_panel.disable(state),
@@ -504,42 +998,69 @@ impl IO {
         }
     }
 
-    /// Push this `UndoRedoState` to the undo stack and clear the redo stack.
+    /// Add this snapshot to the history and remove every snapshot that could be redone.
+    ///
+    /// If `self.keep_redo_on_repeat` is true and `snapshot` leaves the state exactly as it was after the most recent redo, the redoable snapshots are kept.
+    /// This prevents a stray keypress from losing the redo history. Any other action still clears the redoable snapshots; there is no branching history.
     fn push_undo(&mut self, snapshot: Snapshot) {
-        self.undo.push(snapshot);
-        self.redo.clear();
-        // Remove an undo if there are too many.
-        if self.undo.len() > MAX_UNDOS {
-            self.undo.remove(0);
+        let redone = self.redone.take();
+        if redone.is_none() || redone != snapshot.get_signature() {
+            self.history.clear_redos();
         }
+        // Remember the redone state so that repeated stray keypresses don't clear the redo history either.
+        else {
+            self.redone = redone;
+        }
+        self.insert_history(snapshot);
+    }
+
+    /// Insert this snapshot into the history at the current index, without removing any snapshots that could be redone.
+    fn insert_history(&mut self, snapshot: Snapshot) {
+        self.history.insert(snapshot);
     }
 }
 
 /// Try to select a track, given user input.
 ///
 /// This is here an not in a more obvious location because both `TracksPanel` and `PianoRollPanel` need it.
+///
+/// - `wrap` If true, selecting past the last track selects the first track, and vice versa.
 pub(crate) fn select_track(
     state: &mut State,
     input: &Input,
     events: [InputEvent; 2],
+    wrap: bool,
+    tts: &mut TTS,
+    text: &Text,
 ) -> Option<Snapshot> {
-    if let Some(selected) = state.music.selected {
-        if input.happened(&events[0]) && selected > 0 {
-            let s0 = state.clone();
-            state.music.selected = Some(selected - 1);
-            deselect(state);
-            Some(Snapshot::from_states(s0, state))
-        } else if input.happened(&events[1]) && selected < state.music.midi_tracks.len() - 1 {
-            let s0 = state.clone();
-            state.music.selected = Some(selected + 1);
-            deselect(state);
-            Some(Snapshot::from_states(s0, state))
+    let selected = state.music.selected?;
+    let last = state.music.midi_tracks.len() - 1;
+    let (index, wrap_tts) = if input.happened(&events[0]) {
+        if selected > 0 {
+            (selected - 1, None)
+        } else if wrap && last > 0 {
+            (last, Some("WRAP_LAST_TRACK_TTS"))
+        } else {
+            return None;
+        }
+    } else if input.happened(&events[1]) {
+        if selected < last {
+            (selected + 1, None)
+        } else if wrap && last > 0 {
+            (0, Some("WRAP_FIRST_TRACK_TTS"))
         } else {
-            None
+            return None;
         }
     } else {
-        None
+        return None;
+    };
+    let s0 = state.clone();
+    state.music.selected = Some(index);
+    deselect(state);
+    if let Some(key) = wrap_tts {
+        tts.enqueue(text.get_ref(key));
     }
+    Some(Snapshot::from_states(s0, state))
 }
 
 fn deselect(state: &mut State) {
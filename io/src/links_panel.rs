@@ -103,7 +103,7 @@ impl Panel for LinksPanel {
         false
     }
 
-    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn) {}
+    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn, _: &mut TTS, _: &Text) {}
 
     fn update_abc123(
         &mut self,
@@ -162,7 +162,7 @@ impl Panel for MusicPanel {
         }
     }
 
-    fn on_disable_abc123(&mut self, state: &mut State, conn: &mut Conn) {
+    fn on_disable_abc123(&mut self, state: &mut State, conn: &mut Conn, _: &mut TTS, _: &Text) {
         match state.music_panel_field.get_ref() {
             MusicPanelField::BPM => {
                 on_disable_state(|s| &mut s.time.bpm, state, U64orF32::from(DEFAULT_BPM))
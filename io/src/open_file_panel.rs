@@ -3,20 +3,67 @@ use crate::panel::*;
 use crate::Save;
 use audio::export::ExportType;
 use audio::exporter::Exporter;
+use common::config::{parse, parse_bool};
 use common::open_file::*;
-use common::PanelType;
+use common::{PanelType, Paths, ProjectIssue};
+use ini::Ini;
+use std::fs::{read_to_string, write};
+use std::path::{Path, PathBuf};
 use text::get_file_name_no_ex;
 
 /// Data for an open-file panel.
-#[derive(Default)]
 pub struct OpenFilePanel {
     /// Popup handler.
     popup: Popup,
     /// Tooltips handler.
     tooltips: Tooltips,
+    /// If true, ask for confirmation before overwriting an existing file.
+    confirm_overwrite: bool,
+    /// The path of the project when we started writing a save file. Saving over this file doesn't require confirmation.
+    project_path: Option<PathBuf>,
+    /// The path of an existing file that we're waiting to overwrite.
+    overwrite: Option<PathBuf>,
+    /// If true, the user has typed part of the filename since the panel was enabled or since they last stopped typing.
+    typing: bool,
+    /// The filename before the user started typing.
+    filename0: Option<String>,
+    /// If true, closing the panel while typing keeps the typed filename. If false, it restores `filename0`.
+    close_keeps_text: bool,
+    /// The number of backups of a save file to keep when it is overwritten.
+    backups: usize,
+    /// If there isn't a most recently used export directory, the export panel starts in this directory.
+    /// If None, the export panel starts in the export directory of `PathsState`.
+    default_export_directory: Option<PathBuf>,
 }
 
 impl OpenFilePanel {
+    pub fn new(config: &Ini) -> Self {
+        let section = config.section(Some("SAVE")).unwrap();
+        let confirm_overwrite = parse_bool(section, "confirm_overwrite");
+        let backups = parse(section, "backups");
+        let close_keeps_text = parse_bool(
+            config.section(Some("ALPHANUMERIC_INPUT")).unwrap(),
+            "close_keeps_text",
+        );
+        let default_export_directory = config
+            .section(Some("EXPORT"))
+            .unwrap()
+            .get("export_directory")
+            .map(PathBuf::from);
+        Self {
+            popup: Popup::default(),
+            tooltips: Tooltips::default(),
+            confirm_overwrite,
+            project_path: None,
+            overwrite: None,
+            typing: false,
+            filename0: None,
+            close_keeps_text,
+            backups,
+            default_export_directory,
+        }
+    }
+
     /// Enable the panel.
     fn enable(
         &mut self,
@@ -30,8 +77,11 @@ impl OpenFilePanel {
             panels.push(PanelType::ExportSettings);
         }
         self.popup.enable(state, panels);
+        self.overwrite = None;
         // Set the file type.
         paths_state.open_file_type = open_file_type;
+        self.typing = false;
+        self.filename0 = paths_state.get_filename();
     }
 
     /// Enable the panel for loading SoundFonts.
@@ -52,6 +102,10 @@ impl OpenFilePanel {
 
     /// Enable the panel for setting the save path to be written to.
     pub fn write_save(&mut self, state: &mut State, paths_state: &mut PathsState) {
+        self.project_path = paths_state
+            .saves
+            .try_get_path()
+            .map(|path| path.with_extension("cac"));
         self.enable_as_save(OpenFileType::WriteSave, state, paths_state);
         paths_state
             .children
@@ -62,6 +116,17 @@ impl OpenFilePanel {
     pub fn export(&mut self, state: &mut State, paths_state: &mut PathsState, conn: &Conn) {
         let extension = conn.exporter.export_type.get().into();
         let open_file_type = OpenFileType::Export;
+        // Start in the most recently used export directory, which might be from a previous session.
+        let export_directory = read_to_string(&Paths::get().last_export_directory_path)
+            .ok()
+            .map(|path| PathBuf::from(path.trim()))
+            .filter(|path| path.exists())
+            .or_else(|| self.default_export_directory.clone());
+        if let Some(export_directory) = export_directory {
+            if export_directory.exists() {
+                paths_state.exports.directory = FileOrDirectory::new(&export_directory);
+            }
+        }
         paths_state
             .children
             .set(&paths_state.exports.directory.path, &extension, None);
@@ -76,6 +141,16 @@ impl OpenFilePanel {
         self.enable(OpenFileType::ImportMidi, state, paths_state);
     }
 
+    /// Remember the directory of an export so that the next export starts there, including in the next session.
+    fn set_export_directory(directory: &Path) {
+        if let Err(error) = write(
+            &Paths::get().last_export_directory_path,
+            directory.to_str().unwrap_or_default(),
+        ) {
+            println!("Failed to save the export directory: {}", error);
+        }
+    }
+
     fn get_extension(&self, paths_state: &PathsState, exporter: &Exporter) -> Extension {
         match paths_state.open_file_type {
             OpenFileType::Export => exporter.export_type.get().into(),
@@ -97,6 +172,86 @@ impl OpenFilePanel {
         self.enable(open_file_type, state, paths_state);
     }
 
+    /// Returns true if we can write to `path`.
+    ///
+    /// If `path` already exists, this asks the user to confirm and returns false. Selecting the same path again confirms.
+    fn can_write(&mut self, path: &Path, input: &Input, tts: &mut TTS, text: &Text) -> bool {
+        if !self.confirm_overwrite
+            || !path.exists()
+            || self.project_path.as_deref() == Some(path)
+            || self.overwrite.as_deref() == Some(path)
+        {
+            self.overwrite = None;
+            true
+        } else {
+            self.overwrite = Some(path.to_path_buf());
+            tts.enqueue(self.tooltips.get_tooltip_with_values(
+                "OPEN_FILE_PANEL_TTS_OVERWRITE",
+                &[InputEvent::SelectFile, InputEvent::CloseOpenFile],
+                &[get_file_name_no_ex(path)],
+                input,
+                text,
+            ));
+            false
+        }
+    }
+
+    /// Returns true if the music can be exported. If not, say why.
+    fn can_export(state: &State, conn: &Conn, tts: &mut TTS, text: &Text) -> bool {
+        let issues = state
+            .music
+            .validate(|channel| conn.state.programs.contains_key(&channel));
+        if issues.is_empty() {
+            return true;
+        }
+        let mut s = text.get("OPEN_FILE_PANEL_TTS_CANT_EXPORT");
+        for issue in issues.iter() {
+            s.push(' ');
+            s.push_str(&match issue {
+                ProjectIssue::NoPlayableTracks => {
+                    text.get("OPEN_FILE_PANEL_TTS_NO_PLAYABLE_TRACKS")
+                }
+                ProjectIssue::NoProgram { channel } => {
+                    text.get_with_values("OPEN_FILE_PANEL_TTS_NO_PROGRAM", &[&channel.to_string()])
+                }
+                ProjectIssue::InvalidNote { channel, index } => text.get_with_values(
+                    "OPEN_FILE_PANEL_TTS_INVALID_NOTE",
+                    &[&channel.to_string(), &index.to_string()],
+                ),
+            });
+        }
+        tts.enqueue(s);
+        false
+    }
+
+    /// Handle a close event. This doesn't always close the panel:
+    ///
+    /// 1. If we're waiting to overwrite a file, cancel overwriting.
+    /// 2. If the user is typing a filename, stop typing and keep or restore the filename.
+    /// 3. Otherwise, close the panel.
+    ///
+    /// Returns the text key of what happened.
+    fn close(&mut self, state: &mut State, paths_state: &mut PathsState) -> &'static str {
+        if self.overwrite.is_some() {
+            self.overwrite = None;
+            "OPEN_FILE_PANEL_TTS_OVERWRITE_CANCELLED"
+        } else if self.typing {
+            self.typing = false;
+            if self.close_keeps_text {
+                self.filename0 = paths_state.get_filename();
+                "ABC123_TTS_CLOSE_KEEP"
+            } else {
+                if let Some(filename) = &self.filename0 {
+                    paths_state.set_filename(filename);
+                }
+                "ABC123_TTS_CLOSE_DISCARD"
+            }
+        } else {
+            self.disable(state);
+            "OPEN_FILE_PANEL_TTS_CLOSED"
+        }
+    }
+
     /// Disable this panel.
     pub fn disable(&self, state: &mut State) {
         self.popup.disable(state);
@@ -124,6 +279,8 @@ impl Panel for OpenFilePanel {
                 // Modify the path.
                 if input.modify_filename_abc123(&mut filename) {
                     paths_state.set_filename(&filename);
+                    self.overwrite = None;
+                    self.typing = true;
                     return None;
                 }
             }
@@ -310,41 +467,53 @@ impl Panel for OpenFilePanel {
                 OpenFileType::WriteSave => {
                     // There is a filename.
                     if let Some(filename) = &paths_state.saves.filename {
-                        // Disable the panel.
-                        self.disable(state);
                         // Append the extension.
                         let mut filename = filename.clone();
                         filename.push_str(".cac");
+                        let path = paths_state.saves.directory.path.join(filename);
+                        // Don't overwrite a file without confirmation.
+                        if !self.can_write(&path, input, tts, text) {
+                            return None;
+                        }
+                        // Make sure that the state can be saved.
+                        if let Err(error) = Save::validate(state, conn, paths_state) {
+                            tts.enqueue(
+                                text.get_with_values("SAVE_ERROR_TTS", &[&error.to_string()]),
+                            );
+                            return None;
+                        }
+                        // Disable the panel.
+                        self.disable(state);
                         state.unsaved_changes = false;
                         // Write.
-                        Save::write(
-                            &paths_state.saves.directory.path.join(filename),
-                            state,
-                            conn,
-                            paths_state,
-                        );
+                        Save::write(&path, state, conn, paths_state, self.backups);
                     }
                 }
                 // Write an export file.
                 OpenFileType::Export => {
                     // There is a filename.
                     if let Some(filename) = &paths_state.exports.filename {
-                        // Disable the panel.
-                        self.disable(state);
                         // Append the extension.
                         let mut filename = filename.clone();
                         filename.push_str(
                             <ExportType as Into<Extension>>::into(conn.exporter.export_type.get())
                                 .to_str(true),
                         );
+                        let path = paths_state.exports.directory.path.join(filename);
+                        // Don't export music that would result in an empty or incorrect file.
+                        if !OpenFilePanel::can_export(state, conn, tts, text) {
+                            return None;
+                        }
+                        // Don't overwrite a file without confirmation.
+                        if !self.can_write(&path, input, tts, text) {
+                            return None;
+                        }
+                        // Disable the panel.
+                        self.disable(state);
+                        OpenFilePanel::set_export_directory(&paths_state.exports.directory.path);
                         // Export to a .mid file.
                         if conn.exporter.export_type.get() == ExportType::Mid {
-                            conn.exporter.mid(
-                                &paths_state.exports.directory.path.join(filename),
-                                &state.music,
-                                &state.time,
-                                &conn.state,
-                            );
+                            conn.export_mid(&path, &state.music, &state.time);
                         }
                         // Export an audio file.
                         else {
@@ -364,12 +533,16 @@ impl Panel for OpenFilePanel {
         }
         // Close this.
         else if input.happened(&InputEvent::CloseOpenFile) {
-            self.disable(state);
+            let key = self.close(state, paths_state);
+            tts.enqueue(
+                self.tooltips
+                    .get_tooltip(key, &[InputEvent::CloseOpenFile], input, text),
+            );
         }
         None
     }
 
-    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn) {}
+    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn, _: &mut TTS, _: &Text) {}
 
     fn update_abc123(
         &mut self,
@@ -389,3 +562,44 @@ impl Panel for OpenFilePanel {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::OpenFilePanel;
+    use common::open_file::OpenFileType;
+    use common::{PanelType, PathsState, State};
+    use ini::Ini;
+
+    #[test]
+    fn open_file_close() {
+        let config = Ini::load_from_file("../data/config.ini").unwrap();
+        let mut state = State::new(&config);
+        let mut paths_state = PathsState::default();
+        let mut panel = OpenFilePanel::new(&config);
+        for close_keeps_text in [true, false] {
+            panel.close_keeps_text = close_keeps_text;
+            panel.enable(OpenFileType::WriteSave, &mut state, &mut paths_state);
+            assert_eq!(state.panels, vec![PanelType::OpenFile]);
+            let filename0 = paths_state.get_filename();
+            // Type a filename.
+            paths_state.set_filename("my_music");
+            panel.typing = true;
+            // The first close stops typing.
+            let key = panel.close(&mut state, &mut paths_state);
+            assert_eq!(state.panels, vec![PanelType::OpenFile]);
+            if close_keeps_text {
+                assert_eq!(key, "ABC123_TTS_CLOSE_KEEP");
+                assert_eq!(paths_state.get_filename(), Some("my_music".to_string()));
+            } else {
+                assert_eq!(key, "ABC123_TTS_CLOSE_DISCARD");
+                assert_eq!(paths_state.get_filename(), filename0);
+            }
+            // The second close closes the panel.
+            assert_eq!(
+                panel.close(&mut state, &mut paths_state),
+                "OPEN_FILE_PANEL_TTS_CLOSED"
+            );
+            assert!(!state.panels.contains(&PanelType::OpenFile));
+        }
+    }
+}
@@ -46,7 +46,9 @@ pub(crate) trait Panel {
     ///
     /// - `state` The state of the app.
     /// - `conn` The audio connection.
-    fn on_disable_abc123(&mut self, state: &mut State, conn: &mut Conn);
+    /// - `tts` Text-to-speech.
+    /// - `text` The text.
+    fn on_disable_abc123(&mut self, state: &mut State, conn: &mut Conn, tts: &mut TTS, text: &Text);
 
     /// If true, allow the user to toggle alphanumeric input.
     ///
@@ -3,7 +3,7 @@ use super::{
     EditModeDeltas, PianoRollSubPanel,
 };
 use crate::panel::*;
-use common::{MAX_NOTE, MAX_VOLUME, MIN_NOTE};
+use common::{Articulation, MAX_NOTE, MAX_VOLUME, MIN_NOTE};
 use ini::Ini;
 
 /// Edit selected notes.
@@ -28,8 +28,8 @@ impl Panel for Edit {
         state: &mut State,
         _: &mut Conn,
         input: &Input,
-        _: &mut TTS,
-        _: &Text,
+        tts: &mut TTS,
+        text: &Text,
         _: &mut PathsState,
     ) -> Option<Snapshot> {
         // Do nothing if there is no track.
@@ -54,7 +54,10 @@ impl Panel for Edit {
                         // Don't let any notes go to t=0.
                         if !notes.iter().any(|n| n.start.checked_sub(dt).is_none()) {
                             notes.iter_mut().for_each(|n| n.set_t0_by(dt, false));
-                            Some(Snapshot::from_states(s0, state))
+                            Some(
+                                Snapshot::from_states(s0, state)
+                                    .with_action(InputEvent::EditStartLeft),
+                            )
                         } else {
                             None
                         }
@@ -63,7 +66,10 @@ impl Panel for Edit {
                     else if input.happened(&InputEvent::EditStartRight) {
                         let dt = self.deltas.get_dt(mode, &state.input);
                         notes.iter_mut().for_each(|n| n.set_t0_by(dt, true));
-                        Some(Snapshot::from_states(s0, state))
+                        Some(
+                            Snapshot::from_states(s0, state)
+                                .with_action(InputEvent::EditStartRight),
+                        )
                     }
                     // Shorten the duration.
                     else if input.happened(&InputEvent::EditDurationLeft) {
@@ -74,7 +80,10 @@ impl Panel for Edit {
                             .all(|n| n.get_duration().checked_sub(dt).is_some())
                         {
                             notes.iter_mut().for_each(|n| n.end -= dt);
-                            Some(Snapshot::from_states(s0, state))
+                            Some(
+                                Snapshot::from_states(s0, state)
+                                    .with_action(InputEvent::EditDurationLeft),
+                            )
                         } else {
                             None
                         }
@@ -83,7 +92,10 @@ impl Panel for Edit {
                     else if input.happened(&InputEvent::EditDurationRight) {
                         let dt = self.deltas.get_dt(mode, &state.input);
                         notes.iter_mut().for_each(|n| n.end += dt);
-                        Some(Snapshot::from_states(s0, state))
+                        Some(
+                            Snapshot::from_states(s0, state)
+                                .with_action(InputEvent::EditDurationRight),
+                        )
                     }
                     // Move the notes up.
                     else if input.happened(&InputEvent::EditPitchUp) {
@@ -91,7 +103,10 @@ impl Panel for Edit {
                         // Don't let any notes go to dn>=max.
                         if notes.iter().all(|n| (n.note + dn) <= MAX_NOTE) {
                             notes.iter_mut().for_each(|n| n.note += dn);
-                            Some(Snapshot::from_states(s0, state))
+                            Some(
+                                Snapshot::from_states(s0, state)
+                                    .with_action(InputEvent::EditPitchUp),
+                            )
                         } else {
                             None
                         }
@@ -102,7 +117,10 @@ impl Panel for Edit {
                         // Don't let any notes go to dn<=0.
                         if notes.iter().all(|n| (n.note - dn) >= MIN_NOTE) {
                             notes.iter_mut().for_each(|n| n.note -= dn);
-                            Some(Snapshot::from_states(s0, state))
+                            Some(
+                                Snapshot::from_states(s0, state)
+                                    .with_action(InputEvent::EditPitchDown),
+                            )
                         } else {
                             None
                         }
@@ -113,7 +131,10 @@ impl Panel for Edit {
                         // Don't let any notes go to dv>=max.
                         if notes.iter().all(|n| (n.velocity + dv) <= MAX_VOLUME) {
                             notes.iter_mut().for_each(|n| n.velocity += dv);
-                            Some(Snapshot::from_states(s0, state))
+                            Some(
+                                Snapshot::from_states(s0, state)
+                                    .with_action(InputEvent::EditVolumeUp),
+                            )
                         } else {
                             None
                         }
@@ -124,10 +145,58 @@ impl Panel for Edit {
                         // Don't let any notes go to dv<=0.
                         if notes.iter().all(|n| (n.velocity as i8 - dv as i8) >= 0) {
                             notes.iter_mut().for_each(|n| n.velocity -= dv);
-                            Some(Snapshot::from_states(s0, state))
+                            Some(
+                                Snapshot::from_states(s0, state)
+                                    .with_action(InputEvent::EditVolumeDown),
+                            )
                         } else {
                             None
                         }
+                    }
+                    // Cycle the articulation.
+                    else if input.happened(&InputEvent::EditArticulation) {
+                        // Cycle from the articulation of the first note.
+                        let articulation = match notes.first() {
+                            Some(note) => Articulation::cycle(&note.articulation),
+                            None => return None,
+                        };
+                        notes.iter_mut().for_each(|n| n.articulation = articulation);
+                        tts.enqueue(text.get_with_values(
+                            "PIANO_ROLL_PANEL_TTS_ARTICULATION",
+                            &[text.get_articulation(&articulation)],
+                        ));
+                        Some(
+                            Snapshot::from_states(s0, state)
+                                .with_action(InputEvent::EditArticulation),
+                        )
+                    }
+                    // Toggle the tie.
+                    else if input.happened(&InputEvent::EditTie) {
+                        // Toggle from the tie of the first note.
+                        let tied = match notes.first() {
+                            Some(note) => !note.tied,
+                            None => return None,
+                        };
+                        notes.iter_mut().for_each(|n| n.tied = tied);
+                        tts.enqueue(text.get_ref(if tied {
+                            "PIANO_ROLL_PANEL_TTS_TIED"
+                        } else {
+                            "PIANO_ROLL_PANEL_TTS_UNTIED"
+                        }));
+                        Some(Snapshot::from_states(s0, state).with_action(InputEvent::EditTie))
+                    }
+                    // Set the volume to the input volume.
+                    else if input.happened(&InputEvent::SetVelocity) {
+                        if notes.is_empty() {
+                            return None;
+                        }
+                        let velocity = state.input.volume.get().clamp(1, MAX_VOLUME);
+                        notes.iter_mut().for_each(|n| n.velocity = velocity);
+                        tts.enqueue(text.get_with_values(
+                            "PIANO_ROLL_PANEL_TTS_SET_VELOCITY",
+                            &[&velocity.to_string()],
+                        ));
+                        Some(Snapshot::from_states(s0, state))
                     } else {
                         None
                     }
@@ -137,7 +206,7 @@ impl Panel for Edit {
         }
     }
 
-    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn) {}
+    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn, _: &mut TTS, _: &Text) {}
 
     fn update_abc123(
         &mut self,
@@ -159,7 +228,34 @@ impl Panel for Edit {
 
 impl PianoRollSubPanel for Edit {
     fn get_status_tts(&mut self, state: &State, text: &Text) -> Vec<TtsString> {
-        vec![get_edit_mode_status_tts(state.edit_mode.get_ref(), text)]
+        let mode = state.edit_mode.get_ref();
+        // Say how much each edit will change the notes.
+        let deltas = TtsString::from(text.get_with_values(
+            "PIANO_ROLL_PANEL_STATUS_TTS_EDIT_DELTAS",
+            &[
+                &self.deltas.get_dn(mode).to_string(),
+                &text.get_ppq_tts(&self.deltas.get_dt(mode, &state.input)),
+                &self.deltas.get_dv(mode).to_string(),
+            ],
+        ));
+        let mut tts_strings = vec![get_edit_mode_status_tts(mode, text), deltas];
+        // Say how long the selected note is, or how many notes are selected.
+        match state.select_mode.get_notes(&state.music) {
+            Some(notes) if notes.len() == 1 => {
+                tts_strings.push(TtsString::from(text.get_with_values(
+                    "PIANO_ROLL_PANEL_STATUS_TTS_EDIT_DURATION",
+                    &[&text.get_ppq_tts(&notes[0].get_duration())],
+                )))
+            }
+            Some(notes) if notes.len() > 1 => {
+                tts_strings.push(TtsString::from(text.get_with_values(
+                    "PIANO_ROLL_PANEL_TTS_SELECTED_COUNT",
+                    &[&notes.len().to_string()],
+                )))
+            }
+            _ => (),
+        }
+        tts_strings
     }
 
     fn get_input_tts(&mut self, state: &State, input: &Input, text: &Text) -> Vec<TtsString> {
@@ -189,6 +285,24 @@ impl PianoRollSubPanel for Edit {
                     input,
                     text,
                 ),
+                self.tooltips.get_tooltip(
+                    "PIANO_ROLL_PANEL_INPUT_TTS_EDIT_4",
+                    &[InputEvent::EditArticulation],
+                    input,
+                    text,
+                ),
+                self.tooltips.get_tooltip(
+                    "PIANO_ROLL_PANEL_INPUT_TTS_EDIT_5",
+                    &[InputEvent::SetVelocity],
+                    input,
+                    text,
+                ),
+                self.tooltips.get_tooltip(
+                    "PIANO_ROLL_PANEL_INPUT_TTS_EDIT_TIE",
+                    &[InputEvent::EditTie],
+                    input,
+                    text,
+                ),
             ],
             None => vec![get_no_selection_status_tts(text)],
         };
@@ -1,9 +1,14 @@
 use super::*;
 use crate::panel::*;
 use crate::select_track;
-use common::config::parse_fractions;
-use common::{Index, Note, PianoRollMode, SelectMode, U64orF32, PPQ_F};
+use common::config::{parse_bool, parse_fractions};
+use common::{
+    Index, Note, Paths, PianoRollMode, SelectMode, U64orF32, DEFAULT_CHANNEL_VOLUME, MAX_VOLUME,
+    PPQ_F,
+};
 use ini::Ini;
+use serde_json::{from_str, to_string};
+use std::fs::{read_to_string, write};
 
 const TRACK_SCROLL_EVENTS: [InputEvent; 2] = [
     InputEvent::PianoRollPreviousTrack,
@@ -27,6 +32,26 @@ pub struct PianoRollPanel {
     beat: Index<usize>,
     /// A buffer of copied notes.
     copied_notes: Vec<Note>,
+    /// If true, the copied notes are saved on quit and loaded on launch.
+    persist_copied_notes: bool,
+    /// If true, repeated notes are offset by the length of the selection. If false, they are offset by the input beat.
+    repeat_by_selection: bool,
+    /// If true, play a note when it is selected or when the cursor moves onto it.
+    preview_on_select: bool,
+    /// If true, say something when the user tries to set the input volume past its limits.
+    volume_limit_tts: bool,
+    /// If true, pasting or repeating notes moves the cursor to the end of the new notes and scrolls the view to show them.
+    follow_paste: bool,
+    /// If true, selecting past the last track selects the first track, and vice versa.
+    wrap_tracks: bool,
+    /// If true, notes in the punch region have been removed since the track was armed.
+    punched: bool,
+    /// A repeating pattern of note durations in PPQ. If empty, there is no rhythm pattern.
+    rhythm_pattern: Vec<u64>,
+    /// If true, new notes follow `rhythm_pattern` instead of the input beat.
+    rhythm: bool,
+    /// The index of the next duration in `rhythm_pattern`.
+    rhythm_index: usize,
     /// The tooltips handler.
     tooltips: Tooltips,
 }
@@ -34,7 +59,7 @@ pub struct PianoRollPanel {
 impl PianoRollPanel {
     pub fn new(beat: &u64, config: &Ini) -> Self {
         let edit = Edit::new(config);
-        let select = Select::default();
+        let select = Select::new(config);
         let time = Time::new(config);
         let view = View::new(config);
         // Load the beats.
@@ -52,6 +77,28 @@ impl PianoRollPanel {
             }
         };
         let beat = Index::new(beat_index, beats.len());
+        let repeat_by_selection = parse_bool(section, "repeat_notes_by_selection");
+        let preview_on_select = parse_bool(section, "preview_on_select");
+        let volume_limit_tts = parse_bool(section, "volume_limit_tts");
+        let follow_paste = parse_bool(section, "follow_paste");
+        let persist_copied_notes = parse_bool(section, "persist_copied_notes");
+        // Load the notes that were copied in the previous session.
+        let copied_notes = if persist_copied_notes {
+            read_to_string(&Paths::get().copied_notes_path)
+                .ok()
+                .and_then(|s| from_str::<Vec<Note>>(&s).ok())
+                .unwrap_or_default()
+        } else {
+            vec![]
+        };
+        let rhythm_pattern = parse_fractions(section, "rhythm_pattern")
+            .iter()
+            .map(|f| (*f * PPQ_F) as u64)
+            .collect::<Vec<u64>>();
+        if rhythm_pattern.contains(&0) {
+            panic!("Invalid rhythm pattern: {:?}", rhythm_pattern);
+        }
+        let wrap_tracks = parse_bool(config.section(Some("TRACKS")).unwrap(), "wrap_tracks");
         Self {
             edit,
             select,
@@ -59,11 +106,132 @@ impl PianoRollPanel {
             view,
             beats,
             beat,
-            copied_notes: vec![],
+            copied_notes,
+            persist_copied_notes,
+            repeat_by_selection,
+            preview_on_select,
+            volume_limit_tts,
+            follow_paste,
+            wrap_tracks,
+            punched: false,
+            rhythm_pattern,
+            rhythm: false,
+            rhythm_index: 0,
             tooltips: Tooltips::default(),
         }
     }
 
+    /// If `self.persist_copied_notes` is true, write the copied notes to disk so that they can be pasted in the next session.
+    /// Call this when the app quits.
+    pub fn save_copied_notes(&self) {
+        if self.persist_copied_notes {
+            if let Ok(s) = to_string(&self.copied_notes) {
+                if let Err(error) = write(&Paths::get().copied_notes_path, s) {
+                    println!("Failed to save the copied notes: {}", error);
+                }
+            }
+        }
+    }
+
+    /// Preview a note that was just selected, or that the cursor just moved onto.
+    ///
+    /// - `select_mode` The select mode before the sub-panel updated.
+    /// - `cursor` The cursor time before the sub-panel updated.
+    fn preview_note(state: &State, conn: &mut Conn, select_mode: &SelectMode, cursor: u64) {
+        let track = match state.music.get_selected_track() {
+            Some(track) => track,
+            None => return,
+        };
+        let note = match &state.select_mode {
+            SelectMode::Single(Some(index)) if state.select_mode != *select_mode => {
+                track.notes.get(*index)
+            }
+            _ if state.time.cursor != cursor => {
+                track.notes.iter().find(|n| n.start == state.time.cursor)
+            }
+            _ => None,
+        };
+        if let Some(note) = note {
+            conn.preview_note(state, note);
+        }
+    }
+
+    /// Increment or decrement the input volume. The volume won't go above `MAX_VOLUME` or below 1.
+    fn set_input_volume(
+        &self,
+        up: bool,
+        state: &mut State,
+        tts: &mut TTS,
+        text: &Text,
+    ) -> Option<Snapshot> {
+        let volume = state.input.volume.get();
+        if (up && volume >= MAX_VOLUME) || (!up && volume <= 1) {
+            if self.volume_limit_tts {
+                tts.enqueue(text.get_ref(if up {
+                    "PIANO_ROLL_PANEL_TTS_MAX_INPUT_VOLUME"
+                } else {
+                    "PIANO_ROLL_PANEL_TTS_MIN_INPUT_VOLUME"
+                }));
+            }
+            None
+        } else {
+            Some(Snapshot::from_state(
+                |s| s.input.volume.increment(up),
+                state,
+            ))
+        }
+    }
+
+    /// Returns the indices of the tracks that can receive recorded notes.
+    /// This includes every record-armed track, and the selected track if the piano roll is armed.
+    fn get_record_track_indices(state: &State) -> Vec<usize> {
+        let mut indices: Vec<usize> = state
+            .music
+            .midi_tracks
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.record_armed)
+            .map(|(i, _)| i)
+            .collect();
+        if state.input.armed {
+            if let Some(selected) = state.music.selected {
+                if !indices.contains(&selected) {
+                    indices.push(selected);
+                }
+            }
+        }
+        indices
+    }
+
+    /// Returns the index of the track that a recorded note should be added to.
+    /// If there are multiple tracks, a note from a MIDI device goes to the track whose channel matches the note's MIDI channel.
+    /// Otherwise, the note goes to the selected track.
+    ///
+    /// - `indices` The indices of the tracks that can receive recorded notes. This must not be empty.
+    /// - `note` The recorded note as MIDI bytes.
+    /// - `midi` If true, the note is from a MIDI device. If false, it's from qwerty input and its channel is ignored.
+    fn get_record_track_index(
+        state: &State,
+        indices: &[usize],
+        note: &[u8; 3],
+        midi: bool,
+    ) -> usize {
+        if indices.len() == 1 {
+            return indices[0];
+        }
+        let channel = note[0] & 0x0F;
+        match indices
+            .iter()
+            .find(|i| midi && state.music.midi_tracks[**i].channel == channel)
+        {
+            Some(index) => *index,
+            None => match state.music.selected {
+                Some(selected) if indices.contains(&selected) => selected,
+                _ => indices[0],
+            },
+        }
+    }
+
     /// Set the input beat.
     fn set_input_beat(&mut self, up: bool, state: &mut State) -> Option<Snapshot> {
         let s0 = state.clone();
@@ -74,6 +242,46 @@ impl PianoRollPanel {
         Some(Snapshot::from_states(s0, state))
     }
 
+    /// Returns the duration of the next new note.
+    /// This is the next duration in the rhythm pattern, or the input beat if the rhythm pattern is off.
+    fn get_new_note_duration(&self, state: &State) -> u64 {
+        if self.rhythm {
+            self.rhythm_pattern[self.rhythm_index]
+        } else {
+            state.input.beat.get_u()
+        }
+    }
+
+    /// Returns a text-to-speech list of the durations in the rhythm pattern.
+    fn get_rhythm_pattern_tts(&self, text: &Text) -> String {
+        self.rhythm_pattern
+            .iter()
+            .map(|ppq| text.get_ppq_tts(ppq))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// Toggle the rhythm pattern. When toggled on, the pattern starts from its first duration.
+    fn toggle_rhythm_pattern(&mut self, state: &State, tts: &mut TTS, text: &Text) {
+        if self.rhythm_pattern.is_empty() {
+            tts.enqueue(text.get_ref("PIANO_ROLL_PANEL_TTS_NO_RHYTHM_PATTERN"));
+            return;
+        }
+        self.rhythm = !self.rhythm;
+        self.rhythm_index = 0;
+        tts.enqueue(if self.rhythm {
+            text.get_with_values(
+                "PIANO_ROLL_PANEL_TTS_RHYTHM_PATTERN_ON",
+                &[&self.get_rhythm_pattern_tts(text)],
+            )
+        } else {
+            text.get_with_values(
+                "PIANO_ROLL_PANEL_TTS_RHYTHM_PATTERN_OFF",
+                &[&text.get_ppq_tts(&state.input.beat.get_u())],
+            )
+        });
+    }
+
     /// Set the piano roll mode.
     fn set_mode(mode: PianoRollMode, state: &mut State) -> Option<Snapshot> {
         let s0 = state.clone();
@@ -105,6 +313,255 @@ impl PianoRollPanel {
         }
     }
 
+    /// Returns copies of `notes` such that the earliest note starts at `t0`.
+    fn get_offset_notes(notes: &[Note], t0: u64) -> Vec<Note> {
+        // Get the minimum start time.
+        let min_time = notes.iter().map(|n| n.start).min().unwrap();
+        // Adjust the start and end time.
+        let mut notes = notes.to_vec();
+        notes.iter_mut().for_each(|n| {
+            let dt = n.end - n.start;
+            n.start = (n.start - min_time) + t0;
+            n.end = n.start + dt;
+        });
+        notes
+    }
+
+    /// If `self.follow_paste`, move the cursor to the end of new notes and scroll the view to show them.
+    fn follow_paste(&self, notes: &[Note], state: &mut State, tts: &mut TTS, text: &Text) {
+        if !self.follow_paste || notes.is_empty() {
+            return;
+        }
+        let t0 = notes.iter().map(|n| n.start).min().unwrap();
+        let t1 = notes.iter().map(|n| n.end).max().unwrap();
+        state.time.cursor = t1;
+        state.view.show_time(t0, t1);
+        tts.enqueue(text.get_with_values(
+            "PIANO_ROLL_PANEL_TTS_FOLLOW_PASTE",
+            &[&text.get_time(t1, &state.time)],
+        ));
+    }
+
+    /// Duplicate the selected notes, insert them after the selection, and select the new notes.
+    fn repeat_notes(&self, state: &mut State, tts: &mut TTS, text: &Text) -> Option<Snapshot> {
+        let notes: Vec<Note> = match state.select_mode.get_notes(&state.music) {
+            Some(notes) => notes.iter().map(|&n| *n).collect(),
+            None => return None,
+        };
+        if notes.is_empty() {
+            return None;
+        }
+        // Get the offset.
+        let t0 = notes.iter().map(|n| n.start).min().unwrap();
+        let dt = if self.repeat_by_selection {
+            notes.iter().map(|n| n.end).max().unwrap() - t0
+        } else {
+            state.input.beat.get_u()
+        };
+        // Clone the state.
+        let s0 = state.clone();
+        let track = state.music.get_selected_track_mut().unwrap();
+        // Add the notes.
+        let index = track.notes.len();
+        let new_notes = PianoRollPanel::get_offset_notes(&notes, t0 + dt);
+        track.notes.extend(new_notes.iter().copied());
+        // Select the new notes.
+        state.select_mode = match &state.select_mode {
+            SelectMode::Single(_) => SelectMode::Single(Some(index)),
+            SelectMode::Many(_) => SelectMode::Many(Some((index..index + notes.len()).collect())),
+        };
+        self.follow_paste(&new_notes, state, tts, text);
+        Some(Snapshot::from_states(s0, state))
+    }
+
+    /// Move the selected notes in time so that the earliest note starts at the cursor. The notes keep their relative timing.
+    fn align_to_cursor(state: &mut State, tts: &mut TTS, text: &Text) -> Option<Snapshot> {
+        let indices = state.select_mode.get_note_indices()?;
+        let t0 = state
+            .music
+            .get_selected_track()?
+            .notes
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| indices.contains(i))
+            .map(|(_, n)| n.start)
+            .min()?;
+        let cursor = state.time.cursor;
+        if t0 == cursor {
+            tts.enqueue(text.get_ref("PIANO_ROLL_PANEL_TTS_ALIGN_TO_CURSOR_NO_OFFSET"));
+            return None;
+        }
+        // Clone the state.
+        let s0 = state.clone();
+        let track = state.music.get_selected_track_mut().unwrap();
+        for (_, note) in track
+            .notes
+            .iter_mut()
+            .enumerate()
+            .filter(|(i, _)| indices.contains(i))
+        {
+            let dt = note.end - note.start;
+            note.start = (note.start - t0) + cursor;
+            note.end = note.start + dt;
+        }
+        let (key, dt) = if cursor > t0 {
+            ("PIANO_ROLL_PANEL_TTS_ALIGN_TO_CURSOR_LATER", cursor - t0)
+        } else {
+            ("PIANO_ROLL_PANEL_TTS_ALIGN_TO_CURSOR_EARLIER", t0 - cursor)
+        };
+        tts.enqueue(text.get_with_values(key, &[&text.get_ppq_tts(&dt)]));
+        Some(Snapshot::from_states(s0, state))
+    }
+
+    /// Move the cursor to the note with the highest or lowest velocity in the selected track and select it.
+    ///
+    /// - `loudest` If true, go to the note with the highest velocity. If false, go to the note with the lowest velocity.
+    fn go_to_velocity_extreme(
+        state: &mut State,
+        loudest: bool,
+        tts: &mut TTS,
+        text: &Text,
+    ) -> Option<Snapshot> {
+        let track = state.music.get_selected_track()?;
+        let index = match track.get_velocity_extreme(loudest) {
+            Some(index) => index,
+            None => {
+                tts.enqueue(text.get_ref("PIANO_ROLL_PANEL_TTS_GO_TO_NOTE_EMPTY"));
+                return None;
+            }
+        };
+        let note = track.notes[index];
+        let note_name = text
+            .get_channel_note_name(note.note, track.channel)
+            .to_string();
+        // Clone the state.
+        let s0 = state.clone();
+        state.time.cursor = note.start;
+        state.view.show_time(note.start, note.end);
+        state.select_mode = match &state.select_mode {
+            SelectMode::Single(_) => SelectMode::Single(Some(index)),
+            SelectMode::Many(_) => SelectMode::Many(Some(vec![index])),
+        };
+        let key = if loudest {
+            "PIANO_ROLL_PANEL_TTS_GO_TO_LOUDEST_NOTE"
+        } else {
+            "PIANO_ROLL_PANEL_TTS_GO_TO_QUIETEST_NOTE"
+        };
+        tts.enqueue(text.get_with_values(
+            key,
+            &[
+                &note_name,
+                &note.velocity.to_string(),
+                &text.get_time(note.start, &state.time),
+            ],
+        ));
+        Some(Snapshot::from_states(s0, state))
+    }
+
+    /// Raise or lower the selected track's volume envelope at the cursor. If there isn't a breakpoint at the cursor, add one.
+    ///
+    /// - `up` If true, raise the volume. If false, lower it.
+    fn set_volume_point(
+        state: &mut State,
+        up: bool,
+        tts: &mut TTS,
+        text: &Text,
+    ) -> Option<Snapshot> {
+        let cursor = state.time.cursor;
+        let track = state.music.get_selected_track()?;
+        let value = track
+            .get_volume_at(cursor)
+            .unwrap_or(DEFAULT_CHANNEL_VOLUME);
+        let value = if up {
+            (value + 1).min(MAX_VOLUME)
+        } else {
+            value.saturating_sub(1)
+        };
+        // Clone the state.
+        let s0 = state.clone();
+        state
+            .music
+            .get_selected_track_mut()
+            .unwrap()
+            .set_volume_point(cursor, value);
+        tts.enqueue(text.get_with_values(
+            "PIANO_ROLL_PANEL_TTS_VOLUME_POINT",
+            &[&value.to_string(), &text.get_time(cursor, &state.time)],
+        ));
+        Some(Snapshot::from_states(s0, state))
+    }
+
+    /// Remove the selected track's volume envelope breakpoint at the cursor.
+    fn remove_volume_point(state: &mut State, tts: &mut TTS, text: &Text) -> Option<Snapshot> {
+        let cursor = state.time.cursor;
+        let time = text.get_time(cursor, &state.time);
+        if !state
+            .music
+            .get_selected_track()?
+            .volume_envelope
+            .iter()
+            .any(|p| p.0 == cursor)
+        {
+            tts.enqueue(text.get_with_values("PIANO_ROLL_PANEL_TTS_NO_VOLUME_POINT", &[&time]));
+            return None;
+        }
+        // Clone the state.
+        let s0 = state.clone();
+        state
+            .music
+            .get_selected_track_mut()
+            .unwrap()
+            .remove_volume_point(cursor);
+        tts.enqueue(text.get_with_values("PIANO_ROLL_PANEL_TTS_REMOVE_VOLUME_POINT", &[&time]));
+        Some(Snapshot::from_states(s0, state))
+    }
+
+    /// Set the loop region to the bounds of the selected notes, or to the whole music if nothing is selected.
+    /// If the loop region is already enabled and wouldn't change, disable looping.
+    fn loop_selection(state: &mut State, tts: &mut TTS, text: &Text) -> Option<Snapshot> {
+        let bounds = match state.select_mode.get_notes(&state.music) {
+            Some(notes) if !notes.is_empty() => Some((
+                notes.iter().map(|n| n.start).min().unwrap(),
+                notes.iter().map(|n| n.end).max().unwrap(),
+            )),
+            _ => state
+                .music
+                .midi_tracks
+                .iter()
+                .filter_map(|t| t.get_end())
+                .max()
+                .map(|end| (0, end)),
+        };
+        match bounds {
+            Some((start, end)) => {
+                let s0 = state.clone();
+                if state.time.looping
+                    && state.time.loop_start == start
+                    && state.time.loop_end == end
+                {
+                    state.time.looping = false;
+                    tts.enqueue(text.get_ref("PIANO_ROLL_PANEL_TTS_STOP_LOOP"));
+                } else {
+                    state.time.loop_start = start;
+                    state.time.loop_end = end;
+                    state.time.looping = true;
+                    tts.enqueue(text.get_with_values(
+                        "PIANO_ROLL_PANEL_TTS_LOOP",
+                        &[
+                            &text.get_time(start, &state.time),
+                            &text.get_time(end, &state.time),
+                        ],
+                    ));
+                }
+                Some(Snapshot::from_states(s0, state))
+            }
+            None => {
+                tts.enqueue(text.get_ref("PIANO_ROLL_PANEL_TTS_NOTHING_TO_LOOP"));
+                None
+            }
+        }
+    }
+
     /// Delete notes from the track.
     fn delete_notes(state: &mut State) -> Option<Snapshot> {
         // Clone the state.
@@ -144,40 +601,81 @@ impl Panel for PianoRollPanel {
     ) -> Option<Snapshot> {
         // Select a track.
         if !state.view.single_track {
-            if let Some(snapshot) = select_track(state, input, TRACK_SCROLL_EVENTS) {
+            if let Some(snapshot) = select_track(
+                state,
+                input,
+                TRACK_SCROLL_EVENTS,
+                self.wrap_tracks,
+                tts,
+                text,
+            ) {
                 return Some(snapshot);
             }
         }
+        // The next recording will replace the notes in the punch region.
+        if !state.is_armed() {
+            self.punched = false;
+        }
         // Do nothing.
         if state.music.selected.is_none() {
             None
         }
         // Add notes.
-        else if state.input.armed && !input.new_notes.is_empty() {
+        else if state.is_armed() && !input.new_notes.is_empty() {
+            let punch = state.get_punch();
+            // Reject notes outside of the punch region.
+            if let Some([t0, t1]) = punch {
+                if state.time.cursor < t0 || state.time.cursor >= t1 {
+                    return None;
+                }
+            }
+            // Get the tracks that can receive notes.
+            let indices: Vec<usize> = PianoRollPanel::get_record_track_indices(state)
+                .into_iter()
+                .filter(|i| {
+                    conn.state
+                        .programs
+                        .get(&state.music.midi_tracks[*i].channel)
+                        .is_some()
+                })
+                .collect();
+            if indices.is_empty() {
+                return None;
+            }
             // Clone the state.
             let s0 = state.clone();
-            let track = state.music.get_selected_track_mut().unwrap();
-            match conn.state.programs.get(&track.channel) {
-                Some(_) => {
-                    // Get the notes.
-                    let notes: Vec<Note> = input
-                        .new_notes
-                        .iter()
-                        .map(|n| Note {
-                            note: n[1],
-                            velocity: n[2],
-                            start: state.time.cursor,
-                            end: state.time.cursor + state.input.beat.get_u(),
-                        })
-                        .collect();
-                    // Add the notes.
-                    track.notes.extend(notes.iter().copied());
-                    // Move the cursor.
-                    state.time.cursor += state.input.beat.get_u();
-                    Some(Snapshot::from_states(s0, state))
+            let start = state.time.cursor;
+            let dt = self.get_new_note_duration(state);
+            let mut end = start + dt;
+            if let Some([t0, t1]) = punch {
+                // Clamp the notes to the punch region.
+                end = end.min(t1);
+                // Remove the existing notes in the punch region.
+                if !self.punched {
+                    for i in indices.iter() {
+                        state.music.midi_tracks[*i]
+                            .notes
+                            .retain(|n| n.start < t0 || n.start >= t1);
+                    }
+                    state.select_mode = match &state.select_mode {
+                        SelectMode::Single(_) => SelectMode::Single(None),
+                        SelectMode::Many(_) => SelectMode::Many(None),
+                    };
+                    self.punched = true;
                 }
-                None => None,
             }
+            // Add the notes.
+            for (n, midi) in input.new_notes.iter().zip(input.new_notes_midi.iter()) {
+                let index = PianoRollPanel::get_record_track_index(state, &indices, n, *midi);
+                state.music.midi_tracks[index].add_note(n[1], n[2], start, end);
+            }
+            // Move the cursor.
+            state.time.cursor += dt;
+            // Advance the rhythm pattern.
+            if self.rhythm {
+                self.rhythm_index = (self.rhythm_index + 1) % self.rhythm_pattern.len();
+            }
+            Some(Snapshot::from_states(s0, state))
         }
         // Status TTS.
         else if input.happened(&InputEvent::StatusTTS) {
@@ -193,7 +691,6 @@ impl Panel for PianoRollPanel {
                         match state.input.armed {
                             // The beat and the volume.
                             true => {
-                                let beat = text.get_ppq_tts(&state.input.beat.get_u());
                                 let v = state.input.volume.get().to_string();
                                 let volume = if state.input.use_volume {
                                     v
@@ -203,10 +700,20 @@ impl Panel for PianoRollPanel {
                                         &[&v],
                                     )
                                 };
-                                tts_strings.push(TtsString::from(text.get_with_values(
-                                    "PIANO_ROLL_PANEL_STATUS_TTS_ARMED",
-                                    &[&beat, &volume],
-                                )));
+                                let (key, beat) = if self.rhythm {
+                                    (
+                                        "PIANO_ROLL_PANEL_STATUS_TTS_ARMED_RHYTHM_PATTERN",
+                                        self.get_rhythm_pattern_tts(text),
+                                    )
+                                } else {
+                                    (
+                                        "PIANO_ROLL_PANEL_STATUS_TTS_ARMED",
+                                        text.get_ppq_tts(&state.input.beat.get_u()),
+                                    )
+                                };
+                                tts_strings.push(TtsString::from(
+                                    text.get_with_values(key, &[&beat, &volume]),
+                                ));
                             }
                             // Not armed.
                             false => tts_strings.push(TtsString::from(
@@ -242,10 +749,16 @@ impl Panel for PianoRollPanel {
                     Some(_) => {
                         let mut tts_strings = vec![self.tooltips.get_tooltip(
                             "PIANO_ROLL_PANEL_INPUT_TTS_PLAY",
-                            &[InputEvent::PlayStop],
+                            &[InputEvent::PlayStop, InputEvent::PlayFromCursor],
                             input,
                             text,
                         )];
+                        tts_strings.push(self.tooltips.get_tooltip(
+                            "PIANO_ROLL_PANEL_INPUT_TTS_AUDITION",
+                            &[InputEvent::AuditionTrack],
+                            input,
+                            text,
+                        ));
                         // Armed state, beat, volume.
                         match state.input.armed {
                             true => {
@@ -259,6 +772,12 @@ impl Panel for PianoRollPanel {
                                     input,
                                     text,
                                 ));
+                                tts_strings.push(self.tooltips.get_tooltip(
+                                    "PIANO_ROLL_PANEL_INPUT_TTS_RHYTHM_PATTERN",
+                                    &[InputEvent::ToggleRhythmPattern],
+                                    input,
+                                    text,
+                                ));
                                 match state.input.use_volume {
                                     true => tts_strings.push(self.tooltips.get_tooltip(
                                         "PIANO_ROLL_PANEL_INPUT_TTS_DO_NOT_USE_VOLUME",
@@ -362,7 +881,7 @@ impl Panel for PianoRollPanel {
                                 text,
                             ));
                         }
-                        // Delete.
+                        // Delete, repeat.
                         if selected_some {
                             tts_strings.push(self.tooltips.get_tooltip(
                                 "PIANO_ROLL_PANEL_INPUT_TTS_DELETE",
@@ -370,7 +889,50 @@ impl Panel for PianoRollPanel {
                                 input,
                                 text,
                             ));
+                            tts_strings.push(self.tooltips.get_tooltip(
+                                "PIANO_ROLL_PANEL_INPUT_TTS_REPEAT",
+                                &[InputEvent::RepeatNotes],
+                                input,
+                                text,
+                            ));
+                            tts_strings.push(self.tooltips.get_tooltip(
+                                "PIANO_ROLL_PANEL_INPUT_TTS_ALIGN_TO_CURSOR",
+                                &[InputEvent::AlignToCursor],
+                                input,
+                                text,
+                            ));
                         }
+                        // Go to the loudest or quietest note.
+                        tts_strings.push(self.tooltips.get_tooltip(
+                            "PIANO_ROLL_PANEL_INPUT_TTS_GO_TO_VELOCITY_EXTREME",
+                            &[InputEvent::GoToLoudestNote, InputEvent::GoToQuietestNote],
+                            input,
+                            text,
+                        ));
+                        // Volume envelope.
+                        tts_strings.push(self.tooltips.get_tooltip(
+                            "PIANO_ROLL_PANEL_INPUT_TTS_VOLUME_ENVELOPE",
+                            &[
+                                InputEvent::VolumeEnvelopeUp,
+                                InputEvent::VolumeEnvelopeDown,
+                                InputEvent::RemoveVolumePoint,
+                            ],
+                            input,
+                            text,
+                        ));
+                        // Loop.
+                        tts_strings.push(self.tooltips.get_tooltip(
+                            if state.time.looping {
+                                "PIANO_ROLL_PANEL_INPUT_TTS_STOP_LOOP"
+                            } else if selected_some {
+                                "PIANO_ROLL_PANEL_INPUT_TTS_LOOP_SELECTION"
+                            } else {
+                                "PIANO_ROLL_PANEL_INPUT_TTS_LOOP_ALL"
+                            },
+                            &[InputEvent::LoopSelection],
+                            input,
+                            text,
+                        ));
                         // Sub-panel inputs.
                         tts_strings.append(
                             &mut self.get_sub_panel(state).get_input_tts(state, input, text),
@@ -394,11 +956,11 @@ impl Panel for PianoRollPanel {
             // Copy.
             self.copy_notes(state);
             // Delete.
-            PianoRollPanel::delete_notes(state)
+            PianoRollPanel::delete_notes(state).map(|s| s.with_action(InputEvent::CutNotes))
         }
         // Delete notes.
         else if input.happened(&InputEvent::DeleteNotes) {
-            PianoRollPanel::delete_notes(state)
+            PianoRollPanel::delete_notes(state).map(|s| s.with_action(InputEvent::DeleteNotes))
         }
         // Paste notes.
         else if input.happened(&InputEvent::PasteNotes) {
@@ -406,24 +968,15 @@ impl Panel for PianoRollPanel {
                 // Clone the state.
                 let s0 = state.clone();
                 if let Some(track) = state.music.get_selected_track_mut() {
-                    // Get the minimum start time.
-                    let min_time = self
-                        .copied_notes
-                        .iter()
-                        .min_by(|a, b| a.start.cmp(&b.start))
-                        .unwrap()
-                        .start;
                     // Adjust the start and end time.
-                    let mut notes = self.copied_notes.to_vec();
-                    notes.iter_mut().for_each(|n| {
-                        let dt = n.end - n.start;
-                        n.start = (n.start - min_time) + state.time.cursor;
-                        n.end = n.start + dt;
-                    });
+                    let notes =
+                        PianoRollPanel::get_offset_notes(&self.copied_notes, state.time.cursor);
                     // Add the notes.
-                    track.notes.append(&mut notes);
+                    track.notes.extend(notes.iter().copied());
+                    // Move the cursor and the view.
+                    self.follow_paste(&notes, state, tts, text);
                     // Return the undo state.
-                    Some(Snapshot::from_states(s0, state))
+                    Some(Snapshot::from_states(s0, state).with_action(InputEvent::PasteNotes))
                 } else {
                     None
                 }
@@ -431,6 +984,34 @@ impl Panel for PianoRollPanel {
                 None
             }
         }
+        // Repeat notes.
+        else if input.happened(&InputEvent::RepeatNotes) {
+            self.repeat_notes(state, tts, text)
+                .map(|s| s.with_action(InputEvent::RepeatNotes))
+        }
+        // Align the selection to the cursor.
+        else if input.happened(&InputEvent::AlignToCursor) {
+            PianoRollPanel::align_to_cursor(state, tts, text)
+                .map(|s| s.with_action(InputEvent::AlignToCursor))
+        }
+        // Go to the loudest or quietest note.
+        else if input.happened(&InputEvent::GoToLoudestNote) {
+            PianoRollPanel::go_to_velocity_extreme(state, true, tts, text)
+        } else if input.happened(&InputEvent::GoToQuietestNote) {
+            PianoRollPanel::go_to_velocity_extreme(state, false, tts, text)
+        }
+        // Edit the volume envelope.
+        else if input.happened(&InputEvent::VolumeEnvelopeUp) {
+            PianoRollPanel::set_volume_point(state, true, tts, text)
+        } else if input.happened(&InputEvent::VolumeEnvelopeDown) {
+            PianoRollPanel::set_volume_point(state, false, tts, text)
+        } else if input.happened(&InputEvent::RemoveVolumePoint) {
+            PianoRollPanel::remove_volume_point(state, tts, text)
+        }
+        // Loop the selection.
+        else if input.happened(&InputEvent::LoopSelection) {
+            PianoRollPanel::loop_selection(state, tts, text)
+        }
         // Toggle arm.
         else if input.happened(&InputEvent::Arm) {
             let s0 = state.clone();
@@ -451,6 +1032,11 @@ impl Panel for PianoRollPanel {
         } else if input.happened(&InputEvent::InputBeatRight) {
             self.set_input_beat(true, state)
         }
+        // Toggle the rhythm pattern.
+        else if input.happened(&InputEvent::ToggleRhythmPattern) {
+            self.toggle_rhythm_pattern(state, tts, text);
+            None
+        }
         // Set the volume.
         else if input.happened(&InputEvent::ToggleInputVolume) {
             Some(Snapshot::from_state_value(
@@ -459,15 +1045,9 @@ impl Panel for PianoRollPanel {
                 state,
             ))
         } else if input.happened(&InputEvent::DecreaseInputVolume) {
-            Some(Snapshot::from_state(
-                |s| s.input.volume.increment(false),
-                state,
-            ))
+            self.set_input_volume(false, state, tts, text)
         } else if input.happened(&InputEvent::IncreaseInputVolume) {
-            Some(Snapshot::from_state(
-                |s| s.input.volume.increment(true),
-                state,
-            ))
+            self.set_input_volume(true, state, tts, text)
         }
         // Set the mode.
         else if input.happened(&InputEvent::PianoRollSetEdit) {
@@ -480,8 +1060,10 @@ impl Panel for PianoRollPanel {
             PianoRollPanel::set_mode(PianoRollMode::View, state)
         } else {
             // Sub-panel actions.
+            let select_mode = state.select_mode.clone();
+            let cursor = state.time.cursor;
             let mode = state.piano_roll_mode;
-            match mode {
+            let snapshot = match mode {
                 PianoRollMode::Edit => self.edit.update(state, conn, input, tts, text, paths_state),
                 PianoRollMode::Select => {
                     self.select
@@ -489,26 +1071,142 @@ impl Panel for PianoRollPanel {
                 }
                 PianoRollMode::Time => self.time.update(state, conn, input, tts, text, paths_state),
                 PianoRollMode::View => self.view.update(state, conn, input, tts, text, paths_state),
+            };
+            // Preview the note. Don't interrupt the scrubbed notes.
+            let scrubbing = mode == PianoRollMode::Time && self.time.scrub;
+            if self.preview_on_select && !scrubbing && snapshot.is_some() {
+                PianoRollPanel::preview_note(state, conn, &select_mode, cursor);
             }
+            snapshot
         }
     }
 
-    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn) {}
+    fn on_disable_abc123(
+        &mut self,
+        state: &mut State,
+        conn: &mut Conn,
+        tts: &mut TTS,
+        text: &Text,
+    ) {
+        if state.piano_roll_mode == PianoRollMode::Time {
+            self.time.on_disable_abc123(state, conn, tts, text)
+        }
+    }
 
     fn update_abc123(
         &mut self,
-        _: &mut State,
-        _: &Input,
-        _: &mut Conn,
+        state: &mut State,
+        input: &Input,
+        conn: &mut Conn,
     ) -> (Option<Snapshot>, bool) {
-        (None, false)
+        match state.piano_roll_mode {
+            PianoRollMode::Time => self.time.update_abc123(state, input, conn),
+            _ => (None, false),
+        }
     }
 
-    fn allow_alphanumeric_input(&self, _: &State, _: &Conn) -> bool {
-        false
+    fn allow_alphanumeric_input(&self, state: &State, conn: &Conn) -> bool {
+        match state.piano_roll_mode {
+            PianoRollMode::Time => self.time.allow_alphanumeric_input(state, conn),
+            _ => false,
+        }
     }
 
     fn allow_play_music(&self) -> bool {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PianoRollPanel;
+    use common::State;
+    use ini::Ini;
+
+    #[test]
+    fn record_track_index() {
+        let mut state = State::new(&Ini::load_from_file("../data/config.ini").unwrap());
+        state.music.add_track(0);
+        state.music.add_track(1);
+        state.music.selected = Some(1);
+        let indices = [0, 1];
+        // MIDI notes go to the track with the same channel.
+        assert_eq!(
+            PianoRollPanel::get_record_track_index(&state, &indices, &[144, 60, 100], true),
+            0
+        );
+        assert_eq!(
+            PianoRollPanel::get_record_track_index(&state, &indices, &[145, 60, 100], true),
+            1
+        );
+        // Qwerty notes always go to the selected track.
+        assert_eq!(
+            PianoRollPanel::get_record_track_index(&state, &indices, &[144, 60, 100], false),
+            1
+        );
+        // There isn't a track on this channel.
+        state.music.selected = Some(0);
+        assert_eq!(
+            PianoRollPanel::get_record_track_index(&state, &indices, &[146, 60, 100], true),
+            0
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PianoRollPanel;
+    use crate::panel::*;
+    use common::{Paths, PianoRollMode, SelectMode, PPQ_U};
+    use ini::Ini;
+    use std::path::PathBuf;
+
+    #[test]
+    fn piano_roll_action() {
+        Paths::init(&PathBuf::from("../data"));
+        let mut config = Ini::load_from_file("../data/config.ini").unwrap();
+        config
+            .with_section(Some("PIANO_ROLL"))
+            .set("persist_copied_notes", "0");
+        let text = Text::new(&config, Paths::get());
+        let mut tts = TTS::new(&config);
+        let mut conn = Conn::default();
+        let mut paths_state = PathsState::default();
+        let mut state = State::new(&config);
+        state.music.add_track(0).add_note(60, 100, 0, PPQ_U);
+        state.music.add_track(1).add_note(62, 100, 0, PPQ_U);
+        state.music.selected = Some(1);
+        state.view.single_track = false;
+        state.piano_roll_mode = PianoRollMode::Edit;
+        state.select_mode = SelectMode::Single(Some(0));
+        let mut panel = PianoRollPanel::new(&PPQ_U, &config);
+        let mut input = Input::default();
+        let mut update = |events: &[InputEvent], state: &mut State| {
+            input.set_events(events);
+            panel.update(state, &mut conn, &input, &mut tts, &text, &mut paths_state)
+        };
+        // The same key selects the previous track and moves the selected notes up. Only the track is selected.
+        let snapshot = update(
+            &[InputEvent::PianoRollPreviousTrack, InputEvent::EditPitchUp],
+            &mut state,
+        )
+        .unwrap();
+        assert!(snapshot.action.is_none());
+        assert_eq!(state.music.selected, Some(0));
+        // An edit.
+        state.select_mode = SelectMode::Single(Some(0));
+        let snapshot = update(&[InputEvent::EditPitchUp], &mut state).unwrap();
+        assert_eq!(snapshot.action, Some(InputEvent::EditPitchUp));
+        assert!(state.music.midi_tracks[0].notes[0].note > 60);
+        // The same key moves the time cursor left and moves the selected notes left. Only the cursor moves.
+        state.piano_roll_mode = PianoRollMode::Time;
+        state.time.cursor = PPQ_U;
+        let snapshot = update(
+            &[InputEvent::TimeCursorLeft, InputEvent::EditStartLeft],
+            &mut state,
+        )
+        .unwrap();
+        assert!(snapshot.action.is_none());
+        assert!(state.time.cursor < PPQ_U);
+    }
+}
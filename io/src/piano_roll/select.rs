@@ -1,15 +1,64 @@
 use super::{get_no_selection_status_tts, PianoRollSubPanel};
 use crate::panel::*;
+use common::config::parse_bool;
 use common::time::Time;
 use common::{MidiTrack, Note, SelectMode};
+use ini::Ini;
 
 /// Select notes.
-#[derive(Default)]
 pub(super) struct Select {
+    /// If true, extending or shrinking a selection of many notes adds or removes every note that starts at the same time.
+    snap_to_notes: bool,
     tooltips: Tooltips,
 }
 
 impl Select {
+    pub fn new(config: &Ini) -> Self {
+        let section = config.section(Some("PIANO_ROLL")).unwrap();
+        let snap_to_notes = parse_bool(section, "select_snap_to_notes");
+        Self {
+            snap_to_notes,
+            tooltips: Tooltips::default(),
+        }
+    }
+
+    /// Returns the indices of all notes in `track` that start at the same time as `note`.
+    /// If `self.snap_to_notes` is false, this only returns `index`.
+    fn get_snapped_indices(&self, track: &MidiTrack, index: usize) -> Vec<usize> {
+        if self.snap_to_notes {
+            let start = track.notes[index].start;
+            track
+                .notes
+                .iter()
+                .enumerate()
+                .filter(|n| n.1.start == start)
+                .map(|n| n.0)
+                .collect()
+        } else {
+            vec![index]
+        }
+    }
+
+    /// Add notes to a selection.
+    fn add_indices(&self, track: &MidiTrack, index: usize, indices: &mut Vec<usize>) {
+        for i in self.get_snapped_indices(track, index) {
+            if !indices.contains(&i) {
+                indices.push(i);
+            }
+        }
+    }
+
+    /// Remove notes from a selection. Returns false if this would remove every note.
+    fn remove_indices(&self, track: &MidiTrack, index: usize, indices: &mut Vec<usize>) -> bool {
+        let removed = self.get_snapped_indices(track, index);
+        if indices.iter().all(|i| removed.contains(i)) {
+            false
+        } else {
+            indices.retain(|i| !removed.contains(i));
+            true
+        }
+    }
+
     /// Returns the index of the note closest (and before) the cursor.
     fn get_note_index_closest_to_before_cursor(notes: &[Note], time: &Time) -> Option<usize> {
         notes
@@ -55,18 +104,52 @@ impl Select {
             .filter(|n| indices.contains(&n.0))
             .max_by(|a, b| a.1.cmp(b.1))
     }
-}
 
-impl Panel for Select {
-    fn update(
-        &mut self,
-        state: &mut State,
-        _: &mut Conn,
-        input: &Input,
-        _: &mut TTS,
-        _: &Text,
-        _: &mut PathsState,
-    ) -> Option<Snapshot> {
+    /// Select every note in the selected track that isn't selected, and deselect every note that is. Returns a new undo-redo state.
+    fn invert_selection(state: &mut State, tts: &mut TTS, text: &Text) -> Option<Snapshot> {
+        let num_notes = state.music.get_selected_track()?.notes.len();
+        let selected = state.select_mode.get_note_indices().unwrap_or_default();
+        let indices: Vec<usize> = (0..num_notes).filter(|i| !selected.contains(i)).collect();
+        let s0 = state.clone();
+        tts.enqueue(text.get_with_values(
+            "PIANO_ROLL_PANEL_TTS_SELECTED_COUNT",
+            &[&indices.len().to_string()],
+        ));
+        state.select_mode = SelectMode::Many(if indices.is_empty() {
+            None
+        } else {
+            Some(indices)
+        });
+        Some(Snapshot::from_states(s0, state))
+    }
+
+    /// Select every note in the selected track that starts at the cursor, for example every note in a chord. Returns a new undo-redo state.
+    fn select_column(state: &mut State, tts: &mut TTS, text: &Text) -> Option<Snapshot> {
+        let cursor = state.time.cursor;
+        let indices: Vec<usize> = state
+            .music
+            .get_selected_track()?
+            .notes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.start == cursor)
+            .map(|(i, _)| i)
+            .collect();
+        if indices.is_empty() {
+            tts.enqueue(text.get_ref("PIANO_ROLL_PANEL_TTS_NO_NOTES_AT_CURSOR"));
+            return None;
+        }
+        let s0 = state.clone();
+        tts.enqueue(text.get_with_values(
+            "PIANO_ROLL_PANEL_TTS_SELECTED_COUNT",
+            &[&indices.len().to_string()],
+        ));
+        state.select_mode = SelectMode::Many(Some(indices));
+        Some(Snapshot::from_states(s0, state))
+    }
+
+    /// Select notes. Returns a new undo-redo state.
+    fn update_selection(&self, state: &mut State, input: &Input) -> Option<Snapshot> {
         match state.music.get_selected_track() {
             None => None,
             Some(track) => {
@@ -136,7 +219,7 @@ impl Panel for Select {
                                         .max_by(|a, b| a.1.cmp(b.1))
                                     {
                                         // Add the prior note.
-                                        indices.push(prior_note.0);
+                                        self.add_indices(track, prior_note.0, indices);
                                         return Some(Snapshot::from_states(s0, state));
                                     }
                                 }
@@ -197,8 +280,9 @@ impl Panel for Select {
                                     Select::get_first_selected_note(track, indices)
                                 {
                                     // Remove the note.
-                                    indices.retain(|n| *n != first_selected_note.0);
-                                    return Some(Snapshot::from_states(s0, state));
+                                    if self.remove_indices(track, first_selected_note.0, indices) {
+                                        return Some(Snapshot::from_states(s0, state));
+                                    }
                                 }
                             }
                             // Select the note closest to the cursor.
@@ -246,7 +330,13 @@ impl Panel for Select {
                                         let s0 = state.clone();
                                         // Remove the note.
                                         let mut indices = indices.clone();
-                                        indices.retain(|n| *n != last_selected_note.0);
+                                        if !self.remove_indices(
+                                            track,
+                                            last_selected_note.0,
+                                            &mut indices,
+                                        ) {
+                                            return None;
+                                        }
                                         state.select_mode = SelectMode::Many(Some(indices));
                                         return Some(Snapshot::from_states(s0, state));
                                     }
@@ -282,9 +372,9 @@ impl Panel for Select {
                                     {
                                         Some(next_note) => {
                                             let s0 = state.clone();
-                                            // Remove the note.
+                                            // Add the note.
                                             let mut indices = indices.clone();
-                                            indices.push(next_note.0);
+                                            self.add_indices(track, next_note.0, &mut indices);
                                             state.select_mode = SelectMode::Many(Some(indices));
                                             return Some(Snapshot::from_states(s0, state));
                                         }
@@ -314,8 +404,40 @@ impl Panel for Select {
             }
         }
     }
+}
+
+impl Panel for Select {
+    fn update(
+        &mut self,
+        state: &mut State,
+        _: &mut Conn,
+        input: &Input,
+        tts: &mut TTS,
+        text: &Text,
+        _: &mut PathsState,
+    ) -> Option<Snapshot> {
+        // Invert the selection.
+        if input.happened(&InputEvent::InvertSelection) {
+            return Select::invert_selection(state, tts, text);
+        }
+        // Select the notes at the cursor.
+        if input.happened(&InputEvent::SelectColumn) {
+            return Select::select_column(state, tts, text);
+        }
+        let snapshot = self.update_selection(state, input);
+        // Say how many notes are selected.
+        if self.snap_to_notes && snapshot.is_some() {
+            if let SelectMode::Many(Some(indices)) = &state.select_mode {
+                tts.enqueue(text.get_with_values(
+                    "PIANO_ROLL_PANEL_TTS_SELECTED_COUNT",
+                    &[&indices.len().to_string()],
+                ));
+            }
+        }
+        snapshot
+    }
 
-    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn) {}
+    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn, _: &mut TTS, _: &Text) {}
 
     fn update_abc123(
         &mut self,
@@ -344,7 +466,10 @@ impl PianoRollSubPanel for Select {
                         let note = notes[*index];
                         TtsString::from(text.get_with_values(
                             "PIANO_ROLL_PANEL_STATUS_TTS_SELECTED_SINGLE",
-                            &[&note.note.to_string(), &text.get_ppq_tts(&note.start)],
+                            &[
+                                &note.note.to_string(),
+                                &text.get_position_tts(note.start, &state.time),
+                            ],
                         ))
                     }
                     None => TtsString::from(text.get_error("The selected note doesn't exist.")),
@@ -356,7 +481,10 @@ impl PianoRollSubPanel for Select {
                     Some(min) => match notes.iter().map(|n| n.end).max() {
                         Some(max) => TtsString::from(text.get_with_values(
                             "PIANO_ROLL_PANEL_STATUS_TTS_SELECTED_MANY",
-                            &[&text.get_ppq_tts(&min), &text.get_ppq_tts(&max)],
+                            &[
+                                &text.get_position_tts(min, &state.time),
+                                &text.get_position_tts(max, &state.time),
+                            ],
                         )),
                         None => TtsString::from(
                             text.get_error("There is no end time to the selection."),
@@ -425,6 +553,26 @@ impl PianoRollSubPanel for Select {
                     .clone(),
             );
         }
+        tts_strings.push(
+            self.tooltips
+                .get_tooltip(
+                    "PIANO_ROLL_PANEL_INPUT_TTS_SELECT_COLUMN",
+                    &[InputEvent::SelectColumn],
+                    input,
+                    text,
+                )
+                .clone(),
+        );
+        tts_strings.push(
+            self.tooltips
+                .get_tooltip(
+                    "PIANO_ROLL_PANEL_INPUT_TTS_INVERT_SELECTION",
+                    &[InputEvent::InvertSelection],
+                    input,
+                    text,
+                )
+                .clone(),
+        );
         if selected {
             tts_strings.push(
                 self.tooltips
@@ -1,5 +1,7 @@
 use super::{get_edit_mode_status_tts, EditModeDeltas, PianoRollSubPanel};
 use crate::panel::*;
+use common::config::parse;
+use common::{Note, U64orF32, MAX_BPM, MIN_BPM, PPQ_F};
 use ini::Ini;
 
 /// The piano roll time sub-panel.
@@ -7,13 +9,29 @@ pub(super) struct Time {
     /// Time values and deltas.
     deltas: EditModeDeltas,
     tooltips: Tooltips,
+    /// The user-typed "go to" time or number of beats.
+    typed: String,
+    /// If true, the user is typing the number of beats that the selection should last. If false, the user is typing a "go to" time.
+    fit_tempo: bool,
+    /// If true, moving the cursor plays the notes that it moves onto.
+    pub(super) scrub: bool,
+    /// Scrubbed notes are played at this velocity, or at their own velocity if it's lower.
+    scrub_velocity: u8,
 }
 
 impl Time {
     pub fn new(config: &Ini) -> Self {
+        let scrub_velocity = parse(
+            config.section(Some("PIANO_ROLL")).unwrap(),
+            "scrub_velocity",
+        );
         Self {
             deltas: EditModeDeltas::new(config),
             tooltips: Tooltips::default(),
+            typed: String::new(),
+            fit_tempo: false,
+            scrub: false,
+            scrub_velocity,
         }
     }
 
@@ -54,6 +72,149 @@ impl Time {
         Some(Snapshot::from_states(s0, state))
     }
 
+    /// Set the punch-in or punch-out time to the cursor and announce the punch region.
+    fn set_punch(
+        state: &mut State,
+        tts: &mut TTS,
+        text: &Text,
+        punch_in: bool,
+    ) -> Option<Snapshot> {
+        let s0 = state.clone();
+        if punch_in {
+            state.punch_in = Some(state.time.cursor);
+        } else {
+            state.punch_out = Some(state.time.cursor);
+        }
+        tts.enqueue(Time::get_punch_tts(state, text));
+        Some(Snapshot::from_states(s0, state))
+    }
+
+    /// Returns a description of the punch region.
+    fn get_punch_tts(state: &State, text: &Text) -> String {
+        match state.get_punch() {
+            Some([t0, t1]) => {
+                let t1 = if t1 == u64::MAX {
+                    text.get("PIANO_ROLL_PANEL_TTS_PUNCH_END")
+                } else {
+                    text.get_position_tts(t1, &state.time)
+                };
+                text.get_with_values(
+                    "PIANO_ROLL_PANEL_TTS_PUNCH",
+                    &[&text.get_position_tts(t0, &state.time), &t1],
+                )
+            }
+            None => text.get("PIANO_ROLL_PANEL_TTS_NO_PUNCH"),
+        }
+    }
+
+    /// Parse a user-typed "go to" time. This is either a beat e.g. `12` or `12.5`, or seconds e.g. `30s`.
+    /// Beats are relative to the start of the song and can be negative in the pickup. Seconds are relative to the start of the music.
+    ///
+    /// Returns the time in PPQ, or None if the string isn't a valid time.
+    fn parse_go_to(go_to: &str, state: &State) -> Option<u64> {
+        let go_to = go_to.trim();
+        let (value, seconds) = match go_to.strip_suffix(['s', 'S']) {
+            Some(value) => (value.trim(), true),
+            None => (go_to, false),
+        };
+        match value.parse::<f32>() {
+            Ok(v) if v.is_finite() && seconds && v >= 0.0 => Some(state.time.seconds_to_ppq(v)),
+            Ok(v) if v.is_finite() && !seconds => {
+                let t = state.time.song_start as f32 + v * PPQ_F;
+                if t >= 0.0 {
+                    Some(t as u64)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the end time of the music in PPQ.
+    fn get_music_end(state: &State) -> u64 {
+        state
+            .music
+            .midi_tracks
+            .iter()
+            .filter_map(|t| t.get_end())
+            .max()
+            .unwrap_or(state.view.dt[1])
+    }
+
+    /// Set the BPM so that the selected notes last `beats` beats.
+    /// Every note and time is rescaled too, so that the music sounds the same but lines up with the new beats.
+    ///
+    /// Returns the new BPM, or None if `beats` isn't a valid number of beats or if no notes are selected.
+    fn fit_tempo(beats: &str, state: &mut State) -> Option<u64> {
+        let beats = match beats.trim().parse::<f32>() {
+            Ok(beats) if beats.is_finite() && beats > 0.0 => beats,
+            _ => return None,
+        };
+        let notes = state.select_mode.get_notes(&state.music)?;
+        let t0 = notes.iter().map(|n| n.start).min()?;
+        let t1 = notes.iter().map(|n| n.end).max()?;
+        if t1 <= t0 {
+            return None;
+        }
+        // The selection should last `beats` beats in the same number of seconds.
+        let bpm0 = state.time.bpm.get_f();
+        let bpm =
+            ((bpm0 * beats * PPQ_F / (t1 - t0) as f32).round() as u64).clamp(MIN_BPM, MAX_BPM);
+        let scale = bpm as f64 / bpm0 as f64;
+        let rescale = |t: u64| (t as f64 * scale).round() as u64;
+        for track in state.music.midi_tracks.iter_mut() {
+            for note in track.notes.iter_mut() {
+                note.start = rescale(note.start);
+                note.end = rescale(note.end).max(note.start + 1);
+            }
+            for point in track.volume_envelope.iter_mut() {
+                point.0 = rescale(point.0);
+            }
+            // Breakpoints that were rounded onto the same time are merged.
+            track.volume_envelope.dedup_by_key(|p| p.0);
+        }
+        state.time.cursor = rescale(state.time.cursor);
+        state.time.playback = rescale(state.time.playback);
+        state.time.loop_start = rescale(state.time.loop_start);
+        state.time.loop_end = rescale(state.time.loop_end);
+        state.time.song_start = rescale(state.time.song_start);
+        state.punch_in = state.punch_in.map(rescale);
+        state.punch_out = state.punch_out.map(rescale);
+        state.time.bpm = U64orF32::from(bpm);
+        Some(bpm)
+    }
+
+    /// Returns the notes of the selected track that the cursor moved onto: Notes that contain `t1` but not `t0`.
+    /// If the cursor moved forward, this includes notes that the cursor skipped over: Notes that start after `t0` and at or before `t1`.
+    fn get_scrubbed_notes(state: &State, t0: u64, t1: u64) -> Vec<Note> {
+        match state.music.get_selected_track() {
+            Some(track) => track
+                .notes
+                .iter()
+                .filter(|n| {
+                    !(n.start..n.end).contains(&t0)
+                        && ((n.start..n.end).contains(&t1) || (t0 < n.start && n.start <= t1))
+                })
+                .copied()
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Quietly play the notes that the cursor moved onto.
+    /// `Conn` releases the previous scrubbed notes first, so moving the cursor quickly doesn't stack voices.
+    fn scrub(&self, state: &State, conn: &mut Conn, cursor: u64) {
+        if state.time.cursor == cursor {
+            return;
+        }
+        let notes: Vec<(u8, u8)> = Time::get_scrubbed_notes(state, cursor, state.time.cursor)
+            .iter()
+            .map(|n| (n.note, n.velocity.min(self.scrub_velocity)))
+            .collect();
+        conn.preview_notes(state, &notes);
+    }
+
     /// Round a time off to the nearest beat.
     fn get_nearest_beat(t: u64, state: &State) -> u64 {
         ((t as f32 / state.input.beat.get_f()).ceil() * state.input.beat.get_f()) as u64
@@ -64,14 +225,15 @@ impl Panel for Time {
     fn update(
         &mut self,
         state: &mut State,
-        _: &mut Conn,
+        conn: &mut Conn,
         input: &Input,
-        _: &mut TTS,
-        _: &Text,
+        tts: &mut TTS,
+        text: &Text,
         _: &mut PathsState,
     ) -> Option<Snapshot> {
+        let cursor = state.time.cursor;
         // Do nothing if there is no track.
-        if state.music.selected.is_none() {
+        let snapshot = if state.music.selected.is_none() {
             None
         }
         // Cycle the mode.
@@ -136,24 +298,109 @@ impl Panel for Time {
                 Time::get_nearest_beat(state.time.playback, state),
                 state,
             ))
+        }
+        // Type a time.
+        else if input.happened(&InputEvent::TimeGoTo) {
+            self.typed.clear();
+            self.fit_tempo = false;
+            tts.enqueue(text.get_ref("PIANO_ROLL_PANEL_TTS_TIME_GO_TO"));
+            Some(Snapshot::from_state_value(
+                |s| &mut s.input.alphanumeric_input,
+                true,
+                state,
+            ))
+        }
+        // Type the number of beats that the selection should last.
+        else if input.happened(&InputEvent::TimeFitTempo) {
+            if state.select_mode.get_note_indices().is_none() {
+                tts.enqueue(text.get_ref("PIANO_ROLL_PANEL_STATUS_TTS_NO_SELECTION"));
+                return None;
+            }
+            self.typed.clear();
+            self.fit_tempo = true;
+            tts.enqueue(text.get_ref("PIANO_ROLL_PANEL_TTS_TIME_FIT_TEMPO"));
+            Some(Snapshot::from_state_value(
+                |s| &mut s.input.alphanumeric_input,
+                true,
+                state,
+            ))
+        }
+        // Set the punch region.
+        else if input.happened(&InputEvent::TimePunchIn) {
+            Time::set_punch(state, tts, text, true)
+        } else if input.happened(&InputEvent::TimePunchOut) {
+            Time::set_punch(state, tts, text, false)
+        }
+        // Start the song at the cursor.
+        else if input.happened(&InputEvent::TimeSetSongStart) {
+            tts.enqueue(text.get_ref("PIANO_ROLL_PANEL_TTS_SONG_START"));
+            Some(Snapshot::from_state_value(
+                |s| &mut s.time.song_start,
+                state.time.cursor,
+                state,
+            ))
+        } else if input.happened(&InputEvent::TimeClearPunch) {
+            let s0 = state.clone();
+            state.punch_in = None;
+            state.punch_out = None;
+            tts.enqueue(Time::get_punch_tts(state, text));
+            Some(Snapshot::from_states(s0, state))
+        }
+        // Toggle scrubbing.
+        else if input.happened(&InputEvent::TimeToggleScrub) {
+            self.scrub = !self.scrub;
+            tts.enqueue(text.get_ref(if self.scrub {
+                "PIANO_ROLL_PANEL_TTS_SCRUB_ON"
+            } else {
+                "PIANO_ROLL_PANEL_TTS_SCRUB_OFF"
+            }));
+            None
         } else {
             None
+        };
+        if self.scrub {
+            self.scrub(state, conn, cursor);
         }
+        snapshot
     }
 
-    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn) {}
+    fn on_disable_abc123(&mut self, state: &mut State, _: &mut Conn, tts: &mut TTS, text: &Text) {
+        if self.fit_tempo {
+            match Time::fit_tempo(&self.typed, state) {
+                Some(bpm) => tts.enqueue(text.get_with_values(
+                    "PIANO_ROLL_PANEL_TTS_TIME_FIT_TEMPO_BPM",
+                    &[&bpm.to_string()],
+                )),
+                None => tts.enqueue(text.get_ref("PIANO_ROLL_PANEL_TTS_TIME_FIT_TEMPO_INVALID")),
+            }
+            self.typed.clear();
+            return;
+        }
+        match Time::parse_go_to(&self.typed, state) {
+            Some(t) => {
+                state.time.cursor = t.min(Time::get_music_end(state));
+                tts.enqueue(text.get_with_values(
+                    "PIANO_ROLL_PANEL_TTS_TIME_GO_TO_CURSOR",
+                    &[&text.get_time(state.time.cursor, &state.time)],
+                ));
+            }
+            None => tts.enqueue(text.get_ref("PIANO_ROLL_PANEL_TTS_TIME_GO_TO_INVALID")),
+        }
+        self.typed.clear();
+    }
 
     fn update_abc123(
         &mut self,
         _: &mut State,
-        _: &Input,
+        input: &Input,
         _: &mut Conn,
     ) -> (Option<Snapshot>, bool) {
-        (None, false)
+        (None, input.modify_string_abc123(&mut self.typed))
     }
 
-    fn allow_alphanumeric_input(&self, _: &State, _: &Conn) -> bool {
-        false
+    fn allow_alphanumeric_input(&self, state: &State, _: &Conn) -> bool {
+        // Alphanumeric input is enabled with `InputEvent::TimeGoTo` or `InputEvent::TimeFitTempo`, so only allow the user to toggle it off.
+        state.input.alphanumeric_input
     }
 
     fn allow_play_music(&self) -> bool {
@@ -167,14 +414,28 @@ impl PianoRollSubPanel for Time {
         s.push(TtsString::from(text.get_with_values(
             "PIANO_ROLL_PANEL_STATUS_TTS_TIME",
             &[
-                &text.get_ppq_tts(&state.time.cursor),
-                &text.get_ppq_tts(&state.time.playback),
+                &text.get_position_tts(state.time.cursor, &state.time),
+                &text.get_position_tts(state.time.playback, &state.time),
             ],
         )));
+        s.push(TtsString::from(Time::get_punch_tts(state, text)));
         s
     }
 
-    fn get_input_tts(&mut self, _: &State, input: &Input, text: &Text) -> Vec<TtsString> {
+    fn get_input_tts(&mut self, state: &State, input: &Input, text: &Text) -> Vec<TtsString> {
+        if state.input.alphanumeric_input {
+            let key = if self.fit_tempo {
+                "PIANO_ROLL_PANEL_INPUT_TTS_TIME_FIT_TEMPO_ABC123"
+            } else {
+                "PIANO_ROLL_PANEL_INPUT_TTS_TIME_GO_TO_ABC123"
+            };
+            return vec![self.tooltips.get_tooltip(
+                key,
+                &[InputEvent::ToggleAlphanumericInput],
+                input,
+                text,
+            )];
+        }
         vec![
             self.tooltips.get_tooltip(
                 "PIANO_ROLL_PANEL_INPUT_TTS_TIME_0",
@@ -224,6 +485,139 @@ impl PianoRollSubPanel for Time {
                 input,
                 text,
             ),
+            self.tooltips.get_tooltip(
+                "PIANO_ROLL_PANEL_INPUT_TTS_TIME_8",
+                &[
+                    InputEvent::TimePunchIn,
+                    InputEvent::TimePunchOut,
+                    InputEvent::TimeClearPunch,
+                ],
+                input,
+                text,
+            ),
+            self.tooltips.get_tooltip(
+                "PIANO_ROLL_PANEL_INPUT_TTS_TIME_SONG_START",
+                &[InputEvent::TimeSetSongStart],
+                input,
+                text,
+            ),
+            self.tooltips.get_tooltip(
+                "PIANO_ROLL_PANEL_INPUT_TTS_TIME_GO_TO",
+                &[InputEvent::TimeGoTo],
+                input,
+                text,
+            ),
+            self.tooltips.get_tooltip(
+                "PIANO_ROLL_PANEL_INPUT_TTS_TIME_FIT_TEMPO",
+                &[InputEvent::TimeFitTempo],
+                input,
+                text,
+            ),
+            self.tooltips.get_tooltip(
+                if self.scrub {
+                    "PIANO_ROLL_PANEL_INPUT_TTS_TIME_SCRUB_OFF"
+                } else {
+                    "PIANO_ROLL_PANEL_INPUT_TTS_TIME_SCRUB_ON"
+                },
+                &[InputEvent::TimeToggleScrub],
+                input,
+                text,
+            ),
         ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Time;
+    use common::{SelectMode, State, MAX_BPM, PPQ_U};
+    use ini::Ini;
+
+    #[test]
+    fn time_go_to() {
+        let state = State::new(&Ini::load_from_file("../data/config.ini").unwrap());
+        assert_eq!(Time::parse_go_to("0", &state), Some(0));
+        assert_eq!(Time::parse_go_to("12", &state), Some(PPQ_U * 12));
+        assert_eq!(Time::parse_go_to("1.5", &state), Some(PPQ_U + PPQ_U / 2));
+        // At 120 BPM, 1 second is 2 beats.
+        assert_eq!(Time::parse_go_to("1s", &state), Some(PPQ_U * 2));
+        assert_eq!(Time::parse_go_to(" 30 s", &state), Some(PPQ_U * 60));
+        assert_eq!(Time::parse_go_to("", &state), None);
+        assert_eq!(Time::parse_go_to("s", &state), None);
+        assert_eq!(Time::parse_go_to("-1", &state), None);
+        assert_eq!(Time::parse_go_to("abc", &state), None);
+        // Beats are relative to the start of the song, but seconds aren't.
+        let mut state = state;
+        state.time.song_start = PPQ_U;
+        assert_eq!(Time::parse_go_to("0", &state), Some(PPQ_U));
+        assert_eq!(Time::parse_go_to("-1", &state), Some(0));
+        assert_eq!(Time::parse_go_to("-2", &state), None);
+        assert_eq!(Time::parse_go_to("1s", &state), Some(PPQ_U * 2));
+    }
+
+    #[test]
+    fn time_scrub() {
+        let mut state = State::new(&Ini::load_from_file("../data/config.ini").unwrap());
+        state
+            .music
+            .add_track(0)
+            .add_note(60, 127, 0, PPQ_U * 2)
+            .add_note(64, 127, PPQ_U, PPQ_U * 2)
+            .add_note(67, 127, PPQ_U * 2, PPQ_U * 3)
+            .add_note(72, 127, PPQ_U * 4, PPQ_U * 4 + 1);
+        state.music.selected = Some(0);
+        let keys = |t0, t1| {
+            Time::get_scrubbed_notes(&state, t0, t1)
+                .iter()
+                .map(|n| n.note)
+                .collect::<Vec<u8>>()
+        };
+        // The cursor moves onto the first note.
+        assert_eq!(keys(PPQ_U * 5, 0), vec![60]);
+        // The first note is still sounding.
+        assert_eq!(keys(0, PPQ_U), vec![64]);
+        assert_eq!(keys(PPQ_U, PPQ_U + 1), Vec::<u8>::new());
+        // Note ends are exclusive.
+        assert_eq!(keys(PPQ_U, PPQ_U * 2), vec![67]);
+        assert_eq!(keys(PPQ_U * 2, PPQ_U * 3), Vec::<u8>::new());
+        // The cursor skips over a short note.
+        assert_eq!(keys(PPQ_U * 3, PPQ_U * 5), vec![72]);
+        assert_eq!(keys(PPQ_U * 4, PPQ_U * 5), Vec::<u8>::new());
+        assert_eq!(keys(0, PPQ_U * 5), vec![64, 67, 72]);
+        // Skipped notes aren't played if the cursor moves backward.
+        assert_eq!(keys(PPQ_U * 5, PPQ_U * 3), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn time_fit_tempo() {
+        let mut state = State::new(&Ini::load_from_file("../data/config.ini").unwrap());
+        state.music.add_track(0).add_note(60, 127, 0, PPQ_U * 3);
+        state
+            .music
+            .add_track(1)
+            .add_note(60, 127, PPQ_U * 3, PPQ_U * 6);
+        state.music.midi_tracks[1].set_volume_point(PPQ_U * 3, 64);
+        state.music.midi_tracks[1].set_volume_point(PPQ_U * 6, 127);
+        state.music.selected = Some(0);
+        // There is no selection.
+        assert_eq!(Time::fit_tempo("2", &mut state), None);
+        state.select_mode = SelectMode::Single(Some(0));
+        assert_eq!(Time::fit_tempo("abc", &mut state), None);
+        assert_eq!(Time::fit_tempo("0", &mut state), None);
+        state.time.cursor = PPQ_U * 6;
+        // Three beats at 120 BPM last as long as two beats at 80 BPM.
+        assert_eq!(Time::fit_tempo("2", &mut state), Some(80));
+        assert_eq!(state.time.bpm.get_u(), 80);
+        assert_eq!(state.music.midi_tracks[0].notes[0].end, PPQ_U * 2);
+        // Every note and time is rescaled.
+        assert_eq!(state.music.midi_tracks[1].notes[0].start, PPQ_U * 2);
+        assert_eq!(state.music.midi_tracks[1].notes[0].end, PPQ_U * 4);
+        assert_eq!(state.time.cursor, PPQ_U * 4);
+        assert_eq!(
+            state.music.midi_tracks[1].volume_envelope,
+            vec![(PPQ_U * 2, 64), (PPQ_U * 4, 127)]
+        );
+        // The BPM is clamped.
+        assert_eq!(Time::fit_tempo("10000", &mut state), Some(MAX_BPM));
+    }
+}
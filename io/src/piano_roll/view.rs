@@ -2,7 +2,9 @@ use super::{
     get_cycle_edit_mode_input_tts, get_edit_mode_status_tts, EditModeDeltas, PianoRollSubPanel,
 };
 use crate::panel::*;
+use common::config::parse_fractions;
 use common::sizes::get_viewport_size;
+use common::PPQ_F;
 use ini::Ini;
 use text::Tooltips;
 
@@ -12,6 +14,8 @@ pub(super) struct View {
     deltas: EditModeDeltas,
     /// The default viewport dt.
     dt_0: u64,
+    /// The gridline subdivisions that we can cycle through as PPQ values.
+    grid_subdivisions: Vec<u64>,
     tooltips: Tooltips,
 }
 
@@ -19,9 +23,17 @@ impl View {
     pub fn new(config: &Ini) -> Self {
         let viewport_size = get_viewport_size(config);
         let dt_0 = viewport_size[0] as u64;
+        let section = config.section(Some("PIANO_ROLL")).unwrap();
+        let mut grid_subdivisions: Vec<u64> = parse_fractions(section, "beats")
+            .iter()
+            .map(|f| (*f * PPQ_F) as u64)
+            .collect();
+        grid_subdivisions.sort();
+        grid_subdivisions.dedup();
         Self {
             deltas: EditModeDeltas::new(config),
             dt_0,
+            grid_subdivisions,
             tooltips: Tooltips::default(),
         }
     }
@@ -51,6 +63,36 @@ impl View {
         state.view.zoom(zoom_in);
         Some(Snapshot::from_states(s0, state))
     }
+
+    /// Set the distance between gridlines to the previous or next subdivision.
+    fn set_grid_subdivision(
+        &self,
+        state: &mut State,
+        tts: &mut TTS,
+        text: &Text,
+        up: bool,
+    ) -> Option<Snapshot> {
+        let g0 = state.view.grid_subdivision;
+        let g1 = if up {
+            self.grid_subdivisions.iter().find(|g| **g > g0)
+        } else {
+            self.grid_subdivisions.iter().rev().find(|g| **g < g0)
+        };
+        match g1 {
+            Some(g1) => {
+                tts.enqueue(text.get_with_values(
+                    "PIANO_ROLL_PANEL_TTS_GRID_SUBDIVISION",
+                    &[&text.get_ppq_tts(g1)],
+                ));
+                Some(Snapshot::from_state_value(
+                    |s| &mut s.view.grid_subdivision,
+                    *g1,
+                    state,
+                ))
+            }
+            None => None,
+        }
+    }
 }
 
 impl Panel for View {
@@ -59,8 +101,8 @@ impl Panel for View {
         state: &mut State,
         _: &mut Conn,
         input: &Input,
-        _: &mut TTS,
-        _: &Text,
+        tts: &mut TTS,
+        text: &Text,
         _: &mut PathsState,
     ) -> Option<Snapshot> {
         // Do nothing if there is no track.
@@ -132,12 +174,18 @@ impl Panel for View {
                 [state.view.dt[0], state.view.dt[0] + self.dt_0],
                 state,
             ))
+        }
+        // Set the gridline subdivision.
+        else if input.happened(&InputEvent::ViewGridSubdivisionLeft) {
+            self.set_grid_subdivision(state, tts, text, false)
+        } else if input.happened(&InputEvent::ViewGridSubdivisionRight) {
+            self.set_grid_subdivision(state, tts, text, true)
         } else {
             None
         }
     }
 
-    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn) {}
+    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn, _: &mut TTS, _: &Text) {}
 
     fn update_abc123(
         &mut self,
@@ -159,13 +207,23 @@ impl Panel for View {
 
 impl PianoRollSubPanel for View {
     fn get_status_tts(&mut self, state: &State, text: &Text) -> Vec<TtsString> {
+        let (n0, n1) = match state.music.get_selected_track() {
+            Some(track) => (
+                text.get_channel_note_name(state.view.dn[0], track.channel),
+                text.get_channel_note_name(state.view.dn[1], track.channel),
+            ),
+            None => (
+                text.get_note_name(state.view.dn[0]),
+                text.get_note_name(state.view.dn[1]),
+            ),
+        };
         let mut s = vec![TtsString::from(text.get_with_values(
             "PIANO_ROLL_PANEL_STATUS_TTS_VIEW",
             &[
-                &text.get_ppq_tts(&state.view.dt[0]),
-                &text.get_ppq_tts(&state.view.dt[1]),
-                text.get_note_name(state.view.dn[0]),
-                text.get_note_name(state.view.dn[1]),
+                &text.get_position_tts(state.view.dt[0], &state.time),
+                &text.get_position_tts(state.view.dt[1], &state.time),
+                n0,
+                n1,
             ],
         ))];
         s.push(get_edit_mode_status_tts(state.view.mode.get_ref(), text));
@@ -220,6 +278,15 @@ impl PianoRollSubPanel for View {
                 input,
                 text,
             ),
+            self.tooltips.get_tooltip(
+                "PIANO_ROLL_PANEL_INPUT_TTS_VIEW_GRID_SUBDIVISION",
+                &[
+                    InputEvent::ViewGridSubdivisionLeft,
+                    InputEvent::ViewGridSubdivisionRight,
+                ],
+                input,
+                text,
+            ),
         ]);
         s.push(get_cycle_edit_mode_input_tts(
             &mut self.tooltips,
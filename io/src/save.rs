@@ -4,7 +4,7 @@ use common::{PathsState, State};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::{from_str, to_string, Error};
-use std::fs::{File, OpenOptions};
+use std::fs::{copy, read_dir, remove_file, rename, File, OpenOptions};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 
@@ -28,21 +28,48 @@ pub(crate) struct Save {
 }
 
 impl Save {
-    /// Write this state to a file.
-    ///
-    /// - `path` The path we will write to.
     /// - `state` The app state.
-    /// - `conn` The audio connection. Its `SynthState` will be serialized.
+    /// - `conn` The audio connection. Its `SynthState` and `Exporter` will be serialized.
     /// - `paths_state` The paths state.
-    pub fn write(path: &PathBuf, state: &State, conn: &Conn, paths_state: &PathsState) {
-        // Convert the state to something that can be serialized.
-        let save = Save {
+    fn new(state: &State, conn: &Conn, paths_state: &PathsState) -> Self {
+        Self {
             state: state.clone(),
             synth_state: conn.state.clone(),
             paths_state: paths_state.clone(),
             exporter: conn.exporter.clone(),
             version: common::VERSION.to_string(),
-        };
+        }
+    }
+
+    /// Serialize the state without writing anything to disk. Returns an error if the state can't be serialized.
+    ///
+    /// - `state` The app state.
+    /// - `conn` The audio connection. Its `SynthState` and `Exporter` will be serialized.
+    /// - `paths_state` The paths state.
+    pub fn validate(state: &State, conn: &Conn, paths_state: &PathsState) -> Result<(), Error> {
+        to_string(&Save::new(state, conn, paths_state)).map(|_| ())
+    }
+
+    /// Write this state to a file.
+    ///
+    /// - `path` The path we will write to.
+    /// - `state` The app state.
+    /// - `conn` The audio connection. Its `SynthState` will be serialized.
+    /// - `paths_state` The paths state.
+    /// - `backups` If this is greater than 0 and the file already exists, the file is copied to a backup first. See `rotate_backups`.
+    pub fn write(
+        path: &PathBuf,
+        state: &State,
+        conn: &Conn,
+        paths_state: &PathsState,
+        backups: usize,
+    ) {
+        // Convert the state to something that can be serialized.
+        let save = Save::new(state, conn, paths_state);
+        // Back up the previous file.
+        if backups > 0 && path.exists() {
+            Self::rotate_backups(path, backups);
+        }
         // Try to open the file.
         match OpenOptions::new()
             .write(true)
@@ -64,6 +91,51 @@ impl Save {
         }
     }
 
+    /// Copy a file to `<path>.bak.1`. The previous `<path>.bak.1` becomes `<path>.bak.2`, and so on.
+    /// Backups numbered higher than `backups` are deleted.
+    ///
+    /// - `path` The path of the save file.
+    /// - `backups` The maximum number of backups.
+    fn rotate_backups(path: &Path, backups: usize) {
+        // Delete the oldest backups.
+        let prefix = format!("{}.", Self::get_backup_path(path, 0).to_str().unwrap());
+        if let Ok(entries) = read_dir(path.parent().unwrap()) {
+            for entry in entries.flatten() {
+                let other = entry.path();
+                if let Some(index) = other
+                    .to_str()
+                    .and_then(|p| p.strip_prefix(&prefix))
+                    .and_then(|index| index.parse::<usize>().ok())
+                {
+                    if index >= backups && remove_file(&other).is_err() {
+                        println!("Failed to remove backup {:?}", other);
+                    }
+                }
+            }
+        }
+        // Shift the other backups.
+        for index in (1..backups).rev() {
+            let from = Self::get_backup_path(path, index);
+            if from.exists() && rename(&from, Self::get_backup_path(path, index + 1)).is_err() {
+                println!("Failed to rotate backup {:?}", from);
+            }
+        }
+        // Back up the file.
+        if copy(path, Self::get_backup_path(path, 1)).is_err() {
+            println!("Failed to back up {:?}", path);
+        }
+    }
+
+    /// Returns the path of a backup file. If `index` is 0, this returns the path without the index.
+    fn get_backup_path(path: &Path, index: usize) -> PathBuf {
+        let mut p = path.as_os_str().to_os_string();
+        p.push(".bak");
+        if index > 0 {
+            p.push(format!(".{}", index));
+        }
+        PathBuf::from(p)
+    }
+
     /// Load a file and deserialize.
     ///
     /// - `path` The path we read from.
@@ -77,7 +149,7 @@ impl Save {
                 match file.read_to_string(&mut string) {
                     Ok(_) => {
                         // Repair the save file if needed.
-                        let string = Self::fix_no_flac(&string);
+                        let string = Self::fix(&string);
                         let q: Result<Save, Error> = from_str(&string);
                         match q {
                             Ok(s) => {
@@ -87,8 +159,13 @@ impl Save {
                                 // Set the paths.
                                 *paths_state = s.paths_state;
 
-                                // Set the exporter.
+                                // Set the exporter. Its framerate is only used for exporting.
                                 conn.exporter = s.exporter;
+                                conn.set_player_framerate();
+
+                                // The monitor gain and mono monitoring aren't part of the project.
+                                conn.set_monitor_gain(0);
+                                conn.set_mono_monitor(false);
 
                                 // Set the synthesizer.
                                 // Set the gain.
@@ -136,15 +213,223 @@ impl Save {
         }
     }
 
+    /// Repair a save file that was written by an older version.
+    fn fix(string: &str) -> String {
+        Self::fix_normalization(&Self::fix_no_opus(&Self::fix_no_flac(string)))
+    }
+
     /// Fix the export types if this is pre-0.1.3, which didn't have Flac exporting.
     /// This apparently isn't possible to fix in Exporter via serde.
     fn fix_no_flac(string: &str) -> String {
         let re = Regex::new(r#"(("export_type":\{"values":\["Wav","Mid","MP3","Ogg"\],"index":\{"index":)([0-9]),"length":4\}\})"#).unwrap();
         re.replace(string, r#""export_type":{"values":["Wav","Mid","MP3","Ogg","Flac"],"index":{"index":0,"length":5}}"#).into()
     }
+
+    /// Fix the export types if this save file predates Opus exporting. The selected export type is kept.
+    fn fix_no_opus(string: &str) -> String {
+        let re = Regex::new(r#""export_type":\{"values":\["Wav","Mid","MP3","Ogg","Flac"\],"index":\{"index":([0-9]),"length":5\}\}"#).unwrap();
+        re.replace(string, r#""export_type":{"values":["Wav","Mid","MP3","Ogg","Flac","Opus"],"index":{"index":$1,"length":6}}"#).into()
+    }
+
+    /// Fix the normalization settings if this save file predates `Exporter.normalization`.
+    /// Older save files have lists of loudness and peak targets that start with None.
+    /// If both targets were set, the peak target was applied last, so the normalization mode is peak normalization.
+    fn fix_normalization(string: &str) -> String {
+        let loudness_re =
+            Regex::new(r#""loudness_target":\{"index":([0-9]),"length":5\}"#).unwrap();
+        let peak_re = Regex::new(r#""peak_target":\{"index":([0-9]),"length":5\}"#).unwrap();
+        let loudness: usize = match loudness_re.captures(string) {
+            Some(captures) => captures[1].parse().unwrap(),
+            None => return string.to_string(),
+        };
+        // Save files from before peak normalization don't have a peak target.
+        let peak: usize = match peak_re.captures(string) {
+            Some(captures) => captures[1].parse().unwrap(),
+            None => 0,
+        };
+        let normalization = if peak > 0 {
+            2
+        } else if loudness > 0 {
+            1
+        } else {
+            0
+        };
+        // If a target was None, use the default target.
+        let exporter = Exporter::default();
+        let loudness = loudness
+            .checked_sub(1)
+            .unwrap_or(exporter.loudness_target.get());
+        let peak = peak.checked_sub(1).unwrap_or(exporter.peak_target.get());
+        let string = loudness_re.replace(string, format!(r#""loudness_target":{{"index":{},"length":4}},"normalization":{{"values":["None","Loudness","Peak"],"index":{{"index":{},"length":3}}}}"#, loudness, normalization));
+        peak_re
+            .replace(
+                &string,
+                format!(r#""peak_target":{{"index":{},"length":4}}"#, peak),
+            )
+            .into()
+    }
 }
 
 /// Pre-0.1.3, the version isn't in the save file. This is the default version.
 fn default_version() -> String {
     "0.1.2".to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Save;
+    use audio::export::{ExportType, Normalization};
+    use audio::exporter::Exporter;
+    use audio::Conn;
+    use common::{PathsState, State};
+    use ini::Ini;
+    use serde_json::from_str;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, read_dir, read_to_string, remove_dir_all, write};
+
+    #[test]
+    fn save_validate() {
+        let config = Ini::load_from_file("../data/config.ini").unwrap();
+        let mut state = State::new(&config);
+        state.music.add_track(0).add_note(60, 127, 0, common::PPQ_U);
+        let conn = Conn::default();
+        assert!(Save::validate(&state, &conn, &PathsState::default()).is_ok());
+    }
+
+    #[test]
+    fn save_export_type() {
+        let conn = Conn::default();
+        // A save file from before .opus exporting, with .flac selected.
+        let string = serde_json::to_string(&conn.exporter).unwrap().replace(
+            r#""export_type":{"values":["Wav","Mid","MP3","Ogg","Flac","Opus"],"index":{"index":0,"length":6}}"#,
+            r#""export_type":{"values":["Wav","Mid","MP3","Ogg","Flac"],"index":{"index":4,"length":5}}"#,
+        );
+        assert!(from_str::<Exporter>(&string).is_err());
+        let string = Save::fix_no_opus(&string);
+        let exporter: Exporter = from_str(&string).unwrap();
+        assert_eq!(exporter.export_type.get(), ExportType::Flac);
+        assert_eq!(exporter.export_type.get_values().0.len(), 6);
+        // A save file from before .flac exporting.
+        let string =
+            r#""export_type":{"values":["Wav","Mid","MP3","Ogg"],"index":{"index":2,"length":4}}"#;
+        assert_eq!(
+            Save::fix_no_opus(&Save::fix_no_flac(string)),
+            r#""export_type":{"values":["Wav","Mid","MP3","Ogg","Flac","Opus"],"index":{"index":0,"length":6}}"#
+        );
+    }
+
+    #[test]
+    fn save_normalization() {
+        let conn = Conn::default();
+        let string = serde_json::to_string(&conn.exporter).unwrap();
+        let normalization = r#","normalization":{"values":["None","Loudness","Peak"],"index":{"index":0,"length":3}}"#;
+        assert!(string.contains(normalization));
+        let string = string.replace(normalization, "");
+        for (loudness, peak, expected) in [
+            (0, 0, Normalization::None),
+            (1, 0, Normalization::Loudness),
+            (2, 4, Normalization::Peak),
+        ] {
+            // A save file with optional loudness and peak targets.
+            let string = string
+                .replace(
+                    r#""loudness_target":{"index":3,"length":4}"#,
+                    &format!(r#""loudness_target":{{"index":{},"length":5}}"#, loudness),
+                )
+                .replace(
+                    r#""peak_target":{"index":2,"length":4}"#,
+                    &format!(r#""peak_target":{{"index":{},"length":5}}"#, peak),
+                );
+            let exporter: Exporter = from_str(&Save::fix(&string)).unwrap();
+            assert_eq!(exporter.normalization.get(), expected);
+            match expected {
+                Normalization::None => {
+                    assert_eq!(exporter.loudness_target, conn.exporter.loudness_target);
+                    assert_eq!(exporter.peak_target, conn.exporter.peak_target);
+                }
+                Normalization::Loudness => assert_eq!(exporter.get_loudness_target(), -23),
+                Normalization::Peak => assert_eq!(exporter.get_peak_target(), -3.0),
+            }
+        }
+    }
+
+    #[test]
+    fn save_export_settings() {
+        let mut conn = Conn::default();
+        conn.exporter.wav_settings.index.set(2);
+        let string = serde_json::to_string(&conn.exporter).unwrap();
+        let exporter: Exporter = from_str(&string).unwrap();
+        assert_eq!(exporter.wav_settings, conn.exporter.wav_settings);
+        // An older list of settings. The index is kept, but the values are the current settings.
+        let saved = serde_json::to_string(&exporter.wav_settings).unwrap();
+        let string = string.replace(
+            &saved,
+            r#"{"values":["Framerate","Copyright","MultiFile"],"index":{"index":1,"length":3}}"#,
+        );
+        let exporter: Exporter = from_str(&string).unwrap();
+        assert_eq!(exporter.wav_settings.index.get(), 1);
+        assert_eq!(
+            exporter.wav_settings.get_values().0,
+            conn.exporter.wav_settings.get_values().0
+        );
+        // The index is out of range.
+        let string = string.replace(
+            r#""index":{"index":1,"length":3}"#,
+            r#""index":{"index":20,"length":21}"#,
+        );
+        let exporter: Exporter = from_str(&string).unwrap();
+        assert_eq!(exporter.wav_settings.index.get(), 0);
+    }
+
+    #[test]
+    fn save_channels() {
+        let mut conn = Conn::default();
+        conn.exporter.channels = 1;
+        let string = serde_json::to_string(&conn.exporter).unwrap();
+        let exporter: Exporter = from_str(&string).unwrap();
+        assert!(exporter.is_mono());
+        // Invalid numbers of channels are clamped.
+        for (channels, clamped) in [(0, 1), (3, 2), (8, 2)] {
+            let string = string.replace(r#""channels":1"#, &format!(r#""channels":{}"#, channels));
+            let exporter: Exporter = from_str(&string).unwrap();
+            assert_eq!(exporter.channels, clamped);
+        }
+    }
+
+    #[test]
+    fn save_backups() {
+        let config = Ini::load_from_file("../data/config.ini").unwrap();
+        let mut state = State::new(&config);
+        let conn = Conn::default();
+        let paths_state = PathsState::default();
+        let directory = temp_dir().join("cacophony_save_backups");
+        if directory.exists() {
+            remove_dir_all(&directory).unwrap();
+        }
+        create_dir_all(&directory).unwrap();
+        let path = directory.join("song.cac");
+        // A stale backup beyond the limit.
+        let stale = directory.join("song.cac.bak.5");
+        write(&stale, "").unwrap();
+        // Write with a different number of notes each time.
+        for i in 0..4 {
+            state.music.add_track(i);
+            Save::write(&path, &state, &conn, &paths_state, 2);
+        }
+        let mut files: Vec<String> = read_dir(&directory)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_str().unwrap().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["song.cac", "song.cac.bak.1", "song.cac.bak.2"]);
+        // The most recent backup is the previous save.
+        let num_tracks = |p: &str| {
+            let s: Save = from_str(&read_to_string(directory.join(p)).unwrap()).unwrap();
+            s.state.music.midi_tracks.len()
+        };
+        assert_eq!(num_tracks("song.cac"), 4);
+        assert_eq!(num_tracks("song.cac.bak.1"), 3);
+        assert_eq!(num_tracks("song.cac.bak.2"), 2);
+        remove_dir_all(&directory).unwrap();
+    }
+}
@@ -1,5 +1,8 @@
 use crate::{IOCommand, IOCommands, State};
 use audio::{CommandsMessage, Conn};
+use input::InputEvent;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
 /// A snapshot of a state delta.
 #[derive(Default)]
@@ -7,13 +10,16 @@ pub(crate) struct Snapshot {
     /// The state before changes were applied.
     pub(crate) from_state: Option<State>,
     /// The state after changes were applied.
-    to_state: Option<State>,
+    pub(crate) to_state: Option<State>,
     /// Commands that need to be sent to revert to the state before changes were applied.
     pub(crate) from_commands: Option<CommandsMessage>,
     /// Commands  that need to be sent to apply changes.
-    to_commands: Option<CommandsMessage>,
+    pub(crate) to_commands: Option<CommandsMessage>,
     /// A list of commands to send to the `IO` state.
     pub(crate) io_commands: IOCommands,
+    /// The edit that created this snapshot, if it can be repeated with `InputEvent::RepeatLastAction`.
+    /// Navigation and other actions that aren't edits don't set this.
+    pub(crate) action: Option<InputEvent>,
 }
 
 impl Snapshot {
@@ -95,6 +101,7 @@ impl Snapshot {
             from_commands: Some(from_commands),
             to_commands: Some(to_commands.clone()),
             io_commands: None,
+            action: None,
         };
         conn.do_commands(&to_commands);
         snapshot
@@ -110,16 +117,46 @@ impl Snapshot {
         }
     }
 
-    /// Returns a snapshot that flips the from/to of `snapshot`. This is used for undo/redo.
-    ///
-    /// - The Snapshot. Its `from_state` will become the returned Snapshot's `to_state` and vice-versa. Its `from_commands` will become the returned Snapshot's `to_commands` and vice-versa.
-    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
-        Self {
-            from_state: snapshot.to_state.clone(),
-            to_state: snapshot.from_state.clone(),
-            from_commands: snapshot.to_commands.clone(),
-            to_commands: snapshot.from_commands.clone(),
-            io_commands: None,
-        }
+    /// Returns this snapshot, marked as having been created by the edit `action`. See `IO.last_action`.
+    pub fn with_action(mut self, action: InputEvent) -> Self {
+        self.action = Some(action);
+        self
+    }
+
+    /// Returns a signature of the state that this snapshot applies, or None if it doesn't change the state.
+    pub fn get_signature(&self) -> Option<u64> {
+        self.to_state.as_ref().map(get_state_signature)
+    }
+}
+
+/// Returns a hash of `state`. Two states with the same signature are almost certainly identical.
+///
+/// This is much cheaper to store and compare than a clone of the state.
+pub(crate) fn get_state_signature(state: &State) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    format!("{:?}", state).hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_state_signature, Snapshot};
+    use common::State;
+    use ini::Ini;
+
+    #[test]
+    fn snapshot_signature() {
+        let config = Ini::load_from_file("../data/config.ini").unwrap();
+        let mut state = State::new(&config);
+        let s0 = state.clone();
+        // An action that doesn't change anything.
+        let snapshot = Snapshot::from_state(|_| {}, &mut state);
+        assert_eq!(snapshot.get_signature(), Some(get_state_signature(&s0)));
+        // An action that changes the state.
+        let snapshot = Snapshot::from_state(|s| s.time.cursor += 1, &mut state);
+        assert_ne!(snapshot.get_signature(), Some(get_state_signature(&s0)));
+        assert_eq!(snapshot.get_signature(), Some(get_state_signature(&state)));
+        // A snapshot without a state doesn't have a signature.
+        assert!(Snapshot::from_io_commands(vec![]).get_signature().is_none());
     }
 }
@@ -0,0 +1,57 @@
+use std::str::FromStr;
+
+/// Where the playback time goes after the user stops the music.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub(crate) enum StopPosition {
+    /// Return to the start of the music.
+    Start,
+    /// Stay where the music stopped.
+    Stop,
+    /// Return to where playback began.
+    #[default]
+    Begin,
+}
+
+impl StopPosition {
+    /// Returns the new playback time in PPQ.
+    ///
+    /// - `begin` The time at which playback began.
+    /// - `stop` The time at which the music stopped.
+    pub(crate) fn get_playback(&self, begin: u64, stop: u64) -> u64 {
+        match self {
+            Self::Start => 0,
+            Self::Stop => stop,
+            Self::Begin => begin,
+        }
+    }
+}
+
+impl FromStr for StopPosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "start" => Ok(Self::Start),
+            "stop" => Ok(Self::Stop),
+            "begin" => Ok(Self::Begin),
+            other => Err(format!("Invalid stop position: {}", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StopPosition;
+    use std::str::FromStr;
+
+    #[test]
+    fn stop_position() {
+        assert_eq!(StopPosition::from_str("start"), Ok(StopPosition::Start));
+        assert_eq!(StopPosition::from_str("stop"), Ok(StopPosition::Stop));
+        assert_eq!(StopPosition::from_str("begin"), Ok(StopPosition::Begin));
+        assert!(StopPosition::from_str("end").is_err());
+        assert_eq!(StopPosition::Start.get_playback(10, 20), 0);
+        assert_eq!(StopPosition::Stop.get_playback(10, 20), 20);
+        assert_eq!(StopPosition::Begin.get_playback(10, 20), 10);
+    }
+}
@@ -1,20 +1,176 @@
+use crate::clock::Clock;
 use crate::panel::*;
 use crate::select_track;
+use common::config::{parse, parse_bool};
 use common::open_file::OpenFileType;
-use common::{MidiTrack, Paths, SelectMode, MAX_VOLUME};
+use common::{MidiTrack, NoteRange, Paths, SelectMode, MAX_VOLUME};
+use ini::Ini;
+use serde_json::from_str;
 use std::path::PathBuf;
+use std::rc::Rc;
 use text::get_file_name_no_ex;
 
 const TRACK_SCROLL_EVENTS: [InputEvent; 2] = [InputEvent::PreviousTrack, InputEvent::NextTrack];
 /// A list of tracks and their parameters.
 pub(crate) struct TracksPanel {
     default_soundfont_path: PathBuf,
+    /// The bank index of the program that is set when a track is added.
+    default_bank_index: usize,
+    /// The preset index of the program that is set when a track is added.
+    default_preset_index: usize,
+    /// The maximum number of tracks.
+    max_tracks: usize,
+    /// New tracks never use these channels.
+    reserved_channels: Vec<u8>,
+    /// If true, selecting past the last track selects the first track, and vice versa.
+    wrap_tracks: bool,
+    /// If true, clearing a track requires confirmation.
+    confirm_clear_track: bool,
+    /// The index of the track that will be cleared if the user confirms.
+    clear_track: Option<usize>,
+    /// If true, notes that the user is holding down are replayed after a program change.
+    retrigger_notes: bool,
+    /// The clock that is used to name frozen audio files. See `IO::set_clock`.
+    clock: Rc<dyn Clock>,
     tooltips: Tooltips,
 }
 
 impl TracksPanel {
+    pub fn new(config: &Ini, clock: Rc<dyn Clock>) -> Self {
+        let section = config.section(Some("TRACKS")).unwrap();
+        let default_soundfont_path = match section.get("default_soundfont") {
+            Some(path) => PathBuf::from(path),
+            None => Paths::get().default_soundfont_path.clone(),
+        };
+        let default_bank_index = parse(section, "default_bank");
+        let default_preset_index = parse(section, "default_preset");
+        let max_tracks = parse(section, "max_tracks");
+        let reserved_channels = match section.get("reserved_channels") {
+            Some(value) => match from_str::<Vec<u8>>(value) {
+                Ok(channels) => channels,
+                Err(error) => panic!("Error parsing reserved channels {}: {}", value, error),
+            },
+            None => panic!("Missing key reserved_channels"),
+        };
+        let wrap_tracks = parse_bool(section, "wrap_tracks");
+        let confirm_clear_track = parse_bool(section, "confirm_clear_track");
+        let retrigger_notes = parse_bool(section, "retrigger_notes");
+        Self {
+            default_soundfont_path,
+            default_bank_index,
+            default_preset_index,
+            max_tracks,
+            reserved_channels,
+            wrap_tracks,
+            confirm_clear_track,
+            clear_track: None,
+            retrigger_notes,
+            clock,
+            tooltips: Tooltips::default(),
+        }
+    }
+
+    /// Replace the clock. See `IO::set_clock`.
+    pub(crate) fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Returns a new path for the frozen audio of a track. The path includes the current time, so that each freeze has its own file.
+    fn get_frozen_path(&self, channel: u8) -> PathBuf {
+        Paths::get().frozen_directory.join(format!(
+            "track_{}_{}.wav",
+            channel,
+            self.clock.now().as_millis()
+        ))
+    }
+
+    /// Remove all notes from the selected track. The track's channel, program, and settings aren't changed.
+    ///
+    /// If `self.confirm_clear_track` is true, this asks the user to confirm and returns None. Clearing the same track again, without any other input in between, confirms.
+    fn clear_track(
+        &mut self,
+        state: &mut State,
+        input: &Input,
+        tts: &mut TTS,
+        text: &Text,
+    ) -> Option<Snapshot> {
+        let selected = state.music.selected?;
+        let track = &state.music.midi_tracks[selected];
+        let num_notes = track.notes.len();
+        if num_notes == 0 {
+            self.clear_track = None;
+            tts.enqueue(text.get_ref("TRACKS_PANEL_TTS_CLEAR_TRACK_EMPTY"));
+            return None;
+        }
+        // Ask for confirmation.
+        if self.confirm_clear_track && self.clear_track != Some(selected) {
+            self.clear_track = Some(selected);
+            tts.enqueue(self.tooltips.get_tooltip_with_values(
+                "TRACKS_PANEL_TTS_CLEAR_TRACK_CONFIRM",
+                &[InputEvent::ClearTrack],
+                &[&num_notes.to_string(), &track.channel.to_string()],
+                input,
+                text,
+            ));
+            return None;
+        }
+        self.clear_track = None;
+        let s0 = state.clone();
+        state.music.midi_tracks[selected].notes.clear();
+        // Deselect.
+        state.select_mode = match &state.select_mode {
+            SelectMode::Single(_) => SelectMode::Single(None),
+            SelectMode::Many(_) => SelectMode::Many(None),
+        };
+        tts.enqueue(
+            text.get_with_values("TRACKS_PANEL_TTS_CLEAR_TRACK", &[&num_notes.to_string()]),
+        );
+        Some(Snapshot::from_states(s0, state))
+    }
+
+    /// Returns the commands that will set the default program of a new track, or None if the default SoundFont doesn't exist.
+    fn get_default_program_commands(&self, channel: u8, conn: &mut Conn) -> Option<Vec<Command>> {
+        if !self.default_soundfont_path.exists() {
+            println!(
+                "Warning: Default SoundFont {:?} doesn't exist.",
+                self.default_soundfont_path
+            );
+            return None;
+        }
+        let path = self.default_soundfont_path.clone();
+        let load = Command::LoadSoundFont {
+            channel,
+            path: path.clone(),
+        };
+        // Use the first program in the SoundFont.
+        if self.default_bank_index == 0 && self.default_preset_index == 0 {
+            return Some(vec![load]);
+        }
+        // Load the SoundFont to get the number of banks and presets.
+        conn.do_commands(&[load.clone()]);
+        let num_banks = conn.state.programs[&channel].num_banks;
+        let bank_index = self.default_bank_index.min(num_banks - 1);
+        conn.do_commands(&[Command::SetProgram {
+            channel,
+            path: path.clone(),
+            bank_index,
+            preset_index: 0,
+        }]);
+        let num_presets = conn.state.programs[&channel].num_presets;
+        let preset_index = self.default_preset_index.min(num_presets - 1);
+        Some(vec![
+            load,
+            Command::SetProgram {
+                channel,
+                path,
+                bank_index,
+                preset_index,
+            },
+        ])
+    }
+
     /// Increment or decrement the preset index. Returns a new undo-redo state.
-    fn set_preset(channel: u8, conn: &mut Conn, up: bool) -> Option<Snapshot> {
+    fn set_preset(&self, channel: u8, conn: &mut Conn, up: bool) -> Option<Snapshot> {
         let program = conn.state.programs.get(&channel).unwrap();
         let mut index = Index::new(program.preset_index, program.num_presets);
         index.increment(up);
@@ -32,11 +188,11 @@ impl TracksPanel {
             bank_index: program.bank_index,
             preset_index,
         }];
-        Some(Snapshot::from_commands(c0, c1, conn))
+        Some(self.get_program_snapshot(channel, c0, c1, conn))
     }
 
     /// Increment or decrement the bank index, setting the preset index to 0. Returns a new undo-redo state.
-    fn set_bank(channel: u8, conn: &mut Conn, up: bool) -> Option<Snapshot> {
+    fn set_bank(&self, channel: u8, conn: &mut Conn, up: bool) -> Option<Snapshot> {
         let program = conn.state.programs.get(&channel).unwrap();
         let bank_index_0 = program.bank_index;
         let mut index = Index::new(program.bank_index, program.num_banks);
@@ -59,7 +215,199 @@ impl TracksPanel {
                 bank_index,
                 preset_index: 0,
             }];
-            Some(Snapshot::from_commands(c0, c1, conn))
+            Some(self.get_program_snapshot(channel, c0, c1, conn))
+        }
+    }
+
+    /// Returns a snapshot of a program change. If `self.retrigger_notes` is true, held notes are replayed after the program changes, including when the change is undone or redone.
+    ///
+    /// - `channel` The channel.
+    /// - `c0` The commands that revert the program change.
+    /// - `c1` The commands that change the program.
+    /// - `conn` The audio connection.
+    fn get_program_snapshot(
+        &self,
+        channel: u8,
+        mut c0: Vec<Command>,
+        mut c1: Vec<Command>,
+        conn: &mut Conn,
+    ) -> Snapshot {
+        if self.retrigger_notes {
+            c0.push(Command::RetriggerActiveNotes { channel });
+            c1.push(Command::RetriggerActiveNotes { channel });
+        }
+        Snapshot::from_commands(c0, c1, conn)
+    }
+
+    /// Returns the lowest channel that isn't used by any track and isn't reserved.
+    /// Returns None and says why if a track can't be added.
+    fn get_free_channel(&self, state: &State, tts: &mut TTS, text: &Text) -> Option<u8> {
+        self.get_free_channels(state, 1, tts, text)
+            .map(|channels| channels[0])
+    }
+
+    /// Returns the `count` lowest channels that aren't used by any track and aren't reserved.
+    /// Returns None and says why if `count` tracks can't be added.
+    fn get_free_channels(
+        &self,
+        state: &State,
+        count: usize,
+        tts: &mut TTS,
+        text: &Text,
+    ) -> Option<Vec<u8>> {
+        // There are too many tracks.
+        if state.music.midi_tracks.len() + count > self.max_tracks {
+            tts.enqueue(text.get_with_values(
+                "TRACKS_PANEL_TTS_MAX_TRACKS",
+                &[&self.max_tracks.to_string()],
+            ));
+            return None;
+        }
+        // Get all channels currently being used.
+        let track_channels: Vec<u8> = state.music.midi_tracks.iter().map(|t| t.channel).collect();
+        // Get the minimum available channels.
+        let channels: Vec<u8> = (0u8..255u8)
+            .filter(|c| !track_channels.contains(c) && !self.reserved_channels.contains(c))
+            .take(count)
+            .collect();
+        if channels.len() < count {
+            tts.enqueue(text.get_ref("TRACKS_PANEL_TTS_NO_FREE_CHANNELS"));
+            None
+        } else {
+            Some(channels)
+        }
+    }
+
+    /// Cancel a pending confirmation to clear a track. See `clear_track`.
+    pub(crate) fn cancel_clear_track(&mut self) {
+        self.clear_track = None;
+    }
+
+    /// Split the chords of the selected track into new tracks, one per voice. See `MidiTrack::explode_chords`.
+    /// Each new track uses the same program and gain as the selected track. The selected track is muted, not removed.
+    /// Returns a new undo-redo state.
+    fn explode_chords(
+        &self,
+        state: &mut State,
+        conn: &mut Conn,
+        tts: &mut TTS,
+        text: &Text,
+    ) -> Option<Snapshot> {
+        let selected = state.music.selected.unwrap();
+        let track = &state.music.midi_tracks[selected];
+        let program = conn.state.programs.get(&track.channel).unwrap().clone();
+        let gain = track.gain;
+        let voices = track.explode_chords();
+        // There's nothing to split.
+        if voices.len() < 2 {
+            tts.enqueue(text.get_ref("TRACKS_PANEL_TTS_EXPLODE_CHORDS_NO_CHORDS"));
+            return None;
+        }
+        let num_voices = voices.len();
+        let channels = self.get_free_channels(state, num_voices, tts, text)?;
+        let c0 = channels
+            .iter()
+            .map(|&channel| Command::UnsetProgram { channel })
+            .collect();
+        let c1 = channels
+            .iter()
+            .map(|&channel| Command::SetProgram {
+                channel,
+                path: program.path.clone(),
+                bank_index: program.bank_index,
+                preset_index: program.preset_index,
+            })
+            .collect();
+        let s0 = state.clone();
+        // Deselect.
+        state.select_mode = match &state.select_mode {
+            SelectMode::Single(_) => SelectMode::Single(None),
+            SelectMode::Many(_) => SelectMode::Many(None),
+        };
+        // Mute the original track so that the notes aren't doubled.
+        let track = &mut state.music.midi_tracks[selected];
+        track.mute = true;
+        track.solo = false;
+        // Add the new tracks and select the first one.
+        state.music.selected = Some(state.music.midi_tracks.len());
+        for (voice, channel) in voices.into_iter().zip(channels) {
+            let track = state.music.add_track(channel);
+            track.gain = gain;
+            track.notes = voice;
+        }
+        tts.enqueue(text.get_with_values(
+            "TRACKS_PANEL_TTS_EXPLODE_CHORDS",
+            &[&num_voices.to_string()],
+        ));
+        Some(Snapshot::from_states_and_commands(s0, state, c0, c1, conn))
+    }
+
+    /// Freeze the selected track, or unfreeze it if it's already frozen. See `Conn::freeze_track`.
+    ///
+    /// Unfreezing doesn't delete the audio file, so that freezing can be undone and redone.
+    fn freeze_track(
+        &self,
+        state: &mut State,
+        conn: &mut Conn,
+        tts: &mut TTS,
+        text: &Text,
+    ) -> Option<Snapshot> {
+        let index = state.music.selected?;
+        let s0 = state.clone();
+        let track = &state.music.midi_tracks[index];
+        let channel = track.channel;
+        // Unfreeze.
+        if track.frozen_audio.is_some() {
+            state.music.midi_tracks[index].frozen_audio = None;
+            tts.enqueue(text.get_with_values("TRACKS_PANEL_TTS_UNFREEZE", &[&channel.to_string()]));
+            return Some(Snapshot::from_states(s0, state));
+        }
+        // Get a new path.
+        let path = self.get_frozen_path(channel);
+        if conn.freeze_track(state, index, &path) {
+            state.music.midi_tracks[index].frozen_audio = Some(path);
+            tts.enqueue(text.get_with_values("TRACKS_PANEL_TTS_FREEZE", &[&channel.to_string()]));
+            Some(Snapshot::from_states(s0, state))
+        } else {
+            tts.enqueue(text.get_ref("TRACKS_PANEL_TTS_FREEZE_FAILED"));
+            None
+        }
+    }
+
+    /// Add a new empty track that uses the same program as the selected track. Returns a new undo-redo state.
+    fn add_template_track(
+        &self,
+        state: &mut State,
+        conn: &mut Conn,
+        tts: &mut TTS,
+        text: &Text,
+    ) -> Option<Snapshot> {
+        let program = conn
+            .state
+            .programs
+            .get(&state.music.get_selected_track().unwrap().channel)
+            .unwrap();
+        match self.get_free_channel(state, tts, text) {
+            Some(channel) => {
+                let c0 = vec![Command::UnsetProgram { channel }];
+                let c1 = vec![Command::SetProgram {
+                    channel,
+                    path: program.path.clone(),
+                    bank_index: program.bank_index,
+                    preset_index: program.preset_index,
+                }];
+                let s0 = state.clone();
+                // Deselect.
+                state.select_mode = match &state.select_mode {
+                    SelectMode::Single(_) => SelectMode::Single(None),
+                    SelectMode::Many(_) => SelectMode::Many(None),
+                };
+                // Select the new track.
+                state.music.selected = Some(state.music.midi_tracks.len());
+                state.music.add_track(channel);
+                Some(Snapshot::from_states_and_commands(s0, state, c0, c1, conn))
+            }
+            None => None,
         }
     }
 
@@ -73,15 +421,37 @@ impl TracksPanel {
         track.gain = gain;
         Some(Snapshot::from_states(s0, state))
     }
-}
 
-impl Default for TracksPanel {
-    fn default() -> Self {
-        let default_soundfont_path = Paths::get().default_soundfont_path.clone();
-        Self {
-            default_soundfont_path,
-            tooltips: Tooltips::default(),
-        }
+    /// Returns a description of how the track plays out-of-range notes.
+    fn get_note_range_tts(key: &str, track: &MidiTrack, text: &Text) -> String {
+        text.get_with_values(
+            key,
+            &[
+                text.get_channel_note_name(track.note_range.min, track.channel),
+                text.get_channel_note_name(track.note_range.max, track.channel),
+                text.get_note_range_mode(&track.note_range.mode),
+            ],
+        )
+    }
+
+    /// Change the selected track's note range.
+    ///
+    /// - `f` A function that modifies the note range.
+    fn set_note_range(
+        state: &mut State,
+        tts: &mut TTS,
+        text: &Text,
+        f: impl Fn(&mut NoteRange),
+    ) -> Option<Snapshot> {
+        let s0 = state.clone();
+        let track = state.music.get_selected_track_mut().unwrap();
+        f(&mut track.note_range);
+        tts.enqueue(TracksPanel::get_note_range_tts(
+            "TRACKS_PANEL_TTS_NOTE_RANGE",
+            track,
+            text,
+        ));
+        Some(Snapshot::from_states(s0, state))
     }
 }
 
@@ -95,6 +465,12 @@ impl Panel for TracksPanel {
         text: &Text,
         _: &mut PathsState,
     ) -> Option<Snapshot> {
+        // Any other input, or a change of the selected track, cancels a pending confirmation to clear a track.
+        if (input.has_events() && !input.happened(&InputEvent::ClearTrack))
+            || self.clear_track != state.music.selected
+        {
+            self.cancel_clear_track();
+        }
         // Status TTS.
         if input.happened(&InputEvent::StatusTTS) {
             match state.music.get_selected_track() {
@@ -114,7 +490,7 @@ impl Panel for TracksPanel {
                                 &[
                                     &program.preset_name,
                                     &program.bank.to_string(),
-                                    &track.gain.to_string(),
+                                    &text.get_gain(track.gain, true),
                                     get_file_name_no_ex(&program.path),
                                 ],
                             ));
@@ -128,10 +504,48 @@ impl Panel for TracksPanel {
                                 s.push(' ');
                                 s.push_str(text.get_ref("TRACKS_PANEL_STATUS_TTS_SOLOED"))
                             }
+                            // Record-armed.
+                            if track.record_armed {
+                                s.push(' ');
+                                s.push_str(text.get_ref("TRACKS_PANEL_STATUS_TTS_RECORD_ARMED"))
+                            }
+                            // Selected for export.
+                            if track.export_selected {
+                                s.push(' ');
+                                s.push_str(text.get_ref("TRACKS_PANEL_STATUS_TTS_EXPORT_SELECTED"))
+                            }
+                            // Frozen.
+                            if track.frozen_audio.is_some() {
+                                s.push(' ');
+                                s.push_str(text.get_ref("TRACKS_PANEL_STATUS_TTS_FROZEN"))
+                            }
+                            // Out-of-range notes are remapped.
+                            if track.note_range.mode.is_some() {
+                                s.push(' ');
+                                s.push_str(&TracksPanel::get_note_range_tts(
+                                    "TRACKS_PANEL_STATUS_TTS_NOTE_RANGE",
+                                    track,
+                                    text,
+                                ));
+                            }
                         }
                         // No SoundFont.
                         None => s.push_str(text.get_ref("TRACKS_PANEL_STATUS_TTS_NO_SOUNDFONT")),
                     }
+                    // Note stats.
+                    s.push(' ');
+                    match (track.pitch_range(), track.get_end()) {
+                        (Some((min, max)), Some(end)) => s.push_str(&text.get_with_values(
+                            "TRACKS_PANEL_STATUS_TTS_NOTES",
+                            &[
+                                &track.note_count().to_string(),
+                                text.get_channel_note_name(min, track.channel),
+                                text.get_channel_note_name(max, track.channel),
+                                &text.get_ppq_tts(&end),
+                            ],
+                        )),
+                        _ => s.push_str(text.get_ref("TRACKS_PANEL_STATUS_TTS_EMPTY")),
+                    }
                     tts.enqueue(s)
                 }
                 None => tts.enqueue(text.get_ref("TRACKS_PANEL_STATUS_TTS_NO_SELECTION")),
@@ -160,6 +574,12 @@ impl Panel for TracksPanel {
                     input,
                     text,
                 ));
+                s.push(self.tooltips.get_tooltip(
+                    "TRACKS_PANEL_INPUT_TTS_CLEAR_TRACK",
+                    &[InputEvent::ClearTrack],
+                    input,
+                    text,
+                ));
                 s.push(self.tooltips.get_tooltip(
                     "TRACKS_PANEL_INPUT_TTS_TRACK_PREFIX_2",
                     &[InputEvent::EnableSoundFontPanel],
@@ -187,6 +607,43 @@ impl Panel for TracksPanel {
                         input,
                         text,
                     ));
+                    s.push(self.tooltips.get_tooltip(
+                        "TRACKS_PANEL_INPUT_TTS_TRACK_SUFFIX_3",
+                        &[InputEvent::AddTemplateTrack],
+                        input,
+                        text,
+                    ));
+                    s.push(self.tooltips.get_tooltip(
+                        "TRACKS_PANEL_INPUT_TTS_EXPLODE_CHORDS",
+                        &[InputEvent::ExplodeChords],
+                        input,
+                        text,
+                    ));
+                    // Freeze.
+                    let freeze_key = if track.frozen_audio.is_some() {
+                        "TRACKS_PANEL_INPUT_TTS_UNFREEZE"
+                    } else {
+                        "TRACKS_PANEL_INPUT_TTS_FREEZE"
+                    };
+                    s.push(self.tooltips.get_tooltip(
+                        freeze_key,
+                        &[InputEvent::FreezeTrack],
+                        input,
+                        text,
+                    ));
+                    // Note range.
+                    s.push(self.tooltips.get_tooltip(
+                        "TRACKS_PANEL_INPUT_TTS_NOTE_RANGE",
+                        &[
+                            InputEvent::CycleNoteRangeMode,
+                            InputEvent::NoteRangeMinDown,
+                            InputEvent::NoteRangeMinUp,
+                            InputEvent::NoteRangeMaxDown,
+                            InputEvent::NoteRangeMaxUp,
+                        ],
+                        input,
+                        text,
+                    ));
                     // Mute.
                     let mute_key = if track.mute {
                         "TRACKS_PANEL_INPUT_TTS_UNMUTE"
@@ -207,6 +664,52 @@ impl Panel for TracksPanel {
                         self.tooltips
                             .get_tooltip(solo_key, &[InputEvent::Solo], input, text),
                     );
+                    // Record-arm.
+                    let record_arm_key = if track.record_armed {
+                        "TRACKS_PANEL_INPUT_TTS_RECORD_DISARM"
+                    } else {
+                        "TRACKS_PANEL_INPUT_TTS_RECORD_ARM"
+                    };
+                    s.push(self.tooltips.get_tooltip(
+                        record_arm_key,
+                        &[InputEvent::RecordArm],
+                        input,
+                        text,
+                    ));
+                    // Export selection.
+                    let export_select_key = if track.export_selected {
+                        "TRACKS_PANEL_INPUT_TTS_EXPORT_DESELECT"
+                    } else {
+                        "TRACKS_PANEL_INPUT_TTS_EXPORT_SELECT"
+                    };
+                    s.push(self.tooltips.get_tooltip(
+                        export_select_key,
+                        &[InputEvent::ExportSelect],
+                        input,
+                        text,
+                    ));
+                    // Color.
+                    s.push(self.tooltips.get_tooltip(
+                        "TRACKS_PANEL_INPUT_TTS_CYCLE_COLOR",
+                        &[InputEvent::CycleTrackColor],
+                        input,
+                        text,
+                    ));
+                    // MIDI input channel.
+                    if let Some(accept) = state.input.get_midi_channel(track.channel) {
+                        let midi_channel_key = if accept {
+                            "TRACKS_PANEL_INPUT_TTS_IGNORE_MIDI_CHANNEL"
+                        } else {
+                            "TRACKS_PANEL_INPUT_TTS_ACCEPT_MIDI_CHANNEL"
+                        };
+                        s.push(self.tooltips.get_tooltip_with_values(
+                            midi_channel_key,
+                            &[InputEvent::ToggleMidiInputChannel],
+                            &[&track.channel.to_string()],
+                            input,
+                            text,
+                        ));
+                    }
                 }
                 // Say it.
                 tts.enqueue(s);
@@ -219,11 +722,7 @@ impl Panel for TracksPanel {
         // Add a track.
         else if input.happened(&InputEvent::AddTrack) {
             let s0 = state.clone();
-            // Get all channels currently being used.
-            let track_channels: Vec<u8> =
-                state.music.midi_tracks.iter().map(|t| t.channel).collect();
-            // Get all available channels and get the minimum availabe channel.
-            match (0u8..255u8).filter(|c| !track_channels.contains(c)).min() {
+            let snapshot = match self.get_free_channel(state, tts, text) {
                 Some(channel) => {
                     // Deselect.
                     state.select_mode = match &state.select_mode {
@@ -233,17 +732,19 @@ impl Panel for TracksPanel {
                     // Set the selection.
                     state.music.selected = Some(state.music.midi_tracks.len());
                     // Add a track.
-                    state.music.midi_tracks.push(MidiTrack::new(channel));
-                    // Set the soundfont to the default.
-                    let c0 = vec![Command::UnsetProgram { channel }];
-                    let c1 = vec![Command::LoadSoundFont {
-                        channel,
-                        path: self.default_soundfont_path.clone(),
-                    }];
-                    Some(Snapshot::from_states_and_commands(s0, state, c0, c1, conn))
+                    state.music.add_track(channel);
+                    // Set the program to the default.
+                    match self.get_default_program_commands(channel, conn) {
+                        Some(c1) => {
+                            let c0 = vec![Command::UnsetProgram { channel }];
+                            Some(Snapshot::from_states_and_commands(s0, state, c0, c1, conn))
+                        }
+                        None => Some(Snapshot::from_states(s0, state)),
+                    }
                 }
                 None => None,
-            }
+            };
+            snapshot.map(|s| s.with_action(InputEvent::AddTrack))
         }
         // There is a selected track.
         else if let Some(selected) = state.music.selected {
@@ -269,7 +770,7 @@ impl Panel for TracksPanel {
                 // Remove the track.
                 state.music.midi_tracks.retain(|t| t.channel != channel);
                 // This track has a program that needs to be unset.
-                match conn.state.programs.get(&channel) {
+                let snapshot = match conn.state.programs.get(&channel) {
                     Some(program) => {
                         // Undo: Set the program.
                         let c0 = vec![Command::SetProgram {
@@ -282,14 +783,24 @@ impl Panel for TracksPanel {
                         Some(Snapshot::from_states_and_commands(s0, state, c0, c1, conn))
                     }
                     None => Some(Snapshot::from_states(s0, state)),
-                }
+                };
+                snapshot.map(|s| s.with_action(InputEvent::RemoveTrack))
+            } else if input.happened(&InputEvent::ClearTrack) {
+                return self.clear_track(state, input, tts, text);
             } else if input.happened(&InputEvent::EnableSoundFontPanel) {
                 return Some(Snapshot::from_io_commands(vec![IOCommand::EnableOpenFile(
                     OpenFileType::SoundFont,
                 )]));
             }
             // Select a track.
-            else if let Some(snapshot) = select_track(state, input, TRACK_SCROLL_EVENTS) {
+            else if let Some(snapshot) = select_track(
+                state,
+                input,
+                TRACK_SCROLL_EVENTS,
+                self.wrap_tracks,
+                tts,
+                text,
+            ) {
                 return Some(snapshot);
             }
             // Track-specific operations.
@@ -300,17 +811,37 @@ impl Panel for TracksPanel {
                 match conn.state.programs.get(&channel) {
                     Some(_) => {
                         if input.happened(&InputEvent::NextPreset) {
-                            TracksPanel::set_preset(channel, conn, true)
+                            self.set_preset(channel, conn, true)
                         } else if input.happened(&InputEvent::PreviousPreset) {
-                            TracksPanel::set_preset(track.channel, conn, false)
+                            self.set_preset(track.channel, conn, false)
                         } else if input.happened(&InputEvent::NextBank) {
-                            TracksPanel::set_bank(track.channel, conn, true)
+                            self.set_bank(track.channel, conn, true)
                         } else if input.happened(&InputEvent::PreviousBank) {
-                            TracksPanel::set_bank(track.channel, conn, false)
+                            self.set_bank(track.channel, conn, false)
                         } else if input.happened(&InputEvent::IncreaseTrackGain) {
                             TracksPanel::set_gain(state, true)
+                                .map(|s| s.with_action(InputEvent::IncreaseTrackGain))
                         } else if input.happened(&InputEvent::DecreaseTrackGain) {
                             TracksPanel::set_gain(state, false)
+                                .map(|s| s.with_action(InputEvent::DecreaseTrackGain))
+                        } else if input.happened(&InputEvent::AddTemplateTrack) {
+                            self.add_template_track(state, conn, tts, text)
+                                .map(|s| s.with_action(InputEvent::AddTemplateTrack))
+                        } else if input.happened(&InputEvent::ExplodeChords) {
+                            self.explode_chords(state, conn, tts, text)
+                                .map(|s| s.with_action(InputEvent::ExplodeChords))
+                        } else if input.happened(&InputEvent::FreezeTrack) {
+                            self.freeze_track(state, conn, tts, text)
+                        } else if input.happened(&InputEvent::CycleNoteRangeMode) {
+                            TracksPanel::set_note_range(state, tts, text, |r| r.cycle_mode())
+                        } else if input.happened(&InputEvent::NoteRangeMinDown) {
+                            TracksPanel::set_note_range(state, tts, text, |r| r.set_min(false))
+                        } else if input.happened(&InputEvent::NoteRangeMinUp) {
+                            TracksPanel::set_note_range(state, tts, text, |r| r.set_min(true))
+                        } else if input.happened(&InputEvent::NoteRangeMaxDown) {
+                            TracksPanel::set_note_range(state, tts, text, |r| r.set_max(false))
+                        } else if input.happened(&InputEvent::NoteRangeMaxUp) {
+                            TracksPanel::set_note_range(state, tts, text, |r| r.set_max(true))
                         } else if input.happened(&InputEvent::Mute) {
                             let s0 = state.clone();
                             let track = state.music.get_selected_track_mut().unwrap();
@@ -329,6 +860,49 @@ impl Panel for TracksPanel {
                                 track.mute = false;
                             }
                             Some(Snapshot::from_states(s0, state))
+                        } else if input.happened(&InputEvent::RecordArm) {
+                            let s0 = state.clone();
+                            let track = state.music.get_selected_track_mut().unwrap();
+                            track.record_armed = !track.record_armed;
+                            Some(Snapshot::from_states(s0, state))
+                        } else if input.happened(&InputEvent::ExportSelect) {
+                            let s0 = state.clone();
+                            let track = state.music.get_selected_track_mut().unwrap();
+                            track.export_selected = !track.export_selected;
+                            let key = if track.export_selected {
+                                "TRACKS_PANEL_TTS_EXPORT_SELECTED"
+                            } else {
+                                "TRACKS_PANEL_TTS_EXPORT_DESELECTED"
+                            };
+                            tts.enqueue(text.get_with_values(key, &[&track.channel.to_string()]));
+                            Some(Snapshot::from_states(s0, state))
+                        } else if input.happened(&InputEvent::CycleTrackColor) {
+                            let s0 = state.clone();
+                            let index = state.music.selected.unwrap();
+                            let track = state.music.get_selected_track_mut().unwrap();
+                            track.cycle_color(index);
+                            let color = format!("TRACK_COLOR_{}", track.get_color_index(index));
+                            tts.enqueue(text.get_with_values(
+                                "TRACKS_PANEL_TTS_COLOR",
+                                &[text.get_ref(&color)],
+                            ));
+                            Some(Snapshot::from_states(s0, state))
+                        } else if input.happened(&InputEvent::ToggleMidiInputChannel) {
+                            let s0 = state.clone();
+                            let channel = track.channel;
+                            // Channels 16 and higher aren't MIDI input channels.
+                            match state.input.toggle_midi_channel(channel) {
+                                Some(accept) => {
+                                    let key = if accept {
+                                        "TRACKS_PANEL_TTS_ACCEPT_MIDI_CHANNEL"
+                                    } else {
+                                        "TRACKS_PANEL_TTS_IGNORE_MIDI_CHANNEL"
+                                    };
+                                    tts.enqueue(text.get_with_values(key, &[&channel.to_string()]));
+                                    Some(Snapshot::from_states(s0, state))
+                                }
+                                None => None,
+                            }
                         } else {
                             None
                         }
@@ -341,7 +915,7 @@ impl Panel for TracksPanel {
         }
     }
 
-    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn) {}
+    fn on_disable_abc123(&mut self, _: &mut State, _: &mut Conn, _: &mut TTS, _: &Text) {}
 
     fn update_abc123(
         &mut self,
@@ -360,3 +934,29 @@ impl Panel for TracksPanel {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::TracksPanel;
+    use crate::clock::MockClock;
+    use common::Paths;
+    use ini::Ini;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    #[test]
+    fn frozen_path() {
+        Paths::init(&PathBuf::from("../data"));
+        let config = Ini::load_from_file("../data/config.ini").unwrap();
+        let clock = Rc::new(MockClock::default());
+        let panel = TracksPanel::new(&config, clock.clone());
+        clock.advance(Duration::from_millis(1500));
+        let path = panel.get_frozen_path(3);
+        assert_eq!(path.file_name().unwrap(), "track_3_1500.wav");
+        assert!(path.starts_with(&Paths::get().frozen_directory));
+        // Each freeze gets a new file.
+        clock.advance(Duration::from_millis(1));
+        assert_ne!(panel.get_frozen_path(3), path);
+    }
+}
@@ -50,26 +50,31 @@ impl ExportPanel {
 }
 
 impl Drawable for ExportPanel {
-    fn update(&self, renderer: &Renderer, _: &State, conn: &Conn, _: &Text, _: &PathsState) {
+    fn update(&self, renderer: &Renderer, _: &State, conn: &Conn, text: &Text, _: &PathsState) {
         self.popup.update(renderer);
         self.panel.update(true, renderer);
 
-        let export_state = conn.export_state.lock();
-        match *export_state {
-            ExportState::WritingWav {
-                total_samples,
-                exported_samples,
-            } => {
-                let samples = format!("{}/{}", exported_samples, total_samples);
+        let export_state = *conn.export_state.lock();
+        match export_state {
+            ExportState::WritingWav { .. } => {
+                let percent =
+                    ((conn.get_export_progress().unwrap_or(0.0) * 100.0) as u8).to_string();
+                let progress = match conn.get_export_file() {
+                    Some((index, num_files)) => text.get_with_values(
+                        "EXPORT_PANEL_PROGRESS_FILE",
+                        &[&(index + 1).to_string(), &num_files.to_string(), &percent],
+                    ),
+                    None => text.get_with_values("EXPORT_PANEL_PROGRESS", &[&percent]),
+                };
                 // Draw the string.
-                let w = samples.chars().count() as u32;
+                let w = progress.chars().count() as u32;
                 let x = self.panel.background.grid_rect.position[0]
                     + self.panel.background.grid_rect.size[0] / 2
                     - w / 2;
                 let y = self.panel.background.grid_rect.position[1] + 1;
                 let label = Label {
                     position: [x, y],
-                    text: samples,
+                    text: progress,
                 };
                 renderer.text(&label, &ColorKey::FocusDefault);
             }
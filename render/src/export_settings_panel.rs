@@ -1,8 +1,8 @@
 use crate::panel::*;
 use crate::Focus;
-use audio::export::{ExportSetting, ExportType, MultiFileSuffix};
-use audio::exporter::{Exporter, MP3_BIT_RATES};
-use common::IndexedValues;
+use audio::export::{ExportSetting, ExportType, MultiFileSuffix, Normalization, WavBitDepth};
+use audio::exporter::{Exporter, LOUDNESS_TARGETS, MP3_BIT_RATES, OPUS_BIT_RATES, PEAK_TARGETS};
+use common::{IndexedValues, MAX_VOLUME};
 use hashbrown::HashMap;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -23,10 +23,22 @@ pub(crate) struct ExportSettingsPanel {
     framerate: KeyListCorners,
     /// The MP3 bit rate field.
     mp3_bit_rate: KeyListCorners,
+    /// The .opus bit rate field.
+    opus_bit_rate: KeyListCorners,
     /// The MP3/ogg quality field.
     quality: KeyListCorners,
     /// String values of multi-file suffixes.
     multi_file_suffixes: ValueMap<MultiFileSuffix>,
+    /// String values of .wav bit depths.
+    wav_bit_depths: ValueMap<WavBitDepth>,
+    /// String values of normalization modes.
+    normalizations: ValueMap<Normalization>,
+    /// The width of the longest loudness target value.
+    loudness_target_width: u32,
+    /// The width of the longest peak target value.
+    peak_target_width: u32,
+    /// The width of the longest velocity floor value.
+    velocity_floor_width: u32,
     /// Panel background sizes per export type.
     backgrounds: HashMap<ExportType, PanelBackground>,
 }
@@ -60,6 +72,12 @@ impl ExportSettingsPanel {
             w,
             6,
         );
+        let opus_bit_rate = KeyListCorners::new(
+            text.get("EXPORT_SETTINGS_PANEL_OPUS_BIT_RATE"),
+            [x, y + 1],
+            w,
+            6,
+        );
 
         let multi_file_suffixes = ValueMap::new(
             [
@@ -74,6 +92,57 @@ impl ExportSettingsPanel {
             ],
             text,
         );
+        let wav_bit_depths = ValueMap::new(
+            [WavBitDepth::Int16, WavBitDepth::Int24, WavBitDepth::Float32],
+            [
+                "EXPORT_SETTINGS_PANEL_WAV_BIT_DEPTH_16",
+                "EXPORT_SETTINGS_PANEL_WAV_BIT_DEPTH_24",
+                "EXPORT_SETTINGS_PANEL_WAV_BIT_DEPTH_32F",
+            ],
+            text,
+        );
+        let normalizations = ValueMap::new(
+            [
+                Normalization::None,
+                Normalization::Loudness,
+                Normalization::Peak,
+            ],
+            [
+                "NONE",
+                "EXPORT_SETTINGS_PANEL_NORMALIZATION_LOUDNESS",
+                "EXPORT_SETTINGS_PANEL_NORMALIZATION_PEAK",
+            ],
+            text,
+        );
+        let loudness_target_width = LOUDNESS_TARGETS
+            .iter()
+            .map(|loudness_target| {
+                text.get_with_values(
+                    "EXPORT_SETTINGS_PANEL_LOUDNESS_TARGET_VALUE",
+                    &[&loudness_target.to_string()],
+                )
+                .chars()
+                .count() as u32
+            })
+            .max()
+            .unwrap();
+        let peak_target_width = PEAK_TARGETS
+            .iter()
+            .map(|peak_target| {
+                text.get_with_values(
+                    "EXPORT_SETTINGS_PANEL_PEAK_TARGET_VALUE",
+                    &[&peak_target.to_string()],
+                )
+                .chars()
+                .count() as u32
+            })
+            .max()
+            .unwrap();
+        let velocity_floor_width = text
+            .get("NONE")
+            .chars()
+            .count()
+            .max(MAX_VOLUME.to_string().chars().count()) as u32;
 
         // Calculate the background sizes per export type.
         let mut backgrounds = HashMap::new();
@@ -89,7 +158,7 @@ impl ExportSettingsPanel {
             ExportType::Mid,
             PanelBackground::new(
                 position,
-                [width, exporter.mid_settings.index.get_length() as u32 + 2],
+                [width, exporter.mid_settings.index.get_length() as u32 + 3],
                 renderer,
             ),
         );
@@ -117,6 +186,14 @@ impl ExportSettingsPanel {
                 renderer,
             ),
         );
+        backgrounds.insert(
+            ExportType::Opus,
+            PanelBackground::new(
+                position,
+                [width, exporter.opus_settings.index.get_length() as u32 + 4],
+                renderer,
+            ),
+        );
 
         Self {
             position,
@@ -125,8 +202,14 @@ impl ExportSettingsPanel {
             title_rect,
             framerate,
             mp3_bit_rate,
+            opus_bit_rate,
             quality,
             multi_file_suffixes,
+            wav_bit_depths,
+            normalizations,
+            loudness_target_width,
+            peak_target_width,
+            velocity_floor_width,
             backgrounds,
         }
     }
@@ -197,6 +280,15 @@ impl ExportSettingsPanel {
                     y = self.quality.key_list.key.position[1] + 1;
                     self.draw_separator((x, &mut y), renderer, &line_color);
                 }
+                ExportSetting::OpusBitRate => {
+                    renderer.key_list_corners(
+                        &(OPUS_BIT_RATES[exporter.opus_bit_rate.get()] as u32 * 1000).to_string(),
+                        &self.opus_bit_rate,
+                        setting_focus,
+                    );
+                    y = self.opus_bit_rate.key_list.key.position[1] + 1;
+                    self.draw_separator((x, &mut y), renderer, &line_color);
+                }
                 ExportSetting::Title => {
                     let key_input = KeyInput::new_from_padding(
                         text.get_ref("EXPORT_SETTINGS_PANEL_TITLE"),
@@ -234,6 +326,10 @@ impl ExportSettingsPanel {
                         text,
                         setting_focus,
                     );
+                    // For .mid files, this is the last of the metadata. Draw a line.
+                    if export_type == ExportType::Mid {
+                        self.draw_separator((x, &mut y), renderer, &line_color);
+                    }
                 }
                 ExportSetting::Album => self.draw_optional_input(
                     text.get_ref("EXPORT_SETTINGS_PANEL_ALBUM"),
@@ -287,6 +383,107 @@ impl ExportSettingsPanel {
                     text,
                     setting_focus,
                 ),
+                ExportSetting::MultiFileTail => self.draw_boolean(
+                    text.get("EXPORT_SETTINGS_PANEL_MULTI_FILE_TAIL"),
+                    exporter.multi_file_tail,
+                    (x, &mut y),
+                    renderer,
+                    text,
+                    setting_focus,
+                ),
+                ExportSetting::SelectionOnly => self.draw_boolean(
+                    text.get("EXPORT_SETTINGS_PANEL_SELECTION_ONLY"),
+                    exporter.selection_only,
+                    (x, &mut y),
+                    renderer,
+                    text,
+                    setting_focus,
+                ),
+                ExportSetting::Normalization => {
+                    let value = self.normalizations.get(&exporter.normalization.get());
+                    let key_list = KeyListCorners::new(
+                        text.get("EXPORT_SETTINGS_PANEL_NORMALIZATION"),
+                        [x, y],
+                        self.width - 2,
+                        self.normalizations.max_length,
+                    );
+                    renderer.key_list_corners(value, &key_list, setting_focus);
+                    y += 1;
+                }
+                ExportSetting::LoudnessTarget => {
+                    let value = text.get_with_values(
+                        "EXPORT_SETTINGS_PANEL_LOUDNESS_TARGET_VALUE",
+                        &[&exporter.get_loudness_target().to_string()],
+                    );
+                    let key_list = KeyListCorners::new(
+                        text.get("EXPORT_SETTINGS_PANEL_LOUDNESS_TARGET"),
+                        [x, y],
+                        self.width - 2,
+                        self.loudness_target_width,
+                    );
+                    renderer.key_list_corners(&value, &key_list, setting_focus);
+                    y += 1;
+                }
+                ExportSetting::PeakTarget => {
+                    let value = text.get_with_values(
+                        "EXPORT_SETTINGS_PANEL_PEAK_TARGET_VALUE",
+                        &[&exporter.get_peak_target().to_string()],
+                    );
+                    let key_list = KeyListCorners::new(
+                        text.get("EXPORT_SETTINGS_PANEL_PEAK_TARGET"),
+                        [x, y],
+                        self.width - 2,
+                        self.peak_target_width,
+                    );
+                    renderer.key_list_corners(&value, &key_list, setting_focus);
+                    y += 1;
+                }
+                ExportSetting::Limiter => self.draw_boolean(
+                    text.get("EXPORT_SETTINGS_PANEL_LIMITER"),
+                    exporter.limiter,
+                    (x, &mut y),
+                    renderer,
+                    text,
+                    setting_focus,
+                ),
+                ExportSetting::Channels => self.draw_boolean(
+                    text.get("EXPORT_SETTINGS_PANEL_MONO"),
+                    exporter.is_mono(),
+                    (x, &mut y),
+                    renderer,
+                    text,
+                    setting_focus,
+                ),
+                ExportSetting::Dither => self.draw_boolean(
+                    text.get("EXPORT_SETTINGS_PANEL_DITHER"),
+                    exporter.dither,
+                    (x, &mut y),
+                    renderer,
+                    text,
+                    setting_focus,
+                ),
+                ExportSetting::MidiRunningStatus => self.draw_boolean(
+                    text.get("EXPORT_SETTINGS_PANEL_MIDI_RUNNING_STATUS"),
+                    exporter.midi_running_status,
+                    (x, &mut y),
+                    renderer,
+                    text,
+                    setting_focus,
+                ),
+                ExportSetting::VelocityFloor => {
+                    let value = match exporter.velocity_floor.get() {
+                        0 => text.get("NONE"),
+                        velocity_floor => velocity_floor.to_string(),
+                    };
+                    let key_list = KeyListCorners::new(
+                        text.get("EXPORT_SETTINGS_PANEL_VELOCITY_FLOOR"),
+                        [x, y],
+                        self.width - 2,
+                        self.velocity_floor_width,
+                    );
+                    renderer.key_list_corners(&value, &key_list, setting_focus);
+                    y += 1;
+                }
                 ExportSetting::MultiFileSuffix => {
                     let value = self
                         .multi_file_suffixes
@@ -300,6 +497,17 @@ impl ExportSettingsPanel {
                     renderer.key_list_corners(value, &key_list, setting_focus);
                     y += 1;
                 }
+                ExportSetting::WavBitDepth => {
+                    let value = self.wav_bit_depths.get(&exporter.wav_bit_depth.get());
+                    let key_list = KeyListCorners::new(
+                        text.get("EXPORT_SETTINGS_PANEL_WAV_BIT_DEPTH"),
+                        [x, y],
+                        self.width - 2,
+                        self.wav_bit_depths.max_length,
+                    );
+                    renderer.key_list_corners(value, &key_list, setting_focus);
+                    y += 1;
+                }
             }
         }
     }
@@ -420,6 +628,14 @@ impl Drawable for ExportSettingsPanel {
                 &conn.exporter,
                 focus,
             ),
+            ExportType::Opus => self.update_settings(
+                |e| &e.opus_settings,
+                renderer,
+                state,
+                text,
+                &conn.exporter,
+                focus,
+            ),
         }
     }
 }
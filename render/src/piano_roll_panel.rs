@@ -7,10 +7,10 @@ mod multi_track;
 mod top_bar;
 mod viewable_notes;
 mod volume;
-use common::{SelectMode, State, U64orF32, NOTE_NAMES, PPQ_U};
+use common::{SelectMode, State, Time, U64orF32, NOTE_NAMES, PPQ_U};
 use hashbrown::HashSet;
 use multi_track::MultiTrack;
-use text::ppq_to_string;
+use text::position_to_string;
 use top_bar::TopBar;
 use viewable_notes::{ViewableNote, ViewableNotes};
 use volume::Volume;
@@ -43,6 +43,8 @@ pub struct PianoRollPanel {
     time_horizontal_line_y: f32,
     /// The bottom y coordinates for time lines in single- and multi- track modes.
     time_line_bottoms: [f32; 2],
+    /// The minimum width of a note in pixels.
+    min_note_width: f32,
 }
 
 impl PianoRollPanel {
@@ -84,6 +86,7 @@ impl PianoRollPanel {
         let time_y = note_names_position[1] - 1;
         let time_horizontal_line_y = cell_size[1] * (time_y + 1) as f32;
         let volume = Volume::new(config, text, renderer);
+        let min_note_width = get_min_note_width(config);
         let multi_track = MultiTrack::new(config, renderer);
         let mut panel_multi_track = panel_single_track.clone();
         panel_multi_track
@@ -108,6 +111,7 @@ impl PianoRollPanel {
             volume,
             multi_track,
             time_line_bottoms,
+            min_note_width,
         }
     }
 
@@ -195,6 +199,11 @@ impl PianoRollPanel {
     fn get_play_state(play_state: &SharedPlayState) -> PlayState {
         *play_state.lock()
     }
+
+    /// Returns the whole beat of time `t` relative to the start of the song. This is negative in the pickup.
+    fn get_beat(t: u64, time: &Time) -> i64 {
+        (t as i64 - time.song_start as i64).div_euclid(PPQ_U as i64)
+    }
 }
 
 impl Drawable for PianoRollPanel {
@@ -231,6 +240,7 @@ impl Drawable for PianoRollPanel {
                 conn,
                 focus,
                 dt,
+                self.min_note_width,
             );
             // Draw the selection background.
             let selected = notes
@@ -313,7 +323,7 @@ impl Drawable for PianoRollPanel {
             panel.background.grid_rect.position[0] + PIANO_ROLL_PANEL_NOTE_NAMES_WIDTH + 1;
         let cursor_string = text.get_with_values(
             "PIANO_ROLL_PANEL_CURSOR_TIME",
-            &[&ppq_to_string(state.time.cursor)],
+            &[&position_to_string(state.time.cursor, &state.time)],
         );
         let cursor_string_width = cursor_string.chars().count() as u32;
         let playback_x = cursor_x + cursor_string_width + TIME_PADDING;
@@ -334,7 +344,7 @@ impl Drawable for PianoRollPanel {
         };
         let playback_string = text.get_with_values(
             "PIANO_ROLL_PANEL_PLAYBACK_TIME",
-            &[&ppq_to_string(state.time.playback)],
+            &[&position_to_string(state.time.playback, &state.time)],
         );
         let playback_string_width = playback_string.chars().count() as u32;
         let playback_line_x0 = playback_x + playback_string_width / 2;
@@ -346,7 +356,10 @@ impl Drawable for PianoRollPanel {
                     (
                         text.get_with_values(
                             "PIANO_ROLL_PANEL_SELECTED_SINGLE",
-                            &[note.get_name(), &(note.start / PPQ_U).to_string()],
+                            &[
+                                note.get_name(),
+                                &Self::get_beat(note.start, &state.time).to_string(),
+                            ],
                         ),
                         true,
                     )
@@ -357,8 +370,8 @@ impl Drawable for PianoRollPanel {
                 Some(_) => {
                     let mut notes = state.select_mode.get_notes(&state.music).unwrap();
                     notes.sort();
-                    let min = notes[0].start / PPQ_U;
-                    let max = notes.last().unwrap().end / PPQ_U;
+                    let min = Self::get_beat(notes[0].start, &state.time);
+                    let max = Self::get_beat(notes.last().unwrap().end, &state.time);
                     (
                         text.get_with_values(
                             "PIANO_ROLL_PANEL_SELECTED_MANY",
@@ -382,8 +395,10 @@ impl Drawable for PianoRollPanel {
         // Current playback time.
         let play_state = Self::get_play_state(&conn.play_state);
         if let PlayState::Playing(samples) = play_state {
-            let music_time_string =
-                ppq_to_string(state.time.samples_to_ppq(samples, conn.framerate));
+            let music_time_string = position_to_string(
+                state.time.samples_to_ppq(samples, conn.framerate),
+                &state.time,
+            );
             let music_time_x =
                 selection_x + selection_label.text.chars().count() as u32 + TIME_PADDING;
             let music_time_label = Label {
@@ -408,7 +423,10 @@ impl Drawable for PianoRollPanel {
         // Time delta label.
         let dt_string = text.get_with_values(
             "PIANO_ROLL_PANEL_VIEW_DT",
-            &[&ppq_to_string(dt[0].get_u()), &ppq_to_string(dt[1].get_u())],
+            &[
+                &position_to_string(dt[0].get_u(), &state.time),
+                &position_to_string(dt[1].get_u(), &state.time),
+            ],
         );
         let dt_x = panel.background.grid_rect.position[0] + panel.background.grid_rect.size[0]
             - dt_string.chars().count() as u32
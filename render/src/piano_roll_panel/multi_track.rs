@@ -2,10 +2,10 @@ use super::viewable_notes::{ViewableNote, ViewableNotes};
 use crate::panel::*;
 use crate::{get_track_heights, Page};
 use common::config::parse;
-use common::{U64orF32, MAX_NOTE, MIN_NOTE};
+use common::{U64orF32, MAX_NOTE, MIN_NOTE, NUM_TRACK_COLORS};
 
 /// Track colors for when the panel has focus.
-const TRACK_COLORS_FOCUS: [ColorKey; 6] = [
+const TRACK_COLORS_FOCUS: [ColorKey; NUM_TRACK_COLORS] = [
     ColorKey::Track0Focus,
     ColorKey::Track1Focus,
     ColorKey::Track2Focus,
@@ -14,7 +14,7 @@ const TRACK_COLORS_FOCUS: [ColorKey; 6] = [
     ColorKey::Track5Focus,
 ];
 /// Track colors for when the panel doesn't have focus.
-const TRACK_COLORS_NO_FOCUS: [ColorKey; 6] = [
+const TRACK_COLORS_NO_FOCUS: [ColorKey; NUM_TRACK_COLORS] = [
     ColorKey::Track0NoFocus,
     ColorKey::Track1NoFocus,
     ColorKey::Track2NoFocus,
@@ -37,6 +37,8 @@ pub(crate) struct MultiTrack {
     note_height: f32,
     /// The string used for drawing an arrow.
     arrow: Label,
+    /// The minimum width of a note in pixels.
+    min_note_width: f32,
 }
 
 impl MultiTrack {
@@ -65,11 +67,13 @@ impl MultiTrack {
             position: [piano_roll_panel_position[0] - 1, position[1]],
             text: arrow_text,
         };
+        let min_note_width = get_min_note_width(config);
         Self {
             rect,
             rect_f,
             note_height,
             arrow,
+            min_note_width,
         }
     }
 
@@ -87,11 +91,13 @@ impl MultiTrack {
         let x = self.rect.position[0];
         let mut y = self.rect.position[1];
         let w = self.rect.size[0];
-        let mut color_index = 0;
         // Iterate through the heights and indices.
         for (height, i) in track_heights.iter().zip(page) {
+            // Get the track.
+            let track = &state.music.midi_tracks[i];
             // Draw a rectangle.
             let rect = Rectangle::new([x, y], [w, *height]);
+            let color_index = track.get_color_index(i);
             let color = if focus {
                 TRACK_COLORS_FOCUS[color_index]
             } else {
@@ -106,13 +112,6 @@ impl MultiTrack {
                     renderer.text(&arrow, &color);
                 }
             }
-            // Increment the color index.
-            color_index += 1;
-            if color_index >= TRACK_COLORS_FOCUS.len() {
-                color_index = 0;
-            }
-            // Get the track.
-            let track = &state.music.midi_tracks[i];
             // Get the viewable notes.
             let notes = ViewableNotes::new_from_track(
                 self.rect_f[0],
@@ -123,6 +122,7 @@ impl MultiTrack {
                 focus,
                 dt,
                 DN,
+                self.min_note_width,
             );
             // Draw the selection background.
             let selected = notes
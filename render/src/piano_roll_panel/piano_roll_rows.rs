@@ -22,8 +22,6 @@ pub(crate) struct PianoRollRows {
     view: View,
     /// As far as this struct knows, this is whether the piano roll panel has focus.
     focus: bool,
-    /// As far as this struct knows, this is the input beat.
-    beat: U64orF32,
     /// The row texture.
     texture: Texture2D,
 }
@@ -63,7 +61,6 @@ impl PianoRollRows {
             rect,
             view: state.view.clone(),
             focus: false,
-            beat: state.input.beat,
             texture,
         }
     }
@@ -81,12 +78,9 @@ impl PianoRollRows {
     /// Check if we need to re-define the row pattern and, if so, do it.
     pub fn late_update(&mut self, state: &State, renderer: &Renderer) {
         let focus = state.panels[state.focus.get()] == PanelType::PianoRoll;
-        // The focus or the view changed.
-        if state.view.single_track
-            && (focus != self.focus || self.beat != state.input.beat || state.view != self.view)
-        {
+        // The focus or the view changed. The view includes the grid subdivision.
+        if state.view.single_track && (focus != self.focus || state.view != self.view) {
             self.focus = focus;
-            self.beat = state.input.beat;
             self.view = state.view.clone();
             Self::set_row_texture(
                 &mut self.texture,
@@ -118,7 +112,7 @@ impl PianoRollRows {
         let t1 = dt[1].get_u() - dt[0].get_u();
         let ppp = ViewableNotes::get_pulses_per_pixel(&dt, w);
         let line_segment_width = ViewableNotes::get_note_x(
-            state.input.beat.get_u(),
+            state.view.grid_subdivision,
             ppp,
             0.0,
             &[U64orF32::from(0), U64orF32::from(t1)],
@@ -1,5 +1,4 @@
 use crate::panel::*;
-use audio::play_state::PlayState;
 use common::*;
 
 /// A viewable note.
@@ -28,6 +27,8 @@ pub(crate) struct ViewableNotes<'a> {
     dt: [U64orF32; 2],
     /// The number of pulses in 1 pixel.
     pub pulses_per_pixel: u64,
+    /// The minimum width of a note in pixels.
+    min_note_w: f32,
 }
 
 impl<'a> ViewableNotes<'a> {
@@ -37,6 +38,7 @@ impl<'a> ViewableNotes<'a> {
     /// - `conn` The audio conn.
     /// - `focus` If true, the piano roll panel has focus.
     /// - `dt` The time delta.
+    /// - `min_note_w` The minimum width of a note in pixels.
     pub fn new(
         x: f32,
         w: f32,
@@ -44,13 +46,25 @@ impl<'a> ViewableNotes<'a> {
         conn: &Conn,
         focus: bool,
         dt: [U64orF32; 2],
+        min_note_w: f32,
     ) -> Self {
         match state.music.get_selected_track() {
-            Some(track) => Self::new_from_track(x, w, track, state, conn, focus, dt, state.view.dn),
+            Some(track) => Self::new_from_track(
+                x,
+                w,
+                track,
+                state,
+                conn,
+                focus,
+                dt,
+                state.view.dn,
+                min_note_w,
+            ),
             None => Self {
                 pulses_per_pixel: Self::get_pulses_per_pixel(&dt, w),
                 notes: vec![],
                 dt,
+                min_note_w,
             },
         }
     }
@@ -64,6 +78,7 @@ impl<'a> ViewableNotes<'a> {
     /// - `focus` If true, the piano roll panel has focus.
     /// - `dt` The time delta.
     /// - `dn` The range of viewable note pitches.
+    /// - `min_note_w` The minimum width of a note in pixels.
     #[allow(clippy::too_many_arguments)]
     pub fn new_from_track(
         x: f32,
@@ -74,13 +89,11 @@ impl<'a> ViewableNotes<'a> {
         focus: bool,
         dt: [U64orF32; 2],
         dn: [u8; 2],
+        min_note_w: f32,
     ) -> Self {
         let pulses_per_pixel = Self::get_pulses_per_pixel(&dt, w);
         // Get any notes being played.
-        let playtime = match *conn.play_state.lock() {
-            PlayState::Playing(time) => Some(state.time.samples_to_ppq(time, conn.framerate)),
-            _ => None,
-        };
+        let playtime = conn.playback_position_ppq(&state.time);
 
         // Get the selected notes.
         let selected = match state.select_mode.get_notes(&state.music) {
@@ -135,10 +148,13 @@ impl<'a> ViewableNotes<'a> {
             notes,
             dt,
             pulses_per_pixel,
+            min_note_w,
         }
     }
 
     /// Returns the width of a note.
+    /// Short notes are widened to the minimum note width, but never past the end of the viewport.
+    /// Anything that needs to know where a note is on screen should use this width.
     pub fn get_note_w(&self, note: &ViewableNote) -> f32 {
         let t0 = if note.note.start < self.dt[0].get_u() {
             self.dt[0].get_u()
@@ -150,7 +166,14 @@ impl<'a> ViewableNotes<'a> {
         } else {
             note.note.end
         };
-        ((t1 - t0) / self.pulses_per_pixel) as f32
+        let w = ((t1 - t0) / self.pulses_per_pixel) as f32;
+        if w >= self.min_note_w {
+            w
+        } else {
+            // The distance between the start of the note and the end of the viewport.
+            let max_w = ((self.dt[1].get_u() - t0) / self.pulses_per_pixel) as f32;
+            self.min_note_w.min(max_w)
+        }
     }
 
     /// Returns the x pixel coordinate corresonding with time `t` within the viewport defined by `x`, `w` and `dt`.
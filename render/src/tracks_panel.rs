@@ -136,7 +136,7 @@ impl Drawable for TracksPanel {
                     y += 1;
                     // Draw the gain.
                     let gain = KeyList::new(self.gain_key.clone(), [x + 1, y], self.field_width, 3);
-                    renderer.key_list(&track.gain.to_string(), &gain, f);
+                    renderer.key_list(&text.get_gain(track.gain, false), &gain, f);
                     // Mute.
                     if track.mute {
                         let mute_position = [x + self.field_width - MUTE_OFFSET, y];
@@ -1,9 +1,10 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use audio::Conn;
+use audio::export::PostExport;
+use audio::{Conn, ConnSettings};
 use clap::Parser;
 use common::args::Args;
-use common::config::{load, parse_bool};
+use common::config::{load, parse, parse_bool};
 use common::sizes::get_window_pixel_size;
 use common::{get_bytes, Paths, PathsState, State, VERSION};
 use ini::Ini;
@@ -12,6 +13,7 @@ use io::IO;
 use macroquad::prelude::*;
 use regex::Regex;
 use render::{draw_subtitles, Panels, Renderer};
+use std::time::Duration;
 use text::{Text, TTS};
 use ureq::get;
 
@@ -63,7 +65,18 @@ async fn main() {
     let mut input = Input::new(&config, &args);
 
     // Create the audio connection.
-    let mut conn = Conn::default();
+    let audio_section = config.section(Some("AUDIO")).unwrap();
+    let export_section = config.section(Some("EXPORT")).unwrap();
+    let mut conn = Conn::new(ConnSettings {
+        output_device: audio_section.get("output_device").map(|s| s.to_string()),
+        gain_ramp_duration: Duration::from_millis(parse(audio_section, "gain_ramp_ms")),
+        limiter_ceiling: parse(export_section, "limiter_ceiling"),
+        post_export: export_section
+            .get("post_export_command")
+            .and_then(PostExport::new),
+        empty_export_silence: parse(export_section, "empty_export_silence"),
+        synth_headroom: parse(audio_section, "synth_headroom"),
+    });
 
     // Create the state.
     let mut state = State::new(&config);
@@ -131,7 +144,7 @@ async fn main() {
             done = io.update(
                 &mut state,
                 &mut conn,
-                &input,
+                &mut input,
                 &mut tts,
                 &mut text,
                 &mut paths_state,
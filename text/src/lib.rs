@@ -11,8 +11,11 @@ pub use self::tts::{Enqueable, TTS};
 use std::path::Path;
 pub use value_map::ValueMap;
 mod tts_string;
-use common::config::parse;
-use common::{EditMode, Paths, PianoRollMode, Time, MIN_NOTE, PPQ_F, PPQ_U};
+use common::config::{parse, parse_bool};
+use common::{
+    Articulation, EditMode, NoteRangeMode, Paths, PianoRollMode, Time, MAX_VOLUME, MIN_NOTE, PPQ_F,
+    PPQ_U,
+};
 use csv::Reader;
 use hashbrown::HashMap;
 use ini::Ini;
@@ -21,6 +24,8 @@ use macroquad::input::KeyCode;
 pub use tooltips::Tooltips;
 pub use tts_string::TtsString;
 
+/// The pitch of the first drum in `DRUM_NAMES`. This is the General MIDI percussion key map.
+const FIRST_DRUM_NOTE: u8 = 35;
 /// All possible languages.
 const LANGUAGES: [&str; 1] = ["en"];
 /// Keycode lookup string prefixes.
@@ -164,8 +169,14 @@ pub struct Text {
     piano_roll_modes: HashMap<PianoRollMode, String>,
     /// The name of each MIDI note.
     note_names: Vec<String>,
+    /// The name of each drum on the percussion channel, starting at `FIRST_DRUM_NOTE`.
+    drum_names: Vec<String>,
+    /// The percussion channel, if any.
+    percussion_channel: Option<u8>,
     /// Boolean dislay
     booleans: ValueMap<bool>,
+    /// If true, track gain is displayed in dB instead of as a raw value.
+    gain_db: bool,
 }
 
 impl Text {
@@ -189,6 +200,18 @@ impl Text {
             .split(", ")
             .map(|s| s.to_string())
             .collect();
+        let drum_names: Vec<String> = text
+            .remove("DRUM_NAMES")
+            .unwrap()
+            .split(", ")
+            .map(|s| s.to_string())
+            .collect();
+        let percussion_channel: i16 = parse(
+            config.section(Some("TRACKS")).unwrap(),
+            "percussion_channel",
+        );
+        let percussion_channel = u8::try_from(percussion_channel).ok();
+        let gain_db = parse_bool(config.section(Some("TRACKS")).unwrap(), "gain_db");
         let keycodes_spoken = Text::get_keycode_map(&text, true);
         let keycodes_seen = Text::get_keycode_map(&text, false);
         let edit_modes = Text::get_edit_mode_map(&text);
@@ -207,7 +230,10 @@ impl Text {
             edit_modes,
             piano_roll_modes,
             note_names,
+            drum_names,
+            percussion_channel,
             booleans,
+            gain_db,
         }
     }
 
@@ -277,6 +303,42 @@ impl Text {
         }
     }
 
+    /// Returns the string version of a track gain value.
+    /// If `self.gain_db` is true, this is the gain in dB, rounded to the nearest integer. Otherwise, this is the raw value.
+    ///
+    /// - `gain` The track gain.
+    /// - `spoken` If true, the dB value is followed by a spoken unit.
+    pub fn get_gain(&self, gain: u8, spoken: bool) -> String {
+        if !self.gain_db {
+            return gain.to_string();
+        }
+        match (gain_to_db(gain), spoken) {
+            (Some(db), true) => self.get_with_values("GAIN_DB_TTS", &[&db.to_string()]),
+            (Some(db), false) => db.to_string(),
+            (None, true) => self.get("GAIN_DB_SILENT_TTS"),
+            (None, false) => self.get("GAIN_DB_SILENT"),
+        }
+    }
+
+    /// Returns the string version of an articulation.
+    pub fn get_articulation(&self, articulation: &Option<Articulation>) -> &str {
+        self.get_ref(match articulation {
+            None => "ARTICULATION_NONE",
+            Some(Articulation::Staccato) => "ARTICULATION_STACCATO",
+            Some(Articulation::Accent) => "ARTICULATION_ACCENT",
+            Some(Articulation::Tenuto) => "ARTICULATION_TENUTO",
+        })
+    }
+
+    /// Returns the string version of a note range mode.
+    pub fn get_note_range_mode(&self, mode: &Option<NoteRangeMode>) -> &str {
+        self.get_ref(match mode {
+            None => "NOTE_RANGE_MODE_NONE",
+            Some(NoteRangeMode::Clamp) => "NOTE_RANGE_MODE_CLAMP",
+            Some(NoteRangeMode::Fold) => "NOTE_RANGE_MODE_FOLD",
+        })
+    }
+
     /// Returns boolean text.
     pub fn get_boolean(&self, value: &bool) -> &str {
         self.booleans.get(value)
@@ -332,6 +394,18 @@ impl Text {
         }
     }
 
+    /// Returns a text-to-speech string of a position in the music, relative to the start of the song (see `Time.song_start`).
+    pub fn get_position_tts(&self, ppq: u64, time: &Time) -> String {
+        if ppq >= time.song_start {
+            self.get_ppq_tts(&(ppq - time.song_start))
+        } else {
+            self.get_with_values(
+                "POSITION_TTS_PICKUP",
+                &[&self.get_ppq_tts(&(time.song_start - ppq))],
+            )
+        }
+    }
+
     /// Returns an error text-to-speech string.
     pub fn get_error(&self, error: &str) -> String {
         self.get_with_values("ERROR", &[error])
@@ -342,6 +416,17 @@ impl Text {
         &self.note_names[(note - MIN_NOTE) as usize]
     }
 
+    /// Returns the name of a note on a channel.
+    /// On the percussion channel, this is the name of the drum, if there is one. Otherwise, this is the name of the pitch.
+    pub fn get_channel_note_name(&self, note: u8, channel: u8) -> &str {
+        if self.percussion_channel == Some(channel) && note >= FIRST_DRUM_NOTE {
+            if let Some(drum_name) = self.drum_names.get((note - FIRST_DRUM_NOTE) as usize) {
+                return drum_name;
+            }
+        }
+        self.get_note_name(note)
+    }
+
     /// Returns a map of keycodes to displayable/sayable text (NOT string keys).
     fn get_keycode_map(text: &HashMap<String, String>, spoken: bool) -> HashMap<KeyCode, String> {
         let suffix = if spoken { "_SPOKEN" } else { "_SEEN" };
@@ -377,6 +462,15 @@ impl Text {
     }
 }
 
+/// Converts a gain value between 0 and `MAX_VOLUME` to dB, rounded to the nearest integer. Returns None if the gain is 0.
+pub fn gain_to_db(gain: u8) -> Option<i32> {
+    if gain == 0 {
+        None
+    } else {
+        Some((20.0 * (gain as f32 / MAX_VOLUME as f32).log10()).round() as i32)
+    }
+}
+
 /// Converts a PPQ value into a string beat value.
 pub fn ppq_to_string(ppq: u64) -> String {
     // This is a whole note.
@@ -397,6 +491,15 @@ pub fn ppq_to_string(ppq: u64) -> String {
     }
 }
 
+/// Converts a position in the music into a string beat value, relative to the start of the song (see `Time.song_start`).
+pub fn position_to_string(ppq: u64, time: &Time) -> String {
+    if ppq >= time.song_start {
+        ppq_to_string(ppq - time.song_start)
+    } else {
+        format!("-{}", ppq_to_string(time.song_start - ppq))
+    }
+}
+
 /// Truncate a string to fit a specified length.
 ///
 /// - `string` The string.